@@ -0,0 +1,117 @@
+//! The work done for every report of every tracker: converting the raw IMU samples, running the
+//! fusion, turning the rotation into the output frame and serializing the packets for the server.
+//!
+//! `cargo bench -p slimevr-wrangler-core -- --save-baseline before` before a change and
+//! `-- --baseline before` after it shows what the change did, see the README for the numbers of
+//! the current code.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use nalgebra::{UnitQuaternion, Vector3};
+use slimevr_wrangler_core::fusion::{
+    euler_angles, in_frame, EulerOrder, Fusion, Handedness, Imu, JoyconAxisData, RawConversion,
+};
+use slimevr_wrangler_core::protocol::{
+    deku::DekuContainerWrite, Bundle, PacketBuffer, PacketType,
+};
+
+// A report of a Joy-Con lying still, slightly tilted, with some noise on the gyro.
+const RAW_REPORT: [[i16; 6]; 3] = [
+    [312, -45, 4090, 12, -8, 3],
+    [309, -41, 4093, 9, -11, 5],
+    [315, -47, 4088, 14, -6, 2],
+];
+
+fn frame(i: usize) -> JoyconAxisData {
+    let t = i as f64 * 0.005;
+    JoyconAxisData {
+        accel_x: 0.05 * t.sin(),
+        accel_y: 0.02,
+        accel_z: 1.0,
+        gyro_x: 0.3 * t.cos(),
+        gyro_y: 0.1,
+        gyro_z: -0.2 * t.sin(),
+    }
+}
+
+fn rotation() -> UnitQuaternion<f64> {
+    UnitQuaternion::from_euler_angles(0.3, -0.2, 1.1)
+}
+
+fn imu_conversion(c: &mut Criterion) {
+    let conversion = RawConversion::new(&([350, -20, 11], [-6, 14, 3]), true, 1.02);
+    let mut group = c.benchmark_group("imu conversion");
+    group.bench_function("batched", |b| {
+        b.iter(|| conversion.convert(black_box(&RAW_REPORT)))
+    });
+    group.bench_function("per frame", |b| {
+        b.iter(|| conversion.convert_per_frame(black_box(&RAW_REPORT)))
+    });
+    group.finish();
+}
+
+fn fusion_update(c: &mut Criterion) {
+    let mut group = c.benchmark_group("fusion update");
+    for fusion in Fusion::ALL {
+        let mut imu = Imu::new(fusion);
+        let mut i = 0;
+        group.bench_function(format!("{fusion:?}"), |b| {
+            b.iter(|| {
+                i += 1;
+                imu.update(black_box(frame(i)));
+            })
+        });
+    }
+    group.finish();
+}
+
+fn quaternion_ops(c: &mut Criterion) {
+    let rotation = rotation();
+    let offset = UnitQuaternion::from_axis_angle(&Vector3::z_axis(), 0.7);
+    let mounting = UnitQuaternion::from_euler_angles(0.0, std::f64::consts::FRAC_PI_2, 0.0);
+    let mut group = c.benchmark_group("quaternion");
+    group.bench_function("output rotation", |b| {
+        b.iter(|| black_box(offset) * black_box(rotation) * black_box(mounting))
+    });
+    group.bench_function("left-handed frame", |b| {
+        b.iter(|| in_frame(black_box(&rotation), Handedness::Left))
+    });
+    group.bench_function("euler angles", |b| {
+        b.iter(|| euler_angles(black_box(&rotation), EulerOrder::Zyx))
+    });
+    group.finish();
+}
+
+fn packet_serialization(c: &mut Criterion) {
+    let packet = PacketType::RotationData {
+        packet_id: 1234,
+        sensor_id: 0,
+        data_type: 1,
+        quat: (*rotation().quaternion()).into(),
+        calibration_info: 0,
+    };
+    let mut group = c.benchmark_group("packet serialization");
+    group.bench_function("to_bytes", |b| b.iter(|| black_box(&packet).to_bytes()));
+    let mut buffer = PacketBuffer::default();
+    group.bench_function("write_to", |b| {
+        b.iter(|| black_box(&packet).write_to(&mut buffer).map(|bytes| bytes.len()))
+    });
+    let mut bundle = Bundle::default();
+    group.bench_function("bundle of 8", |b| {
+        b.iter(|| {
+            for _ in 0..8 {
+                bundle.push(black_box(&packet)).ok();
+            }
+            bundle.take(1234)
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    imu_conversion,
+    fusion_update,
+    quaternion_ops,
+    packet_serialization
+);
+criterion_main!(benches);
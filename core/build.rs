@@ -0,0 +1,20 @@
+fn main() {
+    // protox compiles the proto file in Rust, so building doesn't need protoc installed.
+    #[cfg(feature = "grpc")]
+    {
+        let descriptors = protox::Compiler::new(["proto"])
+            .unwrap()
+            .include_imports(true)
+            .open_files(["wrangler.proto"])
+            .unwrap()
+            .encode_file_descriptor_set();
+        let path = std::path::PathBuf::from(std::env::var("OUT_DIR").unwrap()).join("wrangler.bin");
+        std::fs::write(&path, descriptors).unwrap();
+        tonic_build::configure()
+            .build_client(false)
+            .file_descriptor_set_path(&path)
+            .skip_protoc_run()
+            .compile(&["proto/wrangler.proto"], &["proto"])
+            .unwrap();
+    }
+}
@@ -0,0 +1,199 @@
+//! Six-sided calibration of the accelerometer. The tracker is laid on each of its sides in turn,
+//! a side is taken once it lay still for a moment, and the offset and scale of each axis are
+//! fitted so all six readings measure 1G.
+//!
+//! The factory calibration only has the origin of each axis, which leaves some controllers a few
+//! degrees off in tilt. The fit doesn't need the sides to lie exactly flat, so a Joy-Con tipped
+//! over by its stick still counts.
+
+use std::time::{Duration, Instant};
+
+use nalgebra::{Matrix6, Vector3, Vector6};
+
+use super::imu::JoyconAxisData;
+use crate::settings::AccelCalibration;
+
+// A side is taken after lying still this long, within these of where it came to rest.
+const SIDE_REST: Duration = Duration::from_secs(1);
+const REST_GYRO: f64 = 0.05; // radians/s, 3°/s
+const REST_ACCEL: f64 = 0.03; // G
+
+// Gravity has to be at least this share along one axis to tell which side the tracker lies on,
+// about 35° off at most.
+const SIDE_SHARE: f64 = 0.82;
+const FIT_ITERATIONS: usize = 10;
+// Further off than this comes from a tracker that moved, not from the sensor.
+const MAX_OFFSET: f64 = 0.2; // G
+const MAX_SCALE_ERROR: f64 = 0.2;
+
+/// One run of the calibration, fed the frames of a tracker until [`AccelMeasurement::result`].
+#[derive(Debug, Default)]
+pub(crate) struct AccelMeasurement {
+    /// Mean acceleration on each side, `+x, -x, +y, -y, +z, -z` of the axis gravity is along.
+    sides: [Option<Vector3<f64>>; 6],
+    /// Since when it lies still, the acceleration then and the sum of it since.
+    rest: Option<(Instant, Vector3<f64>, Vector3<f64>, usize)>,
+}
+
+impl AccelMeasurement {
+    /// Learns from the frames of a report, uncorrected.
+    pub fn add(&mut self, frames: &[JoyconAxisData; 3]) {
+        let mean = JoyconAxisData::mean(frames);
+        let gyro = Vector3::new(mean.gyro_x, mean.gyro_y, mean.gyro_z);
+        let acc = Vector3::new(mean.accel_x, mean.accel_y, mean.accel_z);
+        let still = gyro.norm() < REST_GYRO
+            && self
+                .rest
+                .map_or(true, |(_, start, _, _)| (acc - start).norm() < REST_ACCEL);
+        if !still {
+            self.rest = None;
+            return;
+        }
+        let (since, _, sum, samples) =
+            self.rest
+                .get_or_insert((Instant::now(), acc, Vector3::zeros(), 0));
+        *sum += acc;
+        *samples += 1;
+        if since.elapsed() < SIDE_REST {
+            return;
+        }
+        let mean = *sum / *samples as f64;
+        if let Some(side) = side(&mean) {
+            self.sides[side].get_or_insert(mean);
+        }
+    }
+    /// Sides taken so far, out of six.
+    pub fn sides(&self) -> usize {
+        self.sides.iter().flatten().count()
+    }
+    /// Once all sides are taken, the fitted calibration, `None` if it came out implausible.
+    pub fn result(&self) -> Option<Option<AccelCalibration>> {
+        let sides: Vec<_> = self.sides.iter().copied().collect::<Option<_>>()?;
+        Some(fit(&sides))
+    }
+}
+
+// Index into the sides of the axis gravity is mostly along, if it is.
+fn side(acc: &Vector3<f64>) -> Option<usize> {
+    let axis = acc.iamax();
+    let along = acc[axis];
+    (along.abs() >= acc.norm() * SIDE_SHARE).then(|| axis * 2 + usize::from(along < 0.0))
+}
+
+/// Offset and scale that make all of `sides` 1G, by Gauss-Newton from the classic six-position
+/// estimate, which would be exact for sides lying perfectly flat.
+fn fit(sides: &[Vector3<f64>]) -> Option<AccelCalibration> {
+    let (mut offset, mut scale) = (Vector3::zeros(), Vector3::zeros());
+    for axis in 0..3 {
+        let (up, down) = (sides[axis * 2][axis], sides[axis * 2 + 1][axis]);
+        offset[axis] = (up + down) / 2.0;
+        scale[axis] = 2.0 / (up - down);
+    }
+    for _ in 0..FIT_ITERATIONS {
+        let (mut normal, mut gradient) = (Matrix6::zeros(), Vector6::zeros());
+        for acc in sides {
+            let centered = acc - offset;
+            let corrected = centered.component_mul(&scale);
+            let length = corrected.norm();
+            if length < f64::EPSILON {
+                return None;
+            }
+            let residual = length - 1.0;
+            let d_scale = corrected.component_mul(&centered) / length;
+            let d_offset = -corrected.component_mul(&scale) / length;
+            let jacobian = Vector6::new(
+                d_offset.x, d_offset.y, d_offset.z, d_scale.x, d_scale.y, d_scale.z,
+            );
+            normal += jacobian * jacobian.transpose();
+            gradient += jacobian * residual;
+        }
+        let step = normal.cholesky()?.solve(&-gradient);
+        offset += step.fixed_rows::<3>(0);
+        scale += step.fixed_rows::<3>(3);
+    }
+    let plausible = offset.iter().all(|o| o.abs() <= MAX_OFFSET)
+        && scale.iter().all(|s| (s - 1.0).abs() <= MAX_SCALE_ERROR);
+    plausible.then(|| AccelCalibration {
+        offset: offset.into(),
+        scale: scale.into(),
+    })
+}
+
+/// The frame with `calibration` applied to its acceleration.
+pub(crate) fn correct(calibration: &AccelCalibration, frame: JoyconAxisData) -> JoyconAxisData {
+    let [ox, oy, oz] = calibration.offset;
+    let [sx, sy, sz] = calibration.scale;
+    JoyconAxisData {
+        accel_x: (frame.accel_x - ox) * sx,
+        accel_y: (frame.accel_y - oy) * sy,
+        accel_z: (frame.accel_z - oz) * sz,
+        ..frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OFFSET: [f64; 3] = [0.04, -0.03, 0.06];
+    const SCALE: [f64; 3] = [1.05, 0.97, 1.02];
+
+    // Raw reading of a sensor with `OFFSET` and `SCALE` while gravity is along `down`.
+    fn reading(down: Vector3<f64>) -> Vector3<f64> {
+        let down = down.normalize();
+        Vector3::from_fn(|axis, _| down[axis] / SCALE[axis] + OFFSET[axis])
+    }
+
+    // The six sides, a little tipped over like on a real table.
+    fn sides() -> Vec<Vector3<f64>> {
+        [
+            [1.0, 0.05, -0.02],
+            [-1.0, 0.03, 0.04],
+            [-0.04, 1.0, 0.06],
+            [0.02, -1.0, -0.05],
+            [0.06, -0.03, 1.0],
+            [-0.05, 0.02, -1.0],
+        ]
+        .map(|down| reading(Vector3::from(down)))
+        .to_vec()
+    }
+
+    #[test]
+    fn sides_found() {
+        for (index, acc) in sides().iter().enumerate() {
+            assert_eq!(side(acc), Some(index));
+        }
+        assert_eq!(side(&Vector3::new(0.7, 0.7, 0.0)), None);
+    }
+
+    #[test]
+    fn fit_recovers_offset_and_scale() {
+        let calibration = fit(&sides()).unwrap();
+        for axis in 0..3 {
+            assert!((calibration.offset[axis] - OFFSET[axis]).abs() < 1e-6);
+            assert!((calibration.scale[axis] - SCALE[axis]).abs() < 1e-6);
+        }
+        for acc in sides() {
+            let frame = JoyconAxisData {
+                accel_x: acc.x,
+                accel_y: acc.y,
+                accel_z: acc.z,
+                gyro_x: 0.0,
+                gyro_y: 0.0,
+                gyro_z: 0.0,
+            };
+            let corrected = correct(&calibration, frame);
+            let length =
+                Vector3::new(corrected.accel_x, corrected.accel_y, corrected.accel_z).norm();
+            assert!((length - 1.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn implausible_fit_rejected() {
+        let mut sides = sides();
+        // Read while the tracker was shaken, half a G too much.
+        sides[0] *= 1.5;
+        assert!(fit(&sides).is_none());
+    }
+}
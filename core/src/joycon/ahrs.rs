@@ -0,0 +1,191 @@
+use nalgebra::{Quaternion, UnitQuaternion, Vector3};
+
+// The integral term of Mahony takes out gyro bias slowly, whatever the proportional gain is. A
+// gain of 0 turns off both, for the gyro alone.
+const MAHONY_INTEGRAL_GAIN: f64 = 0.02;
+
+// Starts level with the first gravity reading instead of waiting for convergence, like the EKF.
+fn level(acc: &Vector3<f64>) -> UnitQuaternion<f64> {
+    UnitQuaternion::rotation_between(acc, &Vector3::z()).unwrap_or_else(UnitQuaternion::identity)
+}
+
+// q' = q + dt * rate, renormalized.
+fn integrate(q: &UnitQuaternion<f64>, rate: Quaternion<f64>, dt: f64) -> UnitQuaternion<f64> {
+    UnitQuaternion::from_quaternion(q.into_inner() + rate * dt)
+}
+
+// Rate of change of `q` turning at `gyro`, in the frame of the sensor.
+fn turn_rate(q: &UnitQuaternion<f64>, gyro: &Vector3<f64>) -> Quaternion<f64> {
+    q.into_inner() * Quaternion::from_imag(*gyro) * 0.5
+}
+
+/// Madgwick's gradient descent filter. Each step, the rotation moves by `beta` against the error
+/// between measured and expected gravity: higher follows the accelerometer faster and drifts
+/// less, lower is smoother while moving. z is up.
+pub struct Madgwick {
+    dt: f64,
+    pub beta: f64,
+    rotation: Option<UnitQuaternion<f64>>,
+}
+
+impl Madgwick {
+    pub const DEFAULT_BETA: f64 = 0.1;
+
+    pub fn new(dt: f64, beta: f64) -> Self {
+        Self {
+            dt,
+            beta,
+            rotation: None,
+        }
+    }
+
+    pub fn rotation(&self) -> UnitQuaternion<f64> {
+        self.rotation.unwrap_or_else(UnitQuaternion::identity)
+    }
+
+    pub fn update(&mut self, gyro: Vector3<f64>, acc: Vector3<f64>) {
+        let q = *self.rotation.get_or_insert_with(|| level(&acc));
+        let mut rate = turn_rate(&q, &gyro);
+        if let Some(a) = acc.try_normalize(f64::EPSILON) {
+            let (q0, q1, q2, q3) = (q.w, q.i, q.j, q.k);
+            // Expected gravity in the sensor frame minus the measured one.
+            let f = Vector3::new(
+                2.0 * (q1 * q3 - q0 * q2) - a.x,
+                2.0 * (q0 * q1 + q2 * q3) - a.y,
+                2.0 * (0.5 - q1 * q1 - q2 * q2) - a.z,
+            );
+            // Transposed Jacobian of f times f.
+            let step = Quaternion::new(
+                -2.0 * q2 * f.x + 2.0 * q1 * f.y,
+                2.0 * q3 * f.x + 2.0 * q0 * f.y - 4.0 * q1 * f.z,
+                -2.0 * q0 * f.x + 2.0 * q3 * f.y - 4.0 * q2 * f.z,
+                2.0 * q1 * f.x + 2.0 * q2 * f.y,
+            );
+            let norm = step.norm();
+            if norm > f64::EPSILON {
+                rate -= step * (self.beta / norm);
+            }
+        }
+        self.rotation = Some(integrate(&q, rate, self.dt));
+    }
+}
+
+/// Mahony's complementary filter. The error between measured and expected gravity is fed back
+/// into the gyro, scaled by `kp`: higher trusts the accelerometer more, lower the gyro. z is up.
+pub struct Mahony {
+    dt: f64,
+    pub kp: f64,
+    integral: Vector3<f64>,
+    rotation: Option<UnitQuaternion<f64>>,
+}
+
+impl Mahony {
+    pub const DEFAULT_KP: f64 = 1.0;
+
+    pub fn new(dt: f64, kp: f64) -> Self {
+        Self {
+            dt,
+            kp,
+            integral: Vector3::zeros(),
+            rotation: None,
+        }
+    }
+
+    pub fn rotation(&self) -> UnitQuaternion<f64> {
+        self.rotation.unwrap_or_else(UnitQuaternion::identity)
+    }
+
+    pub fn update(&mut self, gyro: Vector3<f64>, acc: Vector3<f64>) {
+        let q = *self.rotation.get_or_insert_with(|| level(&acc));
+        let mut gyro = gyro;
+        if let Some(a) = acc.try_normalize(f64::EPSILON).filter(|_| self.kp > 0.0) {
+            let expected = q.inverse_transform_vector(&Vector3::z());
+            let error = a.cross(&expected);
+            self.integral += error * MAHONY_INTEGRAL_GAIN * self.dt;
+            gyro += error * self.kp + self.integral;
+        }
+        self.rotation = Some(integrate(&q, turn_rate(&q, &gyro), self.dt));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: f64 = 0.005;
+
+    // Both filters behind one interface, with a gain of 0 or their default.
+    trait Filter {
+        fn update(&mut self, gyro: Vector3<f64>, acc: Vector3<f64>);
+        fn rotation(&self) -> UnitQuaternion<f64>;
+        // Starts from `rotation` instead of the first gravity reading.
+        fn start_at(&mut self, rotation: UnitQuaternion<f64>);
+    }
+    impl Filter for Madgwick {
+        fn update(&mut self, gyro: Vector3<f64>, acc: Vector3<f64>) {
+            Madgwick::update(self, gyro, acc);
+        }
+        fn rotation(&self) -> UnitQuaternion<f64> {
+            Madgwick::rotation(self)
+        }
+        fn start_at(&mut self, rotation: UnitQuaternion<f64>) {
+            self.rotation = Some(rotation);
+        }
+    }
+    impl Filter for Mahony {
+        fn update(&mut self, gyro: Vector3<f64>, acc: Vector3<f64>) {
+            Mahony::update(self, gyro, acc);
+        }
+        fn rotation(&self) -> UnitQuaternion<f64> {
+            Mahony::rotation(self)
+        }
+        fn start_at(&mut self, rotation: UnitQuaternion<f64>) {
+            self.rotation = Some(rotation);
+        }
+    }
+
+    fn filters(gain: Option<f64>) -> [Box<dyn Filter>; 2] {
+        [
+            Box::new(Madgwick::new(DT, gain.unwrap_or(Madgwick::DEFAULT_BETA))),
+            Box::new(Mahony::new(DT, gain.unwrap_or(Mahony::DEFAULT_KP))),
+        ]
+    }
+
+    #[test]
+    fn stationary_converges_to_gravity() {
+        let tilt = UnitQuaternion::from_euler_angles(20f64.to_radians(), 0.0, 0.0);
+        let acc = tilt.inverse() * Vector3::z();
+        for mut filter in filters(None) {
+            filter.start_at(UnitQuaternion::identity());
+            for _ in 0..2000 {
+                filter.update(Vector3::zeros(), acc);
+            }
+            let up = filter.rotation() * acc;
+            assert!(up.angle(&Vector3::z()) < 0.5f64.to_radians(), "{up}");
+        }
+    }
+
+    #[test]
+    fn constant_rate_integrates() {
+        // 0.5 radians/s around the vertical for 2s, where gravity can't correct it.
+        for mut filter in filters(None) {
+            for _ in 0..400 {
+                filter.update(Vector3::new(0.0, 0.0, 0.5), Vector3::z());
+            }
+            let (_, _, yaw) = filter.rotation().euler_angles();
+            assert!((yaw - 1.0).abs() < 0.01, "{yaw}");
+        }
+    }
+
+    #[test]
+    fn zero_gain_is_gyro_only() {
+        // Tipping over at 0.5 radians/s while gravity still reads straight down.
+        let expected = UnitQuaternion::from_axis_angle(&Vector3::x_axis(), 1.0);
+        for mut filter in filters(Some(0.0)) {
+            for _ in 0..400 {
+                filter.update(Vector3::new(0.5, 0.0, 0.0), Vector3::z());
+            }
+            assert!(filter.rotation().angle_to(&expected) < 0.01);
+        }
+    }
+}
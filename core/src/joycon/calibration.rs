@@ -0,0 +1,327 @@
+//! Measuring the gyro offsets of a Joy-Con and writing them to its user calibration in SPI flash,
+//! so other programs using the controller benefit too.
+//!
+//! Only the gyro origin is measured, the accelerometer and sensitivity values are kept from the
+//! calibration the controller had. Before the first write, the original user calibration is saved
+//! to a file, so it can be restored later.
+//! https://github.com/dekuNukem/Nintendo_Switch_Reverse_Engineering/blob/master/spi_flash_notes.md
+
+use std::{fs, io, path::PathBuf};
+
+use directories::ProjectDirs;
+use joycon_rs::joycon::device::calibration::imu::IMUCalibration;
+use joycon_rs::joycon::input_report_mode::standard_full_mode::AxisData;
+use joycon_rs::prelude::*;
+
+// Magic, then acc origin, acc sensitivity, gyro origin and gyro sensitivity, i16 little endian.
+const USER_IMU_CALIBRATION: u32 = 0x8026;
+const USER_CALIBRATION_MAGIC: [u8; 2] = [0xB2, 0xA1];
+const REGION_SIZE: usize = 26;
+/// The user IMU calibration area as stored in flash.
+pub(crate) type Region = [u8; REGION_SIZE];
+
+// Defaults for a controller without any calibration, 8G and 2000dps.
+const DEFAULT_ACC_SENSITIVITY: i16 = 16384;
+const DEFAULT_GYRO_SENSITIVITY: i16 = 13371;
+
+// About three seconds of reports.
+const CALIBRATION_SAMPLES: usize = 600;
+// Largest difference between gyro samples that still counts as lying still, about 5.6°/s.
+const MAX_GYRO_SPREAD: u16 = 80;
+// Reports to look through for the reply to an SPI subcommand, input reports keep coming.
+const REPLY_TRIES: usize = 30;
+const REPLY_TIMEOUT_MS: i32 = 50;
+
+/// How far the IMU calibration of a device got, see [`Action::CalibrateImu`](super::Action).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CalibrationStep {
+    #[default]
+    Idle,
+    /// Collecting reports, the controller has to lie still.
+    Measuring,
+    /// Measured and used by wrangler, not written to the controller yet.
+    Measured,
+    /// Written to the user calibration of the controller.
+    Written,
+}
+
+/// Calibration of a device that can have it written back to its flash.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CalibrationState {
+    pub step: CalibrationStep,
+    /// The calibration the controller had before wrangler first wrote it is saved.
+    pub backup: bool,
+}
+
+/// Averages raw gyro samples while the controller lies still.
+#[derive(Default)]
+pub(crate) struct GyroMeasurement {
+    sum: [i64; 3],
+    min: [i16; 3],
+    max: [i16; 3],
+    samples: usize,
+}
+impl GyroMeasurement {
+    pub fn add(&mut self, frames: &[AxisData; 3]) {
+        for frame in frames {
+            let gyro = [frame.gyro_1, frame.gyro_2, frame.gyro_3];
+            if self.samples == 0 {
+                self.min = gyro;
+                self.max = gyro;
+            }
+            for (i, value) in gyro.into_iter().enumerate() {
+                self.sum[i] += i64::from(value);
+                self.min[i] = self.min[i].min(value);
+                self.max[i] = self.max[i].max(value);
+            }
+            self.samples += 1;
+        }
+    }
+    pub fn done(&self) -> bool {
+        self.samples >= CALIBRATION_SAMPLES
+    }
+    /// Average reading of each gyro axis, `None` if the controller moved.
+    pub fn origin(&self) -> Option<[i16; 3]> {
+        let still = (0..3).all(|i| self.max[i].abs_diff(self.min[i]) <= MAX_GYRO_SPREAD);
+        if !still || self.samples == 0 {
+            return None;
+        }
+        Some(self.sum.map(|sum| (sum / self.samples as i64) as i16))
+    }
+}
+
+/// `calib` with the gyro origin replaced, in the layout of the flash.
+pub(crate) fn with_gyro_origin(calib: &IMUCalibration, origin: [i16; 3]) -> Region {
+    let (acc_origin, acc_sensitivity, gyro_sensitivity) = match calib {
+        IMUCalibration::Available {
+            acc_origin_position: ao,
+            acc_sensitivity_special_coeff: ac,
+            gyro_sensitivity_special_coeff: gc,
+            ..
+        } => ([ao.x, ao.y, ao.z], [ac.x, ac.y, ac.z], [gc.x, gc.y, gc.z]),
+        IMUCalibration::Unavailable => (
+            [0; 3],
+            [DEFAULT_ACC_SENSITIVITY; 3],
+            [DEFAULT_GYRO_SENSITIVITY; 3],
+        ),
+    };
+    let mut region = [0; REGION_SIZE];
+    region[..2].copy_from_slice(&USER_CALIBRATION_MAGIC);
+    let values = acc_origin
+        .iter()
+        .chain(&acc_sensitivity)
+        .chain(&origin)
+        .chain(&gyro_sensitivity);
+    for (bytes, value) in region[2..].chunks_exact_mut(2).zip(values) {
+        bytes.copy_from_slice(&value.to_le_bytes());
+    }
+    region
+}
+
+/// The calibration stored in `region`, `None` if it holds no user calibration.
+pub(crate) fn parse(region: &Region) -> Option<IMUCalibration> {
+    if region[..2] != USER_CALIBRATION_MAGIC {
+        return None;
+    }
+    let mut values = [0; 24];
+    values.copy_from_slice(&region[2..]);
+    match IMUCalibration::from(values) {
+        IMUCalibration::Unavailable => None,
+        calib => Some(calib),
+    }
+}
+
+// Sends an SPI subcommand and waits for its reply among the input reports, returns the reply.
+fn spi_command(
+    driver: &mut SimpleJoyConDriver,
+    sub_command: SubCommand,
+    args: &[u8],
+) -> JoyConResult<[u8; 64]> {
+    driver.send_command_raw(Command::RumbleAndSubCommand as u8, sub_command as u8, args)?;
+    for _ in 0..REPLY_TRIES {
+        let mut buf = [0; 64];
+        driver.read_timeout(&mut buf, REPLY_TIMEOUT_MS)?;
+        if buf[0] == 0x21 && buf[14] == sub_command as u8 {
+            return Ok(buf);
+        }
+    }
+    Err(JoyConError::SubCommandError(sub_command as u8, Vec::new()))
+}
+
+/// Reads the user IMU calibration area of the controller.
+pub(crate) fn read_region(driver: &mut SimpleJoyConDriver) -> JoyConResult<Region> {
+    let mut args = [0; 5];
+    args[..4].copy_from_slice(&USER_IMU_CALIBRATION.to_le_bytes());
+    args[4] = REGION_SIZE as u8;
+    let reply = spi_command(driver, SubCommand::SPIFlashRead, &args)?;
+    if reply[15..20] != args {
+        return Err(JoyConError::SubCommandError(
+            SubCommand::SPIFlashRead as u8,
+            Vec::new(),
+        ));
+    }
+    let mut region = [0; REGION_SIZE];
+    region.copy_from_slice(&reply[20..20 + REGION_SIZE]);
+    Ok(region)
+}
+
+/// Writes the user IMU calibration area and reads it back to make sure it stuck.
+pub(crate) fn write_region(driver: &mut SimpleJoyConDriver, region: &Region) -> JoyConResult<()> {
+    let mut args = [0; 5 + REGION_SIZE];
+    args[..4].copy_from_slice(&USER_IMU_CALIBRATION.to_le_bytes());
+    args[4] = REGION_SIZE as u8;
+    args[5..].copy_from_slice(region);
+    let reply = spi_command(driver, SubCommand::SPIFlashWrite, &args)?;
+    if reply[15] != 0 || read_region(driver)? != *region {
+        return Err(JoyConError::SubCommandError(
+            SubCommand::SPIFlashWrite as u8,
+            Vec::new(),
+        ));
+    }
+    Ok(())
+}
+
+fn backup_file(serial_number: &str) -> Option<PathBuf> {
+    let name: String = serial_number
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    ProjectDirs::from("", "", "SlimeVR Wrangler").map(|pd| {
+        pd.config_dir()
+            .join("calibration")
+            .join(format!("{name}.bin"))
+    })
+}
+
+pub(crate) fn has_backup(serial_number: &str) -> bool {
+    backup_file(serial_number).map_or(false, |file| file.exists())
+}
+
+/// Saves `region` as the original calibration of the controller, unless one is saved already.
+pub(crate) fn save_backup(serial_number: &str, region: &Region) -> io::Result<()> {
+    let file = backup_file(serial_number).ok_or(io::ErrorKind::NotFound)?;
+    if file.exists() {
+        return Ok(());
+    }
+    if let Some(dir) = file.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(file, region)
+}
+
+pub(crate) fn load_backup(serial_number: &str) -> io::Result<Region> {
+    let file = backup_file(serial_number).ok_or(io::ErrorKind::NotFound)?;
+    fs::read(file)?
+        .try_into()
+        .map_err(|_| io::ErrorKind::InvalidData.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A user calibration as read from 0x8026: acc origin 200, -150, 36, gyro origin 14, -9, 5 and
+    // the default sensitivities.
+    #[rustfmt::skip]
+    const REGION: Region = [
+        0xB2, 0xA1,
+        0xC8, 0x00, 0x6A, 0xFF, 0x24, 0x00,
+        0x00, 0x40, 0x00, 0x40, 0x00, 0x40,
+        0x0E, 0x00, 0xF7, 0xFF, 0x05, 0x00,
+        0x3B, 0x34, 0x3B, 0x34, 0x3B, 0x34,
+    ];
+
+    // Acc origin, acc sensitivity, gyro origin and gyro sensitivity.
+    fn values(calib: &IMUCalibration) -> [[i16; 3]; 4] {
+        match calib {
+            IMUCalibration::Available {
+                acc_origin_position: ao,
+                acc_sensitivity_special_coeff: ac,
+                gyro_origin_position: go,
+                gyro_sensitivity_special_coeff: gc,
+            } => [ao, ac, go, gc].map(|v| [v.x, v.y, v.z]),
+            IMUCalibration::Unavailable => panic!("no calibration"),
+        }
+    }
+
+    fn frames(gyro: [i16; 3]) -> [AxisData; 3] {
+        [AxisData {
+            accel_x: 0,
+            accel_y: 0,
+            accel_z: 4096,
+            gyro_1: gyro[0],
+            gyro_2: gyro[1],
+            gyro_3: gyro[2],
+        }; 3]
+    }
+
+    #[test]
+    fn parse_user_calibration() {
+        let calib = parse(&REGION).unwrap();
+        assert_eq!(
+            values(&calib),
+            [
+                [200, -150, 36],
+                [DEFAULT_ACC_SENSITIVITY; 3],
+                [14, -9, 5],
+                [DEFAULT_GYRO_SENSITIVITY; 3],
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_without_user_calibration() {
+        assert!(parse(&[0xFF; REGION_SIZE]).is_none());
+        let mut erased = [0xFF; REGION_SIZE];
+        erased[..2].copy_from_slice(&USER_CALIBRATION_MAGIC);
+        assert!(parse(&erased).is_none());
+    }
+
+    #[test]
+    fn round_trip() {
+        let calib = parse(&REGION).unwrap();
+        assert_eq!(with_gyro_origin(&calib, [14, -9, 5]), REGION);
+    }
+
+    #[test]
+    fn only_gyro_origin_replaced() {
+        let region = with_gyro_origin(&parse(&REGION).unwrap(), [-300, 0, 1234]);
+        assert_eq!(region[..14], REGION[..14]);
+        assert_eq!(region[20..], REGION[20..]);
+        assert_eq!(values(&parse(&region).unwrap())[2], [-300, 0, 1234]);
+    }
+
+    #[test]
+    fn defaults_without_calibration() {
+        let region = with_gyro_origin(&IMUCalibration::Unavailable, [1, 2, 3]);
+        assert_eq!(
+            values(&parse(&region).unwrap()),
+            [
+                [0; 3],
+                [DEFAULT_ACC_SENSITIVITY; 3],
+                [1, 2, 3],
+                [DEFAULT_GYRO_SENSITIVITY; 3],
+            ]
+        );
+    }
+
+    #[test]
+    fn measured_origin() {
+        let mut measurement = GyroMeasurement::default();
+        assert_eq!(measurement.origin(), None);
+        for gyro in [[10, -4, 3], [12, -6, 3], [14, -5, 6]] {
+            measurement.add(&frames(gyro));
+        }
+        assert_eq!(measurement.origin(), Some([12, -5, 4]));
+        let region = with_gyro_origin(&parse(&REGION).unwrap(), measurement.origin().unwrap());
+        assert_eq!(values(&parse(&region).unwrap())[2], [12, -5, 4]);
+    }
+
+    #[test]
+    fn moved_while_measuring() {
+        let mut measurement = GyroMeasurement::default();
+        measurement.add(&frames([0, 0, 0]));
+        measurement.add(&frames([0, MAX_GYRO_SPREAD as i16 + 1, 0]));
+        assert_eq!(measurement.origin(), None);
+    }
+}
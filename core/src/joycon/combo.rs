@@ -0,0 +1,37 @@
+//! Button combos on the controllers that control wrangler, so a setup can change without going to
+//! the PC.
+
+use crate::settings::ButtonCombo;
+
+/// Buttons held on one controller, named so they mean the same on either side. A Pro Controller
+/// has both sides.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HeldButtons {
+    /// Capture or HOME.
+    pub menu: bool,
+    /// Pressing the stick in.
+    pub stick: bool,
+    pub minus_plus: bool,
+    /// L or R.
+    pub shoulder: bool,
+    /// ZL or ZR.
+    pub trigger: bool,
+    /// SL and SR together, on the rail of a Joy-Con.
+    pub side: bool,
+}
+impl HeldButtons {
+    pub fn holds(self, combo: ButtonCombo) -> bool {
+        let triggers = self.shoulder && self.trigger;
+        match combo {
+            ButtonCombo::Off => false,
+            ButtonCombo::MenuTriggers => self.menu && triggers,
+            ButtonCombo::StickTriggers => self.stick && triggers,
+            ButtonCombo::MinusPlusTriggers => self.minus_plus && triggers,
+            ButtonCombo::SideButtons => self.side,
+        }
+    }
+    /// Whether `combo` was completed going from `before` to these buttons.
+    pub fn pressed(self, before: HeldButtons, combo: ButtonCombo) -> bool {
+        self.holds(combo) && !before.holds(combo)
+    }
+}
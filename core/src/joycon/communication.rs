@@ -0,0 +1,2179 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Display,
+    io,
+    net::{SocketAddr, UdpSocket},
+    rc::Rc,
+    sync::{atomic::Ordering, Arc, Mutex, TryLockError},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use itertools::Itertools;
+use nalgebra::{UnitQuaternion, Vector3};
+use protocol::deku::DekuContainerRead;
+use protocol::{Bundle, PacketType, SlimeString};
+use serde_json::json;
+use tracing::info;
+
+use super::{
+    accel_calibration::{self, AccelMeasurement},
+    calibration::CalibrationState,
+    combo::HeldButtons,
+    diagnostics::{Compatibility, ErrorReport, HandshakeLog, PacketLog},
+    firmware::FirmwareVersion,
+    imu::{Imu, JoyconAxisData},
+    orientation, osc_output,
+    profile::{self, Stage},
+    queue::{count_drop, BoundedSender, Latest, Queue},
+    script::Script,
+    sender::{
+        send_bundle, send_bytes, send_packet, send_packet_elsewhere, take_connection_reset,
+        take_send_error, Connection, PacketSender, Timeline,
+    },
+    watchdog::{lock, set_group_paused, Durable, Outputs, Registration},
+    JoyconDesign,
+};
+use crate::settings::{self, FrameMode, LightState, Output, WranglerSettings};
+
+/// Coarse battery level, as reported by the controllers.
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Battery {
+    Empty,
+    Critical,
+    Low,
+    Medium,
+    Full,
+}
+impl Battery {
+    /// Share of a full charge, roughly in the middle of the range the level covers.
+    pub fn fraction(self) -> f32 {
+        match self {
+            Battery::Empty => 0.0,
+            Battery::Critical => 0.1,
+            Battery::Low => 0.3,
+            Battery::Medium => 0.6,
+            Battery::Full => 1.0,
+        }
+    }
+    /// Lowercase name of the level, for other programs to read.
+    pub fn name(self) -> &'static str {
+        match self {
+            Battery::Empty => "empty",
+            Battery::Critical => "critical",
+            Battery::Low => "low",
+            Battery::Medium => "medium",
+            Battery::Full => "full",
+        }
+    }
+    /// Typical voltage of the lithium cell at this level, the controllers don't report it.
+    pub fn voltage(self) -> f32 {
+        match self {
+            Battery::Empty => 3.3,
+            Battery::Critical => 3.5,
+            Battery::Low => 3.6,
+            Battery::Medium => 3.75,
+            Battery::Full => 4.1,
+        }
+    }
+}
+
+/// Snapshot of one device, sent to the GUI as often as it redraws.
+#[derive(Debug, Clone)]
+pub struct Status {
+    /// Fused roll, pitch and yaw in degrees.
+    pub rotation: (f64, f64, f64),
+    /// Rotation as sent to the server.
+    pub orientation: UnitQuaternion<f64>,
+    pub design: JoyconDesign,
+    pub serial_number: String,
+    pub battery: Battery,
+    pub status: DeviceStatus,
+    /// Heading drift in degrees per minute, once measured.
+    pub drift: Option<f64>,
+    /// Lying flat and still on a surface.
+    pub flat: bool,
+    /// IMU reports received in the last second.
+    pub rate: usize,
+    /// Bytes sent to the server for this tracker in the last second.
+    pub bandwidth: usize,
+    /// Only for devices that can store their IMU calibration.
+    pub calibration: Option<CalibrationState>,
+    /// Only for devices that report it.
+    pub firmware: Option<FirmwareVersion>,
+    /// Reports lost on the way in the last second, only for devices that can tell.
+    pub lost: Option<usize>,
+    /// Signal strength of the Bluetooth link in dBm, where the OS tells it.
+    pub rssi: Option<i16>,
+    /// Of the IMU in °C, only for devices that report it.
+    pub temperature: Option<f64>,
+    /// Sides taken out of six while [`Action::CalibrateAccel`] runs.
+    pub accel_calibration: Option<usize>,
+    /// Holding its last rotation, see [`Action::Freeze`].
+    pub frozen: bool,
+}
+
+/// Health of a device's IMU stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DeviceStatus {
+    Healthy,
+    LaggyIMU,
+    Asleep,
+    NoIMU,
+    Disconnected,
+}
+
+impl Display for DeviceStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            DeviceStatus::Healthy => "Healthy",
+            DeviceStatus::LaggyIMU => "Laggy IMU",
+            DeviceStatus::Asleep => "Asleep",
+            DeviceStatus::NoIMU => "No IMU",
+            DeviceStatus::Disconnected => "Disconnected",
+        })
+    }
+}
+
+// Below these, a frame counts as the tracker lying still.
+const MOTION_GYRO_THRESHOLD: f64 = 0.1; // radians/s
+const MOTION_ACCEL_THRESHOLD: f64 = 0.1; // G away from 1G
+
+// How long a tracker has to lie still before it is considered placed on a surface.
+const FLAT_SETTLE_TIME: Duration = Duration::from_secs(5);
+
+// A controller that is charging ignores the power off, it is asked again after this long.
+const POWER_OFF_RETRY: Duration = Duration::from_secs(60);
+
+// Bounds for how long the loop sleeps when there is nothing to do. It wakes up a little before
+// the next report is due, so it doesn't sit on one that just arrived.
+const MIN_IDLE_SLEEP: Duration = Duration::from_micros(250);
+pub(crate) const MAX_IDLE_SLEEP: Duration = Duration::from_millis(2);
+const WAKE_MARGIN: Duration = Duration::from_micros(500);
+// The server is only looked for elsewhere after it has been silent for this long.
+const SERVER_SILENCE: Duration = Duration::from_secs(1);
+// Another address that might be the server is sent a handshake at most this often.
+const CANDIDATE_RETRY: Duration = Duration::from_secs(3);
+// Inspection packet with raw IMU data, as floats in radians/s and G.
+const INSPECTION_RAW_IMU: u8 = 1;
+const INSPECTION_FLOAT: u8 = 2;
+// Sensor id of a SetConfigFlag packet meant for every sensor.
+const ALL_SENSORS: u8 = 255;
+// In low-bandwidth mode a tracker sends at most this often, and only when it moved by more than
+// these. An unchanged one still sends once in a while, so the server can tell it is there.
+const LOW_BANDWIDTH_INTERVAL: Duration = Duration::from_millis(30);
+const UNCHANGED_ROTATION: f64 = 0.002; // radians
+const UNCHANGED_ACCELERATION: f64 = 0.02; // G
+const UNCHANGED_RESEND: Duration = Duration::from_secs(1);
+// A bundle is sent before it gets bigger than this, so it doesn't split up on the way.
+const MAX_BUNDLE_SIZE: usize = 1200;
+// Gaps longer than this are dropouts, not the device's cadence.
+const MAX_REPORT_GAP: Duration = Duration::from_millis(100);
+// Missed frames the fusion steps through, 5ms each, up to a gap of MAX_REPORT_GAP. Holding one
+// frame for longer would do more harm than skipping the time.
+const MAX_MISSED_FRAMES: usize = 20;
+// The battery is sent right away when it drops, and this often otherwise.
+const BATTERY_INTERVAL: Duration = Duration::from_secs(10);
+// A device of its own that hasn't been pinged for this long starts over with a handshake, which
+// is repeated this often until the server answers.
+const LINK_RETRY: Duration = Duration::from_secs(3);
+// Like firmware trackers, for the server to keep the connection while every tracker is paused.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+// Handshakes go out this often until the server answers. While sends fail, the wait doubles with
+// every failed one, up to the longest.
+const HANDSHAKE_INTERVAL: Duration = Duration::from_secs(3);
+const MAX_SEND_RETRY: Duration = Duration::from_secs(30);
+// A frame queue still locked by another thread after this long is given up on.
+const STREAM_STUCK: Duration = Duration::from_secs(5);
+
+/// Learns how often a device delivers reports, as a moving average of the gaps between them.
+#[derive(Default)]
+struct Cadence {
+    last: Option<Instant>,
+    interval: Option<Duration>,
+}
+
+impl Cadence {
+    fn update(&mut self, now: Instant) {
+        if let Some(last) = self.last {
+            let gap = now - last;
+            if gap < MAX_REPORT_GAP {
+                self.interval = Some(self.interval.map_or(gap, |i| (i * 7 + gap) / 8));
+            }
+        }
+        self.last = Some(now);
+    }
+
+    /// When the next report should arrive. None until the cadence is known, or once the
+    /// device is a full interval late and has probably stopped.
+    fn next_due(&self, now: Instant) -> Option<Instant> {
+        let interval = self.interval?;
+        let due = self.last? + interval;
+        (now < due + interval).then_some(due)
+    }
+}
+
+struct Device {
+    serial_number: Rc<str>,
+    imu: Imu,
+    design: JoyconDesign,
+    send_id: u8,
+    battery: Battery,
+    status: DeviceStatus,
+    imu_times: Vec<Instant>,
+    /// Bytes sent to the server for this device, with when.
+    sent: Vec<(Instant, usize)>,
+    /// The rotation and acceleration last sent, for low-bandwidth mode.
+    last_sent: Option<(Instant, UnitQuaternion<f64>, Vector3<f64>)>,
+    cadence: Cadence,
+    control: Option<BoundedSender<DeviceCommand>>,
+    /// Tracker packets go out from a thread of the device's own, if one could be started.
+    sender: Option<PacketSender>,
+    /// When the rotations go out with output smoothing.
+    timeline: Timeline,
+    last_motion: Instant,
+    asleep: bool,
+    /// Sent to the SlimeVR server, an excluded tracker shows up as offline there.
+    slimevr: bool,
+    /// Shows up as offline on the server while tracker data is paused.
+    paused: bool,
+    power_off_sent: Option<Instant>,
+    /// Warned about a low battery, not again until it was charged.
+    low_battery_warned: bool,
+    lights: Option<LightState>,
+    drift: orientation::DriftEstimator,
+    gyro_bias: orientation::GyroBias,
+    accel_measurement: Option<AccelMeasurement>,
+    last_sensor_info: Instant,
+    flat: bool,
+    leveled: bool,
+    standing_pose: Option<UnitQuaternion<f64>>,
+    script: Option<Script>,
+    script_path: String,
+    last_frame: Option<JoyconAxisData>,
+    calibration: Option<CalibrationState>,
+    firmware: Option<FirmwareVersion>,
+    lost: Option<usize>,
+    rssi: Option<i16>,
+    temperature: Option<f64>,
+    buttons: HeldButtons,
+    /// The rotation sent while frozen, kept over reconnects for a battery swap.
+    frozen: Option<UnitQuaternion<f64>>,
+    /// The connection of its own with [`WranglerSettings::separate_devices`].
+    link: Option<Link>,
+}
+
+impl Device {
+    // A device of its own has only this sensor, it is the first one.
+    fn sensor_id(&self) -> u8 {
+        if self.link.is_some() {
+            0
+        } else {
+            self.send_id
+        }
+    }
+    fn sensor_info(&self) -> PacketType {
+        PacketType::SensorInfo {
+            packet_id: 0,
+            sensor_id: self.sensor_id(),
+            sensor_status: if self.asleep
+                || self.paused
+                || !self.slimevr
+                || self.status == DeviceStatus::Disconnected
+            {
+                0
+            } else {
+                1
+            },
+            sensor_type: 0,
+        }
+    }
+    /// Sends the SensorInfo, from the connection of the device if it has one of its own, or
+    /// from `shared`.
+    pub fn handshake(&mut self, shared: &Connection, address: &SocketAddr) {
+        self.last_sensor_info = Instant::now();
+        let connection = self.link.as_ref().map_or(shared, |link| &link.connection);
+        send_packet(connection, &self.sensor_info(), *address);
+    }
+    fn command(&self, command: DeviceCommand) {
+        if let Some(control) = &self.control {
+            control.send(command).ok();
+        }
+    }
+    fn set_asleep(&mut self, asleep: bool, shared: &Connection, address: &SocketAddr) {
+        if self.asleep == asleep {
+            return;
+        }
+        self.asleep = asleep;
+        self.command(DeviceCommand::Sleep(asleep));
+        self.handshake(shared, address);
+    }
+}
+
+/// Connection of one device to the server, when every tracker is a device of its own there. The
+/// shared connection of wrangler still does the rest, like yaw resets and finding the server.
+struct Link {
+    connection: Arc<Connection>,
+    mac_address: [u8; 6],
+    connected: bool,
+    last_handshake: Option<Instant>,
+    last_ping: Instant,
+    last_heartbeat: Instant,
+    /// Battery sent last, and when.
+    sent_battery: Option<(Battery, Instant)>,
+}
+impl Link {
+    fn new(mac_address: [u8; 6]) -> io::Result<Self> {
+        let socket = UdpSocket::bind(SocketAddr::from(([0, 0, 0, 0], 0)))?;
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            connection: Arc::new(Connection::new(socket)),
+            mac_address,
+            connected: false,
+            last_handshake: None,
+            last_ping: Instant::now(),
+            last_heartbeat: Instant::now(),
+            sent_battery: None,
+        })
+    }
+    // Starts over with a handshake, to a server that restarted or moved.
+    fn reset(&mut self) {
+        self.connected = false;
+        self.last_handshake = None;
+        self.sent_battery = None;
+    }
+}
+
+/// Devices by serial number, each also gets a handle at registration so the streaming path can
+/// skip hashing the serial for every report. Devices are never removed, handles stay valid.
+#[derive(Default)]
+struct Devices {
+    list: Vec<Device>,
+    handles: HashMap<Rc<str>, usize>,
+}
+
+impl Devices {
+    fn handle(&self, sn: &str) -> Option<usize> {
+        self.handles.get(sn).copied()
+    }
+    fn get(&self, sn: &str) -> Option<&Device> {
+        self.handle(sn).map(|handle| &self.list[handle])
+    }
+    fn get_mut(&mut self, sn: &str) -> Option<&mut Device> {
+        let handle = self.handle(sn)?;
+        Some(&mut self.list[handle])
+    }
+    fn insert(&mut self, device: Device) {
+        self.handles
+            .insert(device.serial_number.clone(), self.list.len());
+        self.list.push(device);
+    }
+    fn len(&self) -> usize {
+        self.list.len()
+    }
+    fn iter(&self) -> impl Iterator<Item = (&str, &Device)> {
+        self.list
+            .iter()
+            .map(|device| (&*device.serial_number, device))
+    }
+    fn values(&self) -> impl Iterator<Item = &Device> {
+        self.list.iter()
+    }
+    fn values_mut(&mut self) -> impl Iterator<Item = &mut Device> {
+        self.list.iter_mut()
+    }
+}
+
+/// Frame queue of a device, see [`ChannelInfo::Stream`].
+struct FrameStream {
+    handle: usize,
+    frames: Arc<Mutex<FrameConsumer>>,
+    /// Since when the queue is found locked, polls skip it meanwhile.
+    locked_since: Option<Instant>,
+}
+
+// Without waiting, a queue locked by a stuck thread counts as still open.
+fn is_abandoned(stream: &Arc<Mutex<FrameConsumer>>) -> bool {
+    match stream.try_lock() {
+        Ok(frames) => frames.is_abandoned(),
+        Err(TryLockError::Poisoned(frames)) => frames.into_inner().is_abandoned(),
+        Err(TryLockError::WouldBlock) => false,
+    }
+}
+
+/// Port wrangler sends from, a random one is used if it is taken.
+pub(crate) const LOCAL_PORT: u16 = 47589;
+
+// The socket to send from, retried with the wait of failed sends until one can be opened. `None`
+// once a newer thread took over meanwhile.
+fn bind(
+    durable: &Durable,
+    generation: u64,
+    server_tx: &Latest<ServerStatus>,
+    errors_tx: &BoundedSender<ErrorReport>,
+) -> Option<UdpSocket> {
+    let addrs = [
+        SocketAddr::from(([0, 0, 0, 0], LOCAL_PORT)),
+        SocketAddr::from(([0, 0, 0, 0], 0)),
+    ];
+    let mut wait = HANDSHAKE_INTERVAL;
+    loop {
+        let error = match UdpSocket::bind(&addrs[..]) {
+            Ok(socket) => return Some(socket),
+            Err(error) => error,
+        };
+        if wait == HANDSHAKE_INTERVAL {
+            server_tx.send(ServerStatus::SendFailed);
+            errors_tx
+                .send(
+                    ErrorReport::new("Server", format!("Can't open a network socket, {error}"))
+                        .suggest(
+                            "Wrangler keeps trying, check that no firewall or policy blocks it.",
+                        ),
+                )
+                .ok();
+        }
+        let retry = Instant::now() + wait;
+        while Instant::now() < retry {
+            if durable.generation.load(Ordering::Relaxed) != generation {
+                return None;
+            }
+            // Waiting here isn't stuck, the watchdog would only restart into the same error.
+            durable.beat();
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        wait = (wait * 2).min(MAX_SEND_RETRY);
+    }
+}
+
+// Paused with all trackers, which includes no VR session running, or with its group.
+fn is_paused(
+    paused: bool,
+    paused_groups: &BTreeSet<String>,
+    settings: &WranglerSettings,
+    sn: &str,
+) -> bool {
+    paused || paused_groups.contains(settings.joycon_group_get(sn))
+}
+
+/// The handshake wrangler introduces itself to the server with.
+pub(crate) fn handshake_packet(mac_address: [u8; 6]) -> PacketType {
+    PacketType::Handshake {
+        packet_id: 0,
+        board: 0,
+        imu: 0,
+        mcu_type: 0,
+        imu_info: (0, 0, 0),
+        build: 9,
+        firmware: SlimeString::truncated("slimevr-wrangler"),
+        mac_address,
+    }
+}
+
+fn is_moving(frame: &JoyconAxisData) -> bool {
+    let gyro = Vector3::new(frame.gyro_x, frame.gyro_y, frame.gyro_z);
+    let acc = Vector3::new(frame.accel_x, frame.accel_y, frame.accel_z);
+    gyro.norm() > MOTION_GYRO_THRESHOLD || (acc.norm() - 1.0).abs() > MOTION_ACCEL_THRESHOLD
+}
+
+// The battery packet for `battery` if it changed or is due again, which counts as sent then.
+fn battery_update(sent: &mut Option<(Battery, Instant)>, battery: Battery) -> Option<PacketType> {
+    let due = sent.map_or(true, |(sent, time)| {
+        sent != battery || time.elapsed() >= BATTERY_INTERVAL
+    });
+    if !due {
+        return None;
+    }
+    *sent = Some((battery, Instant::now()));
+    Some(PacketType::BatteryLevel {
+        packet_id: 0,
+        voltage: battery.voltage(),
+        level: battery.fraction(),
+    })
+}
+
+/// Sends [`ChannelData`] to the communication thread, waits if it falls behind.
+pub type DeviceSender = BoundedSender<ChannelData>;
+
+/// Message from a device integration to the communication thread.
+#[derive(Debug)]
+pub struct ChannelData {
+    pub serial_number: String,
+    pub info: ChannelInfo,
+}
+impl ChannelData {
+    pub fn new(serial_number: String, info: ChannelInfo) -> Self {
+        Self {
+            serial_number,
+            info,
+        }
+    }
+}
+
+/// Commands sent back from the communication thread to a single device's integration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceCommand {
+    Sleep(bool),
+    /// Turn the controller off, unless it is charging. It disconnects and is picked up again once
+    /// it is turned back on.
+    PowerOff,
+    /// Buzz a few times so the wearer notices, used when the battery gets low.
+    Rumble,
+    /// What the controller is doing, shown on its lights.
+    Lights(LightState),
+    /// Show the identify lights for a few seconds.
+    Identify,
+    /// Measure the gyro offsets while the controller lies still.
+    Calibrate,
+    /// Store the measured calibration in the controller.
+    WriteCalibration,
+    /// Put back the calibration the controller had before wrangler first wrote it.
+    RestoreCalibration,
+}
+
+/// Actions requested by the user from the GUI, see [`Wrapper::action`](super::Wrapper::action).
+#[derive(Debug, Clone)]
+pub enum Action {
+    CaptureMounting(String),
+    /// First step of the two-pose mounting calibration, the user is standing straight.
+    CaptureStanding(String),
+    /// Second step, the user bent forward 90°. Sets the mounting if a standing pose was captured.
+    CaptureBent(String),
+    AlignAll,
+    /// Like [`Action::AlignAll`], for the trackers of one group.
+    AlignGroup(String),
+    Level(String),
+    /// Yaw reset on the server, like pressing B or UP on a tracker.
+    ResetAll,
+    /// Stop or resume sending tracker data to the server, the connection itself is kept. The
+    /// trackers show up as offline there meanwhile.
+    Pause(bool),
+    /// Like [`Action::Pause`], for the trackers of one group. They stay paused while all trackers
+    /// are resumed.
+    PauseGroup(String, bool),
+    /// A VR session ended or started. Pauses like [`Action::Pause`] without touching it, so a
+    /// manual pause stays when VR starts.
+    VrIdle(bool),
+    /// Show the identify lights on a tracker for a few seconds.
+    Identify(String),
+    /// Measure the gyro offsets of a tracker lying still, see [`Status::calibration`].
+    CalibrateImu(String),
+    /// Write the measured calibration into the user calibration of the controller, after saving
+    /// the one it had.
+    WriteCalibration(String),
+    RestoreCalibration(String),
+    /// Start or cancel the six-sided accelerometer calibration of a tracker, see
+    /// [`Status::accel_calibration`]. It ends on its own once all sides are taken.
+    CalibrateAccel(String, bool),
+    /// Keep sending the last rotation of a tracker while its strap or battery is changed. On
+    /// release, its yaw offset is turned so it continues at the heading it was held at.
+    Freeze(String, bool),
+}
+
+/// Reports a device can queue before the communication thread reads them, about a second.
+const FRAME_QUEUE_SIZE: usize = 64;
+
+/// Producing end of a device's frame queue, see [`ChannelInfo::Stream`].
+pub struct FrameProducer(rtrb::Producer<([JoyconAxisData; 3], usize)>);
+impl FrameProducer {
+    /// Queues a report, or drops it and counts it under [`Queue::Frames`] if the queue is full.
+    pub fn push(&mut self, frames: [JoyconAxisData; 3]) {
+        self.push_after_gap(frames, 0);
+    }
+    /// Queues a report that came after `missed` frames got lost on the way. The fusion still
+    /// steps through the time they took, so the rotation doesn't fall behind.
+    pub fn push_after_gap(&mut self, frames: [JoyconAxisData; 3], missed: usize) {
+        if self.0.push((frames, missed)).is_err() {
+            count_drop(Queue::Frames);
+        }
+    }
+}
+/// Consuming end of a device's frame queue, see [`ChannelInfo::Stream`]. Each report comes with
+/// the frames missed before it.
+pub type FrameConsumer = rtrb::Consumer<([JoyconAxisData; 3], usize)>;
+
+/// Lock-free queue for the IMU reports of a single device.
+///
+/// Pushing never allocates or waits on other devices. A full queue means the communication
+/// thread is stalled, and the report is dropped.
+pub fn frame_queue() -> (FrameProducer, FrameConsumer) {
+    let (producer, consumer) = rtrb::RingBuffer::new(FRAME_QUEUE_SIZE);
+    (FrameProducer(producer), consumer)
+}
+
+#[derive(Debug)]
+pub enum ChannelInfo {
+    /// A device appeared, or came back after a disconnect. Must come before anything else.
+    Connected(JoyconDesign),
+    /// Channel for sending [`DeviceCommand`]s back to the device, optional.
+    Control(BoundedSender<DeviceCommand>),
+    /// Three consecutive frames, 5ms apart.
+    ImuData([JoyconAxisData; 3]),
+    /// Queue the device pushes its [`ChannelInfo::ImuData`] frames into instead, from
+    /// [`frame_queue`]. Dropping the producer closes it.
+    Stream(FrameConsumer),
+    Battery(Battery),
+    /// The user asked for a yaw reset on the server.
+    Reset,
+    /// Something went wrong that the user should know about, the device may still be working.
+    Error(ErrorReport),
+    /// The device supports [`Action::CalibrateImu`], sent again whenever the state changes.
+    Calibration(CalibrationState),
+    /// Firmware version of the controller, after [`ChannelInfo::Connected`].
+    Firmware(FirmwareVersion),
+    /// Reports lost on the way in the last second, from gaps in the report timer of the
+    /// controller. Sent once a second.
+    Lost(usize),
+    /// Signal strength in dBm, see [`Status::rssi`].
+    Rssi(i16),
+    /// Of the IMU in °C, every few seconds, see [`GyroBias`](orientation::GyroBias).
+    Temperature(f64),
+    /// The buttons used in combos changed, for the combos in the settings.
+    Buttons(HeldButtons),
+    Disconnected,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Xyz {
+    x: f64,
+    y: f64,
+    z: f64,
+}
+
+fn calc_acceleration(
+    rotation: UnitQuaternion<f64>,
+    axisdata: &JoyconAxisData,
+    rad_rotation: f64,
+) -> Xyz {
+    let a = rotation.coords;
+    let (x, y, z, w) = (a.x, a.y, a.z, a.w);
+    let gravity = [
+        2.0 * ((-x) * (-z) - w * y),
+        -2.0 * (w * (-x) + y * (-z)),
+        w * w - x * x - y * y + z * z,
+    ];
+    let vector = Xyz {
+        x: axisdata.accel_x - gravity[0],
+        y: axisdata.accel_y - gravity[1],
+        z: axisdata.accel_z - gravity[2],
+    };
+
+    let rad_rotation = -rad_rotation;
+    Xyz {
+        x: vector.x * rad_rotation.cos() - vector.y * rad_rotation.sin(),
+        y: vector.x * rad_rotation.sin() + vector.y * rad_rotation.cos(),
+        z: vector.z,
+    }
+}
+
+// Fused rotation with the surface leveling and the coarse 90 degree mounting rotation from the
+// GUI applied.
+fn mounted_rotation(
+    settings: &WranglerSettings,
+    sn: &str,
+    fused: &UnitQuaternion<f64>,
+) -> UnitQuaternion<f64> {
+    let leveled = fused * settings.joycon_level_get(sn);
+    let joycon_rotation = settings.joycon_rotation_get(sn);
+    if joycon_rotation > 0 {
+        let rad_rotation = (joycon_rotation as f64).to_radians();
+        leveled * UnitQuaternion::from_axis_angle(&Vector3::z_axis(), rad_rotation)
+    } else {
+        leveled
+    }
+}
+
+// Rotation sent to the server, with all per-device corrections from the settings applied.
+fn output_rotation(
+    settings: &WranglerSettings,
+    sn: &str,
+    fused: &UnitQuaternion<f64>,
+) -> UnitQuaternion<f64> {
+    let yaw_offset =
+        (settings.joycon_yaw_offset_get(sn) + settings.joycon_yaw_trim_get(sn)).to_radians();
+    UnitQuaternion::from_axis_angle(&Vector3::z_axis(), yaw_offset)
+        * mounted_rotation(settings, sn, fused)
+        * settings.joycon_mounting_get(sn)
+}
+
+/// Connection state with the SlimeVR server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ServerStatus {
+    #[default]
+    Disconnected,
+    Unknown,
+    Connected,
+    /// Nothing listens on the port of the server, which only Windows tells.
+    Unreachable,
+    /// Packets can't be sent at all, like with the network down or no socket to send from.
+    /// Retried with a growing wait.
+    SendFailed,
+}
+
+/// Fuses the data of every device and talks to the SlimeVR server.
+pub struct Communication {
+    durable: Arc<Durable>,
+    generation: u64,
+    status_tx: BoundedSender<Vec<Status>>,
+    server_tx: Latest<ServerStatus>,
+    handshake_tx: Latest<HandshakeLog>,
+    errors_tx: BoundedSender<ErrorReport>,
+    settings: settings::Handler,
+
+    devices: Devices,
+    streams: Vec<FrameStream>,
+
+    use_keep_ids: bool,
+    separate_devices: bool,
+    connection: Arc<Connection>,
+    address: SocketAddr,
+    /// The address from the settings, `address` differs after following a moved server.
+    configured_address: SocketAddr,
+    /// Another address the server might have moved to, and when it was sent a handshake.
+    candidate: Option<(SocketAddr, Instant)>,
+    connected: ServerStatus,
+    /// `None` until the first handshake, and again after retargeting.
+    last_handshake: Option<Instant>,
+    handshake_interval: Duration,
+    last_ping: Instant,
+    last_reset: Instant,
+    last_reply: Option<PacketLog>,
+    sent_handshake_log: HandshakeLog,
+    paused: bool,
+    paused_groups: BTreeSet<String>,
+    vr_idle: bool,
+    telemetry_socket: Option<UdpSocket>,
+    last_telemetry: Instant,
+    /// Battery sent to the server last, and when.
+    sent_battery: Option<(Battery, Instant)>,
+    last_heartbeat: Instant,
+    osc_socket: Option<UdpSocket>,
+    mac_address: [u8; 6],
+    /// Tracker packets waiting to go out together, in low-bandwidth mode.
+    bundle: Bundle,
+}
+impl Communication {
+    /// Runs the communication loop on the current thread, until the watchdog hands over to a
+    /// thread of a newer `generation`.
+    pub(crate) fn start(
+        durable: Arc<Durable>,
+        generation: u64,
+        outputs: Outputs,
+        settings: settings::Handler,
+    ) {
+        let Outputs {
+            status_tx,
+            server_tx,
+            handshake_tx,
+            errors_tx,
+        } = outputs;
+        let Some(socket) = bind(&durable, generation, &server_tx, &errors_tx) else {
+            return;
+        };
+        socket.set_nonblocking(true).ok();
+        socket.set_broadcast(true).ok();
+        let mac_address = *lock(&durable.mac_address).get_or_insert_with(|| {
+            let mac = settings.load().mac_address();
+            let port_taken = socket.local_addr().map_or(false, |a| a.port() != LOCAL_PORT);
+            if !port_taken {
+                return mac;
+            }
+            // Most likely another wrangler with the same config, the server would take both
+            // for one tracker.
+            let mac = settings::derived_mac(mac, "second instance");
+            let shown = mac.iter().map(|b| format!("{b:02X}")).join(":");
+            let problem =
+                format!("Another wrangler seems to be running, this one uses the MAC {shown}");
+            errors_tx
+                .send(
+                    ErrorReport::new("Server", problem).suggest(
+                        "Its trackers show up as new ones on the server, close one of the wranglers unless that is intended.",
+                    ),
+                )
+                .ok();
+            mac
+        });
+        let address = *lock(&durable.server_address);
+        let use_keep_ids = { settings.load().keep_ids };
+        // The relay forwards one connection only.
+        let separate_devices = {
+            let settings = settings.load();
+            settings.separate_devices && settings.relay_address.is_empty()
+        };
+        let paused = durable.paused.load(Ordering::Relaxed);
+        let paused_groups = lock(&durable.paused_groups).clone();
+        let vr_idle = durable.vr_idle.load(Ordering::Relaxed);
+
+        server_tx.send(ServerStatus::Disconnected);
+        profile::name_thread("Communication");
+
+        let mut communication = Self {
+            durable,
+            generation,
+            status_tx,
+            server_tx,
+            handshake_tx,
+            errors_tx,
+            settings,
+            devices: Devices::default(),
+            streams: Vec::new(),
+            use_keep_ids,
+            separate_devices,
+            connection: Arc::new(Connection::new(socket)),
+            address,
+            configured_address: address,
+            candidate: None,
+            connected: ServerStatus::Disconnected,
+            last_handshake: None,
+            handshake_interval: HANDSHAKE_INTERVAL,
+            last_ping: Instant::now(),
+            last_reset: Instant::now(),
+            last_reply: None,
+            sent_handshake_log: HandshakeLog::default(),
+            paused,
+            paused_groups,
+            vr_idle,
+            telemetry_socket: None,
+            last_telemetry: Instant::now(),
+            sent_battery: None,
+            last_heartbeat: Instant::now(),
+            osc_socket: None,
+            mac_address,
+            bundle: Bundle::default(),
+        };
+        communication.replay();
+        communication.main_loop();
+    }
+
+    // Picks up the devices that registered with a previous communication thread.
+    fn replay(&mut self) {
+        let registrations: Vec<_> = lock(&self.durable.registrations)
+            .iter()
+            .map(|r| {
+                (
+                    r.serial_number.clone(),
+                    r.design.clone(),
+                    r.control.clone(),
+                    r.stream.clone(),
+                    r.calibration,
+                    r.firmware,
+                )
+            })
+            .collect();
+        for (sn, design, control, stream, calibration, firmware) in registrations {
+            self.parse_message(ChannelData::new(sn.clone(), ChannelInfo::Connected(design)));
+            if let Some(control) = control {
+                self.parse_message(ChannelData::new(sn.clone(), ChannelInfo::Control(control)));
+            }
+            if let Some(stream) = stream {
+                self.add_stream(&sn, stream);
+            }
+            if let Some(calibration) = calibration {
+                self.parse_message(ChannelData::new(
+                    sn.clone(),
+                    ChannelInfo::Calibration(calibration),
+                ));
+            }
+            if let Some(firmware) = firmware {
+                self.parse_message(ChannelData::new(
+                    sn.clone(),
+                    ChannelInfo::Firmware(firmware),
+                ));
+            }
+        }
+    }
+
+    // Keeps the registration of a device up to date, for the watchdog to replay.
+    fn record(&self, sn: &str, update: impl FnOnce(&mut Registration)) {
+        let mut registrations = lock(&self.durable.registrations);
+        if let Some(registration) = registrations.iter_mut().find(|r| r.serial_number == sn) {
+            update(registration);
+        }
+    }
+
+    fn add_stream(&mut self, sn: &str, stream: Arc<Mutex<FrameConsumer>>) {
+        if let Some(handle) = self.devices.handle(sn) {
+            self.record(sn, |r| r.stream = Some(stream.clone()));
+            self.streams.push(FrameStream {
+                handle,
+                frames: stream,
+                locked_since: None,
+            });
+        }
+    }
+
+    fn handshake_packet(&self) -> PacketType {
+        handshake_packet(self.mac_address)
+    }
+
+    fn send_handshake(&self) -> usize {
+        send_packet(&self.connection, &self.handshake_packet(), self.address)
+    }
+
+    // What the last handshake and SensorInfo packets contained, rebuilt from the current state.
+    fn handshake_log(&self) -> HandshakeLog {
+        HandshakeLog {
+            handshake: self
+                .last_handshake
+                .map(|sent| PacketLog::sent(&self.handshake_packet(), sent)),
+            sensor_info: self
+                .devices
+                .values()
+                .map(|d| {
+                    (
+                        d.send_id,
+                        PacketLog::sent(&d.sensor_info(), d.last_sensor_info),
+                    )
+                })
+                .collect(),
+            reply: self.last_reply.clone(),
+        }
+    }
+
+    fn command(&self, sn: &str, command: DeviceCommand) {
+        if let Some(device) = self.devices.get(sn) {
+            device.command(command);
+        }
+    }
+
+    fn send_reset(&self) {
+        let handshake = PacketType::UserAction {
+            packet_id: 0,
+            typ: 3,
+        };
+        send_packet(&self.connection, &handshake, self.address);
+    }
+
+    // The server shows one battery for each of its devices. Trackers that share the connection of
+    // wrangler get the lowest of theirs, so it warns before any controller dies.
+    fn send_battery(&mut self) {
+        let address = self.address;
+        for device in self.devices.values_mut() {
+            let battery = device.battery;
+            match &mut device.link {
+                Some(link) if link.connected && device.status != DeviceStatus::Disconnected => {
+                    if let Some(packet) = battery_update(&mut link.sent_battery, battery) {
+                        send_packet(&link.connection, &packet, address);
+                    }
+                }
+                _ => {}
+            }
+        }
+        if self.connected != ServerStatus::Connected {
+            self.sent_battery = None;
+            return;
+        }
+        let lowest = self
+            .devices
+            .values()
+            .filter(|device| device.link.is_none() && device.status != DeviceStatus::Disconnected)
+            .map(|device| device.battery)
+            .min();
+        if let Some(packet) =
+            lowest.and_then(|battery| battery_update(&mut self.sent_battery, battery))
+        {
+            send_packet(&self.connection, &packet, address);
+        }
+    }
+
+    // Handshakes, pings and config flags of the trackers that are devices of their own on the
+    // server, each on its own socket.
+    fn serve_links(&mut self, buf: &mut [u8]) {
+        let address = self.address;
+        let mut flags = Vec::new();
+        for device in self.devices.values_mut() {
+            let Some(link) = &mut device.link else {
+                continue;
+            };
+            while let Ok((len, from)) = link.connection.socket().recv_from(buf) {
+                if from != address {
+                    continue;
+                }
+                match PacketType::from_bytes((&buf[..len], 0)) {
+                    Ok((_, PacketType::Ping { .. })) => {
+                        link.last_ping = Instant::now();
+                        send_bytes(&link.connection, &buf[..len], address);
+                    }
+                    Ok((_, PacketType::HandshakeResponse)) => {
+                        link.connected = true;
+                        link.last_ping = Instant::now();
+                    }
+                    Ok((_, PacketType::SetConfigFlag { flag_id, state, .. })) => {
+                        flags.push((device.serial_number.clone(), flag_id, state));
+                    }
+                    _ => {}
+                }
+            }
+            if link.connected && link.last_ping.elapsed() >= LINK_RETRY {
+                link.reset();
+            }
+            // Each device on the server is a connection of its own to keep.
+            if link.connected && link.last_heartbeat.elapsed() >= HEARTBEAT_INTERVAL {
+                link.last_heartbeat = Instant::now();
+                send_packet(
+                    &link.connection,
+                    &PacketType::Heartbeat { packet_id: 0 },
+                    address,
+                );
+            }
+            let due = !link.connected
+                && link
+                    .last_handshake
+                    .map_or(true, |sent| sent.elapsed() >= LINK_RETRY);
+            if due {
+                link.last_handshake = Some(Instant::now());
+                send_packet(
+                    &link.connection,
+                    &handshake_packet(link.mac_address),
+                    address,
+                );
+                device.handshake(&self.connection, &address);
+            }
+        }
+        for (sn, flag_id, state) in flags {
+            self.settings
+                .change(|ws| ws.joycon_config_flag_set(sn.to_string(), flag_id, state));
+            if let Some(link) = self
+                .devices
+                .get(&sn)
+                .and_then(|device| device.link.as_ref())
+            {
+                let ack = PacketType::AckConfigChange {
+                    packet_id: 0,
+                    sensor_id: 0,
+                    flag_id,
+                };
+                send_packet(&link.connection, &ack, address);
+            }
+        }
+    }
+
+    // Raw and fused data of every device as one JSON object, for visualizers.
+    fn send_telemetry(&mut self) {
+        let settings = self.settings.load();
+        let target = match settings.telemetry_target() {
+            Some(target) => target,
+            None => return,
+        };
+        if self.last_telemetry.elapsed() < settings.telemetry_interval() {
+            return;
+        }
+        self.last_telemetry = Instant::now();
+        if self.telemetry_socket.is_none() {
+            self.telemetry_socket = UdpSocket::bind(SocketAddr::from(([0, 0, 0, 0], 0))).ok();
+        }
+        let devices: Vec<_> = self
+            .devices
+            .iter()
+            .filter(|(_, device)| device.status != DeviceStatus::Disconnected)
+            .map(|(sn, device)| {
+                let fused = device.imu.rotation;
+                let rotation = output_rotation(&settings, sn, &device.imu.rotation);
+                let raw = device.last_frame.map(|f| {
+                    json!({
+                        "accel": [f.accel_x, f.accel_y, f.accel_z],
+                        "gyro": [f.gyro_x, f.gyro_y, f.gyro_z],
+                    })
+                });
+                json!({
+                    "serial": sn,
+                    "id": device.send_id,
+                    "status": device.status.to_string(),
+                    "raw": raw,
+                    "fused": [fused.w, fused.i, fused.j, fused.k],
+                    "rotation": [rotation.w, rotation.i, rotation.j, rotation.k],
+                })
+            })
+            .collect();
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64);
+        let packet = json!({ "time": time, "devices": devices }).to_string();
+        if let Some(socket) = &self.telemetry_socket {
+            socket.send_to(packet.as_bytes(), target).ok();
+        }
+    }
+
+    fn parse_message(&mut self, msg: ChannelData) {
+        let sn = msg.serial_number;
+        match msg.info {
+            ChannelInfo::Connected(design) => {
+                info!(serial_number = %sn, "Controller connected");
+                {
+                    let mut registrations = lock(&self.durable.registrations);
+                    let registration = Registration {
+                        serial_number: sn.clone(),
+                        design: design.clone(),
+                        control: None,
+                        stream: None,
+                        calibration: None,
+                        firmware: None,
+                    };
+                    match registrations.iter_mut().find(|r| r.serial_number == sn) {
+                        Some(existing) => *existing = registration,
+                        None => registrations.push(registration),
+                    }
+                }
+                if let Some(device) = self.devices.get_mut(&sn) {
+                    device.imu = Imu::new(self.settings.load().joycon_fusion_get(&sn));
+                    device.imu_times = vec![];
+                    device.cadence = Default::default();
+                    device.timeline = Timeline::default();
+                    device.control = None;
+                    device.last_motion = Instant::now();
+                    device.drift = Default::default();
+                    device.gyro_bias = Default::default();
+                    device.flat = false;
+                    device.leveled = false;
+                    // Reloads the script with the next sample.
+                    device.script = None;
+                    device.script_path.clear();
+                    device.calibration = None;
+                    device.firmware = None;
+                    device.buttons = HeldButtons::default();
+                    device.power_off_sent = None;
+                    device.lights = None;
+                    device.last_sent = None;
+                    device.status = DeviceStatus::NoIMU;
+                    device.set_asleep(false, &self.connection, &self.address);
+                    device.handshake(&self.connection, &self.address);
+                    return;
+                }
+
+                let send_id = if self.use_keep_ids {
+                    self.settings.joycon_keep_id(sn.clone())
+                } else {
+                    self.devices.len() as _
+                };
+                let slimevr = self.settings.load().sends_to(&sn, Output::SlimeVr);
+                let paused = is_paused(
+                    self.paused || self.vr_idle,
+                    &self.paused_groups,
+                    &self.settings.load(),
+                    &sn,
+                );
+                let link = if self.separate_devices {
+                    Link::new(settings::serial_mac(self.mac_address, &sn)).ok()
+                } else {
+                    None
+                };
+                let connection = link
+                    .as_ref()
+                    .map_or(&self.connection, |link| &link.connection);
+                let sender = PacketSender::spawn(connection, &sn).ok();
+                let fusion = self.settings.load().joycon_fusion_get(&sn);
+                let mut device = Device {
+                    serial_number: sn.into(),
+                    imu: Imu::new(fusion),
+                    design,
+                    send_id,
+                    battery: Battery::Full,
+                    status: DeviceStatus::NoIMU,
+                    imu_times: vec![],
+                    sent: vec![],
+                    last_sent: None,
+                    cadence: Default::default(),
+                    control: None,
+                    sender,
+                    timeline: Timeline::default(),
+                    last_motion: Instant::now(),
+                    asleep: false,
+                    slimevr,
+                    paused,
+                    power_off_sent: None,
+                    low_battery_warned: false,
+                    lights: None,
+                    drift: Default::default(),
+                    gyro_bias: Default::default(),
+                    accel_measurement: None,
+                    last_sensor_info: Instant::now(),
+                    flat: false,
+                    leveled: false,
+                    standing_pose: None,
+                    script: None,
+                    script_path: String::new(),
+                    last_frame: None,
+                    calibration: None,
+                    firmware: None,
+                    lost: None,
+                    rssi: None,
+                    temperature: None,
+                    buttons: HeldButtons::default(),
+                    frozen: None,
+                    link,
+                };
+
+                device.handshake(&self.connection, &self.address);
+                self.devices.insert(device);
+            }
+            ChannelInfo::Control(control) => {
+                self.record(&sn, |r| r.control = Some(control.clone()));
+                if let Some(device) = self.devices.get_mut(&sn) {
+                    if device.asleep {
+                        control.send(DeviceCommand::Sleep(true)).ok();
+                    }
+                    // Sent again with the next status update.
+                    device.lights = None;
+                    device.control = Some(control);
+                }
+            }
+            ChannelInfo::ImuData(imu_data) => {
+                if let Some(handle) = self.devices.handle(&sn) {
+                    self.parse_frames(handle, imu_data, 0);
+                }
+            }
+            ChannelInfo::Stream(frames) => self.add_stream(&sn, Arc::new(Mutex::new(frames))),
+            ChannelInfo::Battery(battery) => {
+                if let Some(device) = self.devices.get_mut(&sn) {
+                    if battery == Battery::Full {
+                        device.low_battery_warned = false;
+                    }
+                    let got_low = battery <= Battery::Low && !device.low_battery_warned;
+                    device.battery = battery;
+                    if got_low {
+                        device.low_battery_warned = true;
+                        if self.settings.load().low_battery_rumble {
+                            device.command(DeviceCommand::Rumble);
+                        }
+                        self.errors_tx
+                            .send(
+                                ErrorReport::new(&sn, "The battery is low")
+                                    .suggest("Charge the controller after this session."),
+                            )
+                            .ok();
+                    }
+                }
+            }
+            ChannelInfo::Reset => {
+                if self.settings.load().send_reset && self.last_reset.elapsed().as_secs() >= 2 {
+                    self.last_reset = Instant::now();
+                    self.send_reset();
+                }
+            }
+            ChannelInfo::Error(report) => {
+                self.errors_tx.send(report).ok();
+            }
+            ChannelInfo::Calibration(calibration) => {
+                self.record(&sn, |r| r.calibration = Some(calibration));
+                if let Some(device) = self.devices.get_mut(&sn) {
+                    device.calibration = Some(calibration);
+                }
+            }
+            ChannelInfo::Buttons(buttons) => {
+                let Some(device) = self.devices.get_mut(&sn) else {
+                    return;
+                };
+                let before = std::mem::replace(&mut device.buttons, buttons);
+                let settings = self.settings.load();
+                if buttons.pressed(before, settings.combo_pause) {
+                    self.set_paused(!self.paused);
+                    let state = if self.paused { "Paused" } else { "Resumed" };
+                    self.errors_tx
+                        .send(ErrorReport::new(&sn, format!("{state} all trackers")))
+                        .ok();
+                }
+                if buttons.pressed(before, settings.combo_reset) {
+                    self.last_reset = Instant::now();
+                    self.send_reset();
+                }
+                if buttons.pressed(before, settings.combo_next_profile) {
+                    let mut next = None;
+                    self.settings.change(|ws| next = ws.profile_next());
+                    let message = match next {
+                        Some(name) => format!("Switched to profile \"{name}\""),
+                        None => "There is no profile to switch to".to_owned(),
+                    };
+                    self.errors_tx.send(ErrorReport::new(&sn, message)).ok();
+                }
+            }
+            ChannelInfo::Firmware(firmware) => {
+                self.record(&sn, |r| r.firmware = Some(firmware));
+                if let Some(device) = self.devices.get_mut(&sn) {
+                    device.firmware = Some(firmware);
+                }
+            }
+            ChannelInfo::Lost(lost) => {
+                if let Some(device) = self.devices.get_mut(&sn) {
+                    device.lost = Some(lost);
+                }
+            }
+            ChannelInfo::Rssi(rssi) => {
+                if let Some(device) = self.devices.get_mut(&sn) {
+                    device.rssi = Some(rssi);
+                }
+            }
+            ChannelInfo::Temperature(temperature) => {
+                if let Some(device) = self.devices.get_mut(&sn) {
+                    device.temperature = Some(temperature);
+                }
+            }
+            ChannelInfo::Disconnected => {
+                info!(serial_number = %sn, "Controller disconnected");
+                // Whatever the device queued before going away.
+                self.poll_streams();
+                if let Some(device) = self.devices.get_mut(&sn) {
+                    device.imu_times = vec![];
+                    device.status = DeviceStatus::Disconnected;
+                    device.lost = None;
+                    device.rssi = None;
+                    device.temperature = None;
+                    device.accel_measurement = None;
+                    // Offline on the server until it is back.
+                    device.handshake(&self.connection, &self.address);
+                }
+            }
+        }
+    }
+
+    fn parse_frames(&mut self, handle: usize, imu_data: [JoyconAxisData; 3], missed: usize) {
+        let device = &mut self.devices.list[handle];
+        let sn = device.serial_number.clone();
+        let sn = &*sn;
+        {
+            let settings = self.settings.load();
+            let fusion = settings.joycon_fusion_get(sn);
+            if device.imu.fusion() != fusion {
+                device.imu = Imu::new(fusion);
+            }
+            if let Some(gain) = settings.joycon_fusion_gain_get(sn) {
+                device.imu.set_gain(gain);
+            }
+        }
+        if let Some(measurement) = &mut device.accel_measurement {
+            measurement.add(&imu_data);
+            if let Some(calibration) = measurement.result() {
+                device.accel_measurement = None;
+                match calibration {
+                    Some(calibration) => {
+                        info!(serial_number = %sn, ?calibration, "Calibrated the accelerometer");
+                        self.settings.change(|ws| {
+                            ws.joycon_accel_calibration_set(sn.to_owned(), Some(calibration))
+                        });
+                    }
+                    None => {
+                        self.errors_tx
+                            .send(
+                                ErrorReport::new(sn, "Accelerometer calibration failed").suggest(
+                                    "Hold each side still until it is taken and try again.",
+                                ),
+                            )
+                            .ok();
+                    }
+                }
+            }
+        }
+        let imu_data = match self.settings.load().joycon_accel_calibration_get(sn) {
+            Some(calibration) => {
+                imu_data.map(|frame| accel_calibration::correct(&calibration, frame))
+            }
+            None => imu_data,
+        };
+        let corrected = if self.settings.load().gyro_bias_refinement {
+            let temperature = device.temperature;
+            device.gyro_bias.update(&imu_data, temperature);
+            imu_data.map(|frame| device.gyro_bias.correct(frame, temperature))
+        } else {
+            imu_data
+        };
+        // Every mode still makes three updates, the fusion expects a frame every 5ms.
+        let fused = match self.settings.load().joycon_frame_mode_get(sn) {
+            FrameMode::All => corrected,
+            FrameMode::Mean => [JoyconAxisData::mean(&corrected); 3],
+            FrameMode::Newest => [corrected[2]; 3],
+        };
+        {
+            let _measure = profile::measure(Stage::Fusion);
+            // The fusion takes a frame every 5ms of the controller's clock, not of when reports
+            // arrive. The first frame of the report stands in for the ones lost before it.
+            if missed <= MAX_MISSED_FRAMES {
+                for _ in 0..missed {
+                    device.imu.update(fused[0]);
+                }
+            }
+            for frame in fused {
+                device.imu.update(frame);
+            }
+        }
+        device.last_frame = Some(imu_data[2]);
+        let now = Instant::now();
+        device.imu_times.push(now);
+        device.cadence.update(now);
+
+        let moving = imu_data.iter().any(is_moving);
+        if moving {
+            device.last_motion = Instant::now();
+        }
+        device.drift.update(&device.imu.rotation, moving);
+
+        device.flat = device.last_motion.elapsed() >= FLAT_SETTLE_TIME
+            && orientation::level_offset(&device.imu.rotation).is_some();
+        if !device.flat {
+            device.leveled = false;
+        } else if !device.leveled && self.settings.load().auto_level {
+            device.leveled = true;
+            if let Some(level) = orientation::level_offset(&device.imu.rotation) {
+                self.settings
+                    .change(|ws| ws.joycon_level_set(sn.to_owned(), level));
+            }
+        }
+        let asleep = self
+            .settings
+            .load()
+            .sleep_timeout()
+            .map_or(false, |timeout| device.last_motion.elapsed() >= timeout);
+        device.set_asleep(asleep, &self.connection, &self.address);
+        let slimevr = self.settings.load().sends_to(sn, Output::SlimeVr);
+        let paused = is_paused(
+            self.paused || self.vr_idle,
+            &self.paused_groups,
+            &self.settings.load(),
+            sn,
+        );
+        if device.slimevr != slimevr || device.paused != paused {
+            device.slimevr = slimevr;
+            device.paused = paused;
+            device.handshake(&self.connection, &self.address);
+        }
+        let idle = self
+            .settings
+            .load()
+            .power_off_timeout()
+            .map_or(false, |timeout| device.last_motion.elapsed() >= timeout);
+        if idle
+            && device
+                .power_off_sent
+                .map_or(true, |sent| sent.elapsed() >= POWER_OFF_RETRY)
+        {
+            device.power_off_sent = Some(Instant::now());
+            device.command(DeviceCommand::PowerOff);
+        }
+        if device.asleep || device.paused {
+            return;
+        }
+
+        let settings = self.settings.load();
+        let script_path = settings.joycon_script_get(sn);
+        if device.script_path != script_path {
+            device.script_path = script_path.to_owned();
+            device.script = None;
+            if !script_path.is_empty() {
+                match Script::load(script_path, sn) {
+                    Ok(script) => device.script = Some(script),
+                    Err(e) => {
+                        self.errors_tx
+                            .send(
+                                ErrorReport::new(sn, format!("Could not load the script: {e}"))
+                                    .suggest("Fix the script and reconnect the tracker."),
+                            )
+                            .ok();
+                    }
+                }
+            }
+        }
+
+        let rad_rotation = (settings.joycon_rotation_get(sn) as f64).to_radians();
+        // The turn rate the fusion last saw, with the bias taken out and after the frame mode.
+        let latest = &fused[2];
+        let predicted = match settings.prediction() {
+            Some(ahead) => {
+                let gyro = Vector3::new(latest.gyro_x, latest.gyro_y, latest.gyro_z);
+                orientation::predict(&device.imu.rotation, &gyro, ahead)
+            }
+            None => device.imu.rotation,
+        };
+        let mut rotated_quat = output_rotation(&settings, sn, &predicted);
+        let acc = calc_acceleration(device.imu.rotation, &imu_data[2], rad_rotation);
+        let mut acc = Vector3::new(acc.x, acc.y, acc.z);
+        if let Some(script) = &mut device.script {
+            match script.transform(rotated_quat, acc) {
+                Ok(transformed) => (rotated_quat, acc) = transformed,
+                Err(e) => {
+                    device.script = None;
+                    self.errors_tx
+                        .send(
+                            ErrorReport::new(sn, format!("The script failed and was stopped: {e}"))
+                                .suggest("Fix the script and reconnect the tracker."),
+                        )
+                        .ok();
+                }
+            }
+        }
+
+        if let Some(held) = device.frozen {
+            rotated_quat = held;
+            acc = Vector3::zeros();
+        }
+
+        if device.slimevr {
+            // Inspection samples are all sent, decimating them would defeat the point.
+            let low_bandwidth = settings.low_bandwidth;
+            let due = device
+                .last_sent
+                .map_or(true, |(time, rotation, acceleration)| {
+                    let elapsed = time.elapsed();
+                    let changed = rotation.angle_to(&rotated_quat) > UNCHANGED_ROTATION
+                        || (acceleration - acc).norm() > UNCHANGED_ACCELERATION;
+                    !low_bandwidth
+                        || elapsed >= UNCHANGED_RESEND
+                        || (changed && elapsed >= LOW_BANDWIDTH_INTERVAL)
+                });
+            // Bundles go out with the other trackers, they can't keep a timeline of their own.
+            let send_at =
+                (due && settings.output_smoothing && !low_bandwidth && device.sender.is_some())
+                    .then(|| device.timeline.schedule(now, device.cadence.interval));
+            let (shared, address, bundle) = (&self.connection, self.address, &mut self.bundle);
+            // A bundle goes out on the shared connection, a device of its own sends on its own.
+            let bundled = low_bandwidth && device.link.is_none();
+            let connection = device.link.as_ref().map_or(shared, |link| &link.connection);
+            let sensor_id = device.sensor_id();
+            let mut send = |packet: PacketType, send_at: Option<Instant>| {
+                let size = if bundled {
+                    if bundle.len() >= MAX_BUNDLE_SIZE {
+                        send_bundle(shared, bundle, address);
+                    }
+                    bundle.push(&packet).unwrap_or_default()
+                } else if let Some(sender) = &device.sender {
+                    // Counted once the sender thread reports it.
+                    match send_at {
+                        Some(time) => sender.send_at(packet, address, time),
+                        None => sender.send(packet, address),
+                    }
+                    return;
+                } else {
+                    send_packet(connection, &packet, address)
+                };
+                device.sent.push((now, size));
+            };
+            if settings.inspection {
+                for frame in &imu_data {
+                    send(
+                        PacketType::Inspection {
+                            packet_id: 0,
+                            typ: INSPECTION_RAW_IMU,
+                            sensor_id,
+                            data_type: INSPECTION_FLOAT,
+                            gyro: (
+                                frame.gyro_x as f32,
+                                frame.gyro_y as f32,
+                                frame.gyro_z as f32,
+                            ),
+                            gyro_accuracy: 0,
+                            accel: (
+                                frame.accel_x as f32,
+                                frame.accel_y as f32,
+                                frame.accel_z as f32,
+                            ),
+                            accel_accuracy: 0,
+                            mag: (0.0, 0.0, 0.0),
+                            mag_accuracy: 0,
+                        },
+                        None,
+                    );
+                }
+            }
+            if due {
+                send(
+                    PacketType::RotationData {
+                        packet_id: 0,
+                        sensor_id,
+                        data_type: 1,
+                        quat: (*rotated_quat).into(),
+                        calibration_info: 0,
+                    },
+                    send_at,
+                );
+                send(
+                    PacketType::Acceleration {
+                        packet_id: 0,
+                        vector: (acc.x as f32, acc.y as f32, acc.z as f32),
+                        sensor_id: Some(sensor_id),
+                    },
+                    send_at,
+                );
+                device.last_sent = Some((now, rotated_quat, acc));
+            }
+            if let Some(sender) = &device.sender {
+                let size = sender.take_sent();
+                if size > 0 {
+                    device.sent.push((now, size));
+                }
+            }
+        }
+
+        if settings.sends_to(sn, Output::Osc) {
+            if let Some(target) = settings.osc_output_target() {
+                if self.osc_socket.is_none() {
+                    self.osc_socket = UdpSocket::bind(SocketAddr::from(([0, 0, 0, 0], 0))).ok();
+                }
+                if let Some(socket) = &self.osc_socket {
+                    let packet =
+                        osc_output::tracker_rotation(u32::from(device.send_id) + 1, &rotated_quat);
+                    socket.send_to(&packet, target).ok();
+                }
+            }
+        }
+    }
+
+    // Frames skip the shared channel, each device has its own queue. A queue that is locked
+    // belongs to a stuck thread the watchdog replaced, it is skipped. One that stays locked is
+    // dropped and reported, the device sends a new one when it reconnects.
+    fn poll_streams(&mut self) -> bool {
+        let mut streams = std::mem::take(&mut self.streams);
+        let mut received = false;
+        let mut abandoned = false;
+        let mut stuck = vec![];
+        for stream in &mut streams {
+            let mut frames = match stream.frames.try_lock() {
+                Ok(frames) => frames,
+                Err(TryLockError::Poisoned(frames)) => frames.into_inner(),
+                Err(TryLockError::WouldBlock) => {
+                    let since = *stream.locked_since.get_or_insert_with(Instant::now);
+                    if since.elapsed() >= STREAM_STUCK {
+                        stuck.push(stream.frames.clone());
+                    }
+                    continue;
+                }
+            };
+            stream.locked_since = None;
+            while let Ok((imu_data, missed)) = frames.pop() {
+                received = true;
+                self.parse_frames(stream.handle, imu_data, missed);
+            }
+            abandoned |= frames.is_abandoned();
+        }
+        let dropped = |frames: &Arc<Mutex<FrameConsumer>>| {
+            is_abandoned(frames) || stuck.iter().any(|stuck| Arc::ptr_eq(stuck, frames))
+        };
+        if abandoned || !stuck.is_empty() {
+            for stream in streams.iter().filter(|stream| dropped(&stream.frames)) {
+                if stream.locked_since.is_some() {
+                    let sn = &self.devices.list[stream.handle].serial_number;
+                    self.errors_tx
+                        .send(
+                            ErrorReport::new(&**sn, "Stopped reading this controller, it is stuck")
+                                .suggest("Turn the controller off and on again to reconnect it."),
+                        )
+                        .ok();
+                }
+            }
+            streams.retain(|stream| !dropped(&stream.frames));
+            for registration in lock(&self.durable.registrations).iter_mut() {
+                if registration.stream.as_ref().map_or(false, dropped) {
+                    registration.stream = None;
+                }
+            }
+        }
+        self.streams = streams;
+        received
+    }
+
+    fn parse_action(&mut self, action: Action) {
+        match action {
+            Action::CaptureMounting(sn) => {
+                if let Some(device) = self.devices.get(&sn) {
+                    let mounting = orientation::neutral_offset(&mounted_rotation(
+                        &self.settings.load(),
+                        &sn,
+                        &device.imu.rotation,
+                    ));
+                    self.settings
+                        .change(|ws| ws.joycon_mounting_set(sn, mounting));
+                }
+            }
+            Action::CaptureStanding(sn) => {
+                if let Some(device) = self.devices.get_mut(&sn) {
+                    device.standing_pose = Some(mounted_rotation(
+                        &self.settings.load(),
+                        &sn,
+                        &device.imu.rotation,
+                    ));
+                }
+            }
+            Action::CaptureBent(sn) => {
+                if let Some(device) = self.devices.get_mut(&sn) {
+                    let bent = mounted_rotation(&self.settings.load(), &sn, &device.imu.rotation);
+                    let mounting = device
+                        .standing_pose
+                        .take()
+                        .and_then(|standing| orientation::two_pose_offset(&standing, &bent));
+                    match mounting {
+                        Some(mounting) => self
+                            .settings
+                            .change(|ws| ws.joycon_mounting_set(sn, mounting)),
+                        None => {
+                            self.errors_tx
+                                .send(ErrorReport::new(sn, "Mounting calibration failed").suggest(
+                                    "Bend forward further in the second pose and try again.",
+                                ))
+                                .ok();
+                        }
+                    }
+                }
+            }
+            Action::Level(sn) => {
+                if let Some(device) = self.devices.get_mut(&sn) {
+                    if let Some(level) = orientation::level_offset(&device.imu.rotation) {
+                        device.leveled = true;
+                        self.settings.change(|ws| ws.joycon_level_set(sn, level));
+                    }
+                }
+            }
+            Action::Identify(sn) => self.command(&sn, DeviceCommand::Identify),
+            Action::CalibrateImu(sn) => self.command(&sn, DeviceCommand::Calibrate),
+            Action::WriteCalibration(sn) => self.command(&sn, DeviceCommand::WriteCalibration),
+            Action::RestoreCalibration(sn) => self.command(&sn, DeviceCommand::RestoreCalibration),
+            Action::CalibrateAccel(sn, start) => {
+                if let Some(device) = self.devices.get_mut(&sn) {
+                    device.accel_measurement = start.then(AccelMeasurement::default);
+                }
+            }
+            Action::ResetAll => self.send_reset(),
+            Action::Pause(paused) => self.set_paused(paused),
+            Action::PauseGroup(group, paused) => {
+                set_group_paused(&self.durable.paused_groups, &group, paused);
+                self.paused_groups = lock(&self.durable.paused_groups).clone();
+                self.update_paused();
+            }
+            Action::VrIdle(idle) => {
+                self.vr_idle = idle;
+                self.update_paused();
+            }
+            Action::AlignAll => self.align(None),
+            Action::AlignGroup(group) => self.align(Some(&group)),
+            Action::Freeze(sn, frozen) => self.freeze(&sn, frozen),
+        }
+    }
+
+    fn freeze(&mut self, sn: &str, frozen: bool) {
+        let settings = self.settings.load();
+        let Some(device) = self.devices.get_mut(sn) else {
+            return;
+        };
+        if frozen {
+            if device.frozen.is_none() {
+                let current = output_rotation(&settings, sn, &device.imu.rotation);
+                device.frozen = Some(device.last_sent.map_or(current, |(_, sent, _)| sent));
+            }
+            return;
+        }
+        let Some(held) = device.frozen.take() else {
+            return;
+        };
+        let current = output_rotation(&settings, sn, &device.imu.rotation);
+        let (_, _, held_yaw) = orientation::yaw_component(&held).euler_angles();
+        let (_, _, yaw) = orientation::yaw_component(&current).euler_angles();
+        let offset = settings.joycon_yaw_offset_get(sn) + (held_yaw - yaw).to_degrees();
+        self.settings
+            .change(|ws| ws.joycon_yaw_offset_set(sn.to_owned(), offset));
+    }
+
+    // Turns the heading of every connected tracker, or those of `group`, to zero.
+    fn align(&mut self, group: Option<&str>) {
+        let settings = self.settings.load();
+        let offsets: Vec<_> = self
+            .devices
+            .iter()
+            .filter(|(_, device)| device.status != DeviceStatus::Disconnected)
+            .filter(|(sn, _)| group.map_or(true, |group| settings.joycon_group_get(sn) == group))
+            .map(|(sn, device)| {
+                let rotation = output_rotation(&settings, sn, &device.imu.rotation);
+                let (_, _, yaw) = orientation::yaw_component(&rotation).euler_angles();
+                // The trim is folded into the offset, aligned trackers start without one.
+                let offset = settings.joycon_yaw_offset_get(sn) + settings.joycon_yaw_trim_get(sn)
+                    - yaw.to_degrees();
+                (sn.to_owned(), offset)
+            })
+            .collect();
+        self.settings.change(|ws| {
+            for (sn, offset) in offsets {
+                ws.joycon_yaw_trim_set(sn.clone(), 0.0);
+                ws.joycon_yaw_offset_set(sn, offset);
+            }
+        });
+    }
+
+    fn update_statuses(&mut self) {
+        let now = Instant::now();
+        let recent = |t: &Instant| now.duration_since(*t) < Duration::from_secs(1);
+        let searching = self.connected != ServerStatus::Connected;
+        let vr_idle = self.vr_idle;
+        for device in self.devices.values_mut() {
+            device.imu_times.retain(recent);
+            device.sent.retain(|(t, _)| recent(t));
+            match device.imu_times.len() {
+                x if x > 0 && device.asleep => {
+                    device.status = DeviceStatus::Asleep;
+                }
+                x if x >= 55 => {
+                    device.status = DeviceStatus::Healthy;
+                }
+                x if x > 0 => {
+                    device.status = DeviceStatus::LaggyIMU;
+                }
+                _ => {
+                    if device.status != DeviceStatus::Disconnected {
+                        device.status = DeviceStatus::NoIMU;
+                    }
+                }
+            }
+            let lights = match device.status {
+                DeviceStatus::NoIMU | DeviceStatus::LaggyIMU => LightState::Error,
+                _ if vr_idle => LightState::Idle,
+                _ if searching => LightState::Searching,
+                _ => LightState::Streaming,
+            };
+            if device.lights != Some(lights) {
+                device.lights = Some(lights);
+                device.command(DeviceCommand::Lights(lights));
+            }
+        }
+    }
+
+    // Until just before the first device is due to send its next report.
+    fn idle_sleep(&self) -> Duration {
+        let now = Instant::now();
+        self.devices
+            .values()
+            .filter_map(|device| device.cadence.next_due(now))
+            .min()
+            .map_or(MAX_IDLE_SLEEP, |due| {
+                due.saturating_duration_since(now)
+                    .saturating_sub(WAKE_MARGIN)
+            })
+            .clamp(MIN_IDLE_SLEEP, MAX_IDLE_SLEEP)
+    }
+
+    // Only believed while not connected, a reset may also be about a packet to another address
+    // and pings tell better once the server answered. Handshakes keep going out, so the server
+    // is found once it runs.
+    fn set_unreachable(&mut self) {
+        if matches!(
+            self.connected,
+            ServerStatus::Connected | ServerStatus::Unreachable | ServerStatus::SendFailed
+        ) {
+            return;
+        }
+        info!(address = %self.address, "Nothing listens at the server address");
+        self.connected = ServerStatus::Unreachable;
+        self.server_tx.send(self.connected);
+    }
+
+    // Reported once, the handshakes then find out when sending works again.
+    fn set_send_failed(&mut self, error: String) {
+        if self.connected == ServerStatus::SendFailed {
+            return;
+        }
+        self.connected = ServerStatus::SendFailed;
+        self.server_tx.send(self.connected);
+        self.errors_tx
+            .send(
+                ErrorReport::new(
+                    "Server",
+                    format!("Sending to the SlimeVR server fails: {error}"),
+                )
+                .suggest("Check the network connection, wrangler keeps retrying."),
+            )
+            .ok();
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        self.durable.paused.store(paused, Ordering::Relaxed);
+        self.paused = paused;
+        self.update_paused();
+    }
+
+    // Tells the server right away about the trackers that were paused or resumed.
+    fn update_paused(&mut self) {
+        let settings = self.settings.load();
+        for device in self.devices.values_mut().sorted_by_key(|d| d.send_id) {
+            let paused = is_paused(
+                self.paused || self.vr_idle,
+                &self.paused_groups,
+                &settings,
+                &device.serial_number,
+            );
+            if device.paused != paused {
+                device.paused = paused;
+                device.handshake(&self.connection, &self.address);
+            }
+        }
+    }
+
+    // Keeps the flag for the devices it is meant for, so it survives reconnects, and confirms it.
+    fn set_config_flag(&mut self, sensor_id: u8, flag_id: u16, state: bool) {
+        let serial_numbers: Vec<_> = self
+            .devices
+            .iter()
+            .filter(|(_, device)| {
+                device.link.is_none() && (sensor_id == ALL_SENSORS || device.send_id == sensor_id)
+            })
+            .map(|(serial_number, _)| serial_number.to_string())
+            .collect();
+        if serial_numbers.is_empty() {
+            return;
+        }
+        self.settings.change(|ws| {
+            for serial_number in serial_numbers {
+                ws.joycon_config_flag_set(serial_number, flag_id, state);
+            }
+        });
+        let ack = PacketType::AckConfigChange {
+            packet_id: 0,
+            sensor_id,
+            flag_id,
+        };
+        send_packet(&self.connection, &ack, self.address);
+    }
+
+    // Sends everything to `address` from now on, it gets a handshake right away.
+    fn retarget(&mut self, address: SocketAddr) {
+        self.address = address;
+        for link in self
+            .devices
+            .values_mut()
+            .filter_map(|device| device.link.as_mut())
+        {
+            link.reset();
+        }
+        self.candidate = None;
+        self.connected = ServerStatus::Disconnected;
+        self.server_tx.send(self.connected);
+        self.last_handshake = None;
+        self.handshake_interval = HANDSHAKE_INTERVAL;
+    }
+
+    // A packet from somewhere else than the server. While the server is silent, a sender on the
+    // configured host is sent a handshake, and if it answers like a server, it is followed to its
+    // new port. Other hosts are never followed, that could be anyone on the network.
+    fn follow(&mut self, from: SocketAddr, packet: &[u8]) {
+        if from.ip() != self.configured_address.ip() || self.last_ping.elapsed() < SERVER_SILENCE {
+            return;
+        }
+        let confirmed = self
+            .candidate
+            .map_or(false, |(candidate, _)| candidate == from)
+            && matches!(
+                PacketType::from_bytes((packet, 0)),
+                Ok((_, PacketType::HandshakeResponse))
+            );
+        if confirmed {
+            self.retarget(from);
+            self.errors_tx
+                .send(
+                    ErrorReport::new(
+                        "Server",
+                        format!("The server moved to port {}", from.port()),
+                    )
+                    .suggest("Update the server port in the settings if it stays there."),
+                )
+                .ok();
+        } else if self
+            .candidate
+            .map_or(true, |(_, asked)| asked.elapsed() >= CANDIDATE_RETRY)
+        {
+            self.candidate = Some((from, Instant::now()));
+            send_packet_elsewhere(&self.connection, &self.handshake_packet(), from);
+        }
+    }
+
+    pub fn main_loop(&mut self) {
+        let mut buf = [0; 512];
+
+        // Spin sleeper with 1ns accuracy. The accuracy is backwards, it means that a request for
+        // X sleep will actually sleep for X - 1ns then spin for 1ns max.
+        // It is used here because it also sets the minimum Windows sleep time to 1ms instead of 15ms.
+        let light_sleeper = spin_sleep::SpinSleeper::new(1)
+            .with_spin_strategy(spin_sleep::SpinStrategy::YieldThread);
+
+        let mut last_ui_send = Instant::now();
+
+        loop {
+            if self.durable.generation.load(Ordering::Relaxed) != self.generation {
+                return;
+            }
+            self.durable.beat();
+            let address = *lock(&self.durable.server_address);
+            if address != self.configured_address {
+                self.configured_address = address;
+                self.retarget(address);
+            }
+            // Without the SlimeVR output there is no server to look for.
+            let slimevr = self.settings.load().output_slimevr;
+            if slimevr
+                && self.connected != ServerStatus::Connected
+                && self
+                    .last_handshake
+                    .map_or(true, |sent| sent.elapsed() >= self.handshake_interval)
+            {
+                self.last_handshake = Some(Instant::now());
+                let sent = self.send_handshake();
+                if self.connected == ServerStatus::SendFailed {
+                    if sent > 0 {
+                        // What failed before this one doesn't count anymore.
+                        take_send_error();
+                        info!("Sending to the server works again");
+                        self.connected = ServerStatus::Disconnected;
+                        self.server_tx.send(self.connected);
+                        self.handshake_interval = HANDSHAKE_INTERVAL;
+                    } else {
+                        self.handshake_interval = (self.handshake_interval * 2).min(MAX_SEND_RETRY);
+                    }
+                }
+                for device in self.devices.values_mut().sorted_by_key(|d| d.send_id) {
+                    device.handshake(&self.connection, &self.address);
+                }
+            }
+            loop {
+                let (len, from) = match self.connection.socket().recv_from(&mut buf) {
+                    Ok(received) => received,
+                    Err(e) if e.kind() == io::ErrorKind::ConnectionReset => {
+                        self.set_unreachable();
+                        continue;
+                    }
+                    Err(_) => break,
+                };
+                if from != self.address {
+                    self.follow(from, &buf[0..len]);
+                    continue;
+                }
+                if matches!(
+                    self.connected,
+                    ServerStatus::Disconnected | ServerStatus::Unreachable
+                ) {
+                    self.connected = ServerStatus::Unknown;
+                    self.server_tx.send(self.connected);
+                }
+                let b = PacketType::from_bytes((&buf, 0));
+                match b {
+                    Ok((_, PacketType::Ping { id: _ })) => {
+                        self.last_ping = Instant::now();
+                        send_bytes(&self.connection, &buf[0..len], self.address);
+                    }
+                    Ok((_, PacketType::HandshakeResponse)) => {
+                        self.last_reply = Some(PacketLog::received(&buf[0..len]));
+                        if self.connected != ServerStatus::Connected {
+                            info!(address = %self.address, "Connected to the SlimeVR server");
+                            if let Some(warning) = Compatibility::of(&buf[0..len]).warning() {
+                                self.errors_tx.send(warning).ok();
+                            }
+                        }
+                        self.connected = ServerStatus::Connected;
+                        self.server_tx.send(self.connected);
+                    }
+                    Ok((
+                        _,
+                        PacketType::SetConfigFlag {
+                            sensor_id,
+                            flag_id,
+                            state,
+                            ..
+                        },
+                    )) => {
+                        self.set_config_flag(sensor_id, flag_id, state);
+                    }
+                    // The heartbeats of the server and anything else, receiving them already
+                    // told that the server is there.
+                    _ => {}
+                }
+            }
+            if slimevr {
+                self.serve_links(&mut buf);
+            }
+            if take_connection_reset() {
+                self.set_unreachable();
+            }
+            if let Some(error) = take_send_error() {
+                self.set_send_failed(error);
+            }
+            if slimevr
+                && matches!(
+                    self.connected,
+                    ServerStatus::Unknown | ServerStatus::Connected
+                )
+                && self.last_ping.elapsed().as_secs() >= 3
+            {
+                self.connected = ServerStatus::Disconnected;
+                self.server_tx.send(self.connected);
+                self.errors_tx
+                    .send(
+                        ErrorReport::new("Server", "Lost connection to the SlimeVR server")
+                            .suggest(
+                                "Check that the server is running and the address in the settings.",
+                            ),
+                    )
+                    .ok();
+            }
+
+            let actions: Vec<_> = lock(&self.durable.actions).try_iter().collect();
+            for action in actions {
+                self.parse_action(action);
+            }
+
+            self.send_telemetry();
+            self.send_battery();
+            if self.connected == ServerStatus::Connected
+                && self.last_heartbeat.elapsed() >= HEARTBEAT_INTERVAL
+            {
+                self.last_heartbeat = Instant::now();
+                send_packet(
+                    &self.connection,
+                    &PacketType::Heartbeat { packet_id: 0 },
+                    self.address,
+                );
+            }
+
+            let streamed = self.poll_streams();
+            if !self.bundle.is_empty() {
+                send_bundle(&self.connection, &mut self.bundle, self.address);
+            }
+            let messages: Vec<_> = lock(&self.durable.receive).try_iter().collect();
+            let busy = streamed || !messages.is_empty();
+            for msg in messages {
+                self.parse_message(msg);
+            }
+            // Only as often as the GUI redraws, building them costs more with every tracker.
+            if last_ui_send.elapsed() >= self.settings.load().gui_refresh_interval() {
+                self.update_statuses();
+
+                last_ui_send = Instant::now();
+                let settings = self.settings.load();
+                let mut statuses = Vec::new();
+                for (serial_number, device) in self.devices.iter() {
+                    statuses.push(Status {
+                        rotation: device.imu.euler_angles_deg(),
+                        orientation: device.frozen.unwrap_or_else(|| {
+                            output_rotation(&settings, serial_number, &device.imu.rotation)
+                        }),
+                        design: device.design.clone(),
+                        serial_number: serial_number.to_owned(),
+                        battery: device.battery,
+                        status: device.status,
+                        drift: device.drift.rate(),
+                        flat: device.flat,
+                        rate: device.imu_times.len(),
+                        bandwidth: device.sent.iter().map(|(_, size)| size).sum(),
+                        calibration: device.calibration,
+                        firmware: device.firmware,
+                        lost: device.lost,
+                        rssi: device.rssi,
+                        temperature: device.temperature,
+                        accel_calibration: device
+                            .accel_measurement
+                            .as_ref()
+                            .map(AccelMeasurement::sides),
+                        frozen: device.frozen.is_some(),
+                    });
+                }
+                self.status_tx.send(statuses).ok();
+
+                let handshake_log = self.handshake_log();
+                if handshake_log != self.sent_handshake_log {
+                    self.handshake_tx.send(handshake_log.clone());
+                    self.sent_handshake_log = handshake_log;
+                }
+            }
+            if !busy {
+                light_sleeper.sleep(self.idle_sleep());
+            }
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use std::{
+    collections::BTreeSet,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    },
+};
+
+use super::queue::{bounded, BoundedSender, Overflow, Queue, STATUS_QUEUE_SIZE};
+use super::watchdog::{lock, set_group_paused};
+use super::{rescan, Action, DeviceSender, Status};
+use crate::settings;
+
+/// Control surface shared by the IPC interfaces (D-Bus, named pipe, gRPC), MQTT and OSC.
+pub struct Control {
+    actions: Mutex<BoundedSender<Action>>,
+    devices: Mutex<DeviceSender>,
+    settings: settings::Handler,
+    statuses: Arc<Mutex<Vec<Status>>>,
+    paused: Arc<AtomicBool>,
+    paused_groups: Arc<Mutex<BTreeSet<String>>>,
+}
+impl Control {
+    fn action(&self, action: Action) {
+        if let Ok(actions) = self.actions.lock() {
+            actions.send(action).ok();
+        }
+    }
+    /// Yaw reset on the SlimeVR server.
+    pub fn reset(&self) {
+        self.action(Action::ResetAll);
+    }
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+        self.action(Action::Pause(paused));
+    }
+    pub fn paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+    /// Turns the heading of the trackers in `group` to zero, a yaw reset of only these.
+    pub fn reset_group(&self, group: &str) {
+        if group.is_empty() {
+            return;
+        }
+        self.action(Action::AlignGroup(group.to_owned()));
+    }
+    pub fn set_group_paused(&self, group: &str, paused: bool) {
+        if group.is_empty() {
+            return;
+        }
+        set_group_paused(&self.paused_groups, group, paused);
+        self.action(Action::PauseGroup(group.to_owned(), paused));
+    }
+    pub fn paused_groups(&self) -> Vec<String> {
+        lock(&self.paused_groups).iter().cloned().collect()
+    }
+    /// Show the identify lights on every tracker in `group`.
+    pub fn identify_group(&self, group: &str) {
+        for serial_number in self.settings.load().group_members(group) {
+            self.identify(serial_number);
+        }
+    }
+    /// Look for new Joy-Cons right away.
+    pub fn rescan(&self) {
+        if let Ok(devices) = self.devices.lock() {
+            rescan(&devices, &self.settings);
+        }
+    }
+    /// Show the identify lights on a tracker for a few seconds.
+    pub fn identify(&self, serial_number: String) {
+        self.action(Action::Identify(serial_number));
+    }
+    pub fn settings(&self) -> &settings::Handler {
+        &self.settings
+    }
+    /// Latest statuses of all devices.
+    pub fn statuses(&self) -> Vec<Status> {
+        self.statuses
+            .lock()
+            .map(|statuses| statuses.clone())
+            .unwrap_or_default()
+    }
+    /// Serial number, status, battery and IMU reports per second of every tracker.
+    pub fn devices(&self) -> Vec<(String, String, String, u32)> {
+        self.statuses()
+            .into_iter()
+            .map(|status| {
+                (
+                    status.serial_number,
+                    status.status.to_string(),
+                    format!("{:?}", status.battery),
+                    status.rate as u32,
+                )
+            })
+            .collect()
+    }
+}
+
+/// Creates the control surface. It sees the statuses on their way to the GUI, which reads them
+/// from the returned receiver instead.
+pub fn spawn(
+    actions: BoundedSender<Action>,
+    devices: DeviceSender,
+    settings: settings::Handler,
+    status_rx: mpsc::Receiver<Vec<Status>>,
+    paused: Arc<AtomicBool>,
+    paused_groups: Arc<Mutex<BTreeSet<String>>>,
+) -> (Arc<Control>, mpsc::Receiver<Vec<Status>>) {
+    let statuses = Arc::new(Mutex::new(Vec::new()));
+    let control = Control {
+        actions: Mutex::new(actions),
+        devices: Mutex::new(devices),
+        settings,
+        statuses: statuses.clone(),
+        paused,
+        paused_groups,
+    };
+
+    let (gui_tx, gui_rx) = bounded(STATUS_QUEUE_SIZE, Overflow::Drop(Queue::Statuses));
+    std::thread::spawn(move || {
+        for status in status_rx {
+            if let Ok(mut statuses) = statuses.lock() {
+                statuses.clone_from(&status);
+            }
+            if gui_tx.send(status).is_err() {
+                break;
+            }
+        }
+    });
+    (Arc::new(control), gui_rx)
+}
@@ -0,0 +1,70 @@
+use std::sync::Arc;
+
+use zbus::dbus_interface;
+
+use super::control::Control;
+
+const BUS_NAME: &str = "dev.slimevr.Wrangler";
+const OBJECT_PATH: &str = "/dev/slimevr/Wrangler";
+
+/// The `dev.slimevr.Wrangler1` interface on the session bus, for desktop integrations.
+struct DbusControl(Arc<Control>);
+
+#[dbus_interface(name = "dev.slimevr.Wrangler1")]
+impl DbusControl {
+    /// Yaw reset on the SlimeVR server.
+    fn reset(&self) {
+        self.0.reset();
+    }
+    /// Stop sending tracker data to the server until `Resume` is called.
+    fn pause(&self) {
+        self.0.set_paused(true);
+    }
+    fn resume(&self) {
+        self.0.set_paused(false);
+    }
+    /// Yaw reset of only the trackers in a group, done by wrangler.
+    fn reset_group(&self, group: &str) {
+        self.0.reset_group(group);
+    }
+    fn pause_group(&self, group: &str) {
+        self.0.set_group_paused(group, true);
+    }
+    fn resume_group(&self, group: &str) {
+        self.0.set_group_paused(group, false);
+    }
+    fn identify_group(&self, group: &str) {
+        self.0.identify_group(group);
+    }
+    /// Look for new Joy-Cons right away.
+    fn rescan(&self) {
+        self.0.rescan();
+    }
+    #[dbus_interface(property)]
+    fn paused(&self) -> bool {
+        self.0.paused()
+    }
+    #[dbus_interface(property)]
+    fn paused_groups(&self) -> Vec<String> {
+        self.0.paused_groups()
+    }
+    /// Serial number, status, battery and IMU reports per second of every tracker.
+    #[dbus_interface(property)]
+    fn devices(&self) -> Vec<(String, String, String, u32)> {
+        self.0.devices()
+    }
+}
+
+/// Serves [`Control`] on the session bus until the process exits. Returns early if there is no
+/// session bus or another wrangler already owns the name.
+#[tokio::main]
+pub async fn serve(control: Arc<Control>) {
+    let connection = zbus::ConnectionBuilder::session()
+        .and_then(|builder| builder.name(BUS_NAME))
+        .and_then(|builder| builder.serve_at(OBJECT_PATH, DbusControl(control)));
+    if let Ok(builder) = connection {
+        if let Ok(_connection) = builder.build().await {
+            std::future::pending::<()>().await;
+        }
+    }
+}
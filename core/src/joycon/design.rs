@@ -0,0 +1,14 @@
+/// Which controller model a device is, used to pick the picture shown for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum JoyconDesignType {
+    Left,
+    Right,
+    Pro,
+}
+
+/// Look of a connected controller: its model and body color as a `#rrggbb` string.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct JoyconDesign {
+    pub color: String,
+    pub design_type: JoyconDesignType,
+}
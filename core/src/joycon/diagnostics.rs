@@ -0,0 +1,175 @@
+use std::{
+    collections::BTreeMap,
+    ops::RangeInclusive,
+    panic::Location,
+    time::{Instant, SystemTime},
+};
+
+use itertools::Itertools;
+use protocol::{deku::DekuContainerWrite, PacketType};
+use tracing::warn;
+
+/// A packet as exchanged with the server, decoded into named fields.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PacketLog {
+    pub time: Instant,
+    pub fields: Vec<(&'static str, String)>,
+    pub bytes: Vec<u8>,
+}
+impl PacketLog {
+    pub fn sent(packet: &PacketType, time: Instant) -> Self {
+        Self {
+            time,
+            fields: describe(packet),
+            bytes: packet.to_bytes().unwrap_or_default(),
+        }
+    }
+    pub fn received(bytes: &[u8]) -> Self {
+        let text: String = bytes
+            .iter()
+            .map(|&b| if b.is_ascii_graphic() { b as char } else { '.' })
+            .collect();
+        Self {
+            time: Instant::now(),
+            fields: vec![("Text", text)],
+            bytes: bytes.to_vec(),
+        }
+    }
+    pub fn hex(&self) -> String {
+        self.bytes.iter().map(|b| format!("{b:02x}")).join(" ")
+    }
+}
+
+/// The handshake and SensorInfo packets last sent, and the last reply of the server.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HandshakeLog {
+    pub handshake: Option<PacketLog>,
+    /// By sensor id.
+    pub sensor_info: BTreeMap<u8, PacketLog>,
+    pub reply: Option<PacketLog>,
+}
+
+impl HandshakeLog {
+    pub fn compatibility(&self) -> Compatibility {
+        self.reply
+            .as_ref()
+            .map_or(Compatibility::Supported, |reply| {
+                Compatibility::of(&reply.bytes)
+            })
+    }
+}
+
+/// Protocol versions of the SlimeVR server that wrangler was made for, from the number at the
+/// end of its handshake reply. Older servers don't know the packets wrangler sends.
+pub const SERVER_PROTOCOLS: RangeInclusive<u32> = 5..=5;
+
+/// How the server that answered fits [`SERVER_PROTOCOLS`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compatibility {
+    /// Also for a server that doesn't tell its version.
+    Supported,
+    TooOld(u32),
+    Newer(u32),
+}
+impl Compatibility {
+    /// From the handshake reply of the server.
+    pub fn of(reply: &[u8]) -> Self {
+        match protocol::server_protocol_version(reply) {
+            Some(version) if version < *SERVER_PROTOCOLS.start() => Compatibility::TooOld(version),
+            Some(version) if version > *SERVER_PROTOCOLS.end() => Compatibility::Newer(version),
+            _ => Compatibility::Supported,
+        }
+    }
+    /// What to tell the user, nothing for a supported server.
+    pub fn warning(self) -> Option<ErrorReport> {
+        match self {
+            Compatibility::Supported => None,
+            Compatibility::TooOld(version) => Some(
+                ErrorReport::new(
+                    "Server",
+                    format!(
+                        "The SlimeVR server is too old (protocol {version}, wrangler needs {}), it ignores the rotations of the trackers",
+                        SERVER_PROTOCOLS.start()
+                    ),
+                )
+                .suggest("Update the SlimeVR server to its latest release."),
+            ),
+            Compatibility::Newer(version) => Some(
+                ErrorReport::new(
+                    "Server",
+                    format!(
+                        "The SlimeVR server is newer than this wrangler knows (protocol {version}, wrangler knows up to {})",
+                        SERVER_PROTOCOLS.end()
+                    ),
+                )
+                .suggest("The trackers should still work, update wrangler if they don't."),
+            ),
+        }
+    }
+}
+
+fn describe(packet: &PacketType) -> Vec<(&'static str, String)> {
+    match packet {
+        PacketType::Handshake {
+            board,
+            imu,
+            mcu_type,
+            imu_info,
+            build,
+            firmware,
+            mac_address,
+            ..
+        } => vec![
+            ("Board", board.to_string()),
+            ("IMU", imu.to_string()),
+            ("MCU type", mcu_type.to_string()),
+            ("IMU info", format!("{imu_info:?}")),
+            ("Build", build.to_string()),
+            ("Firmware", firmware.to_string_lossy()),
+            (
+                "MAC address",
+                mac_address.iter().map(|b| format!("{b:02X}")).join(":"),
+            ),
+        ],
+        PacketType::SensorInfo {
+            sensor_id,
+            sensor_status,
+            sensor_type,
+            ..
+        } => vec![
+            ("Sensor id", sensor_id.to_string()),
+            ("Status", sensor_status.to_string()),
+            ("Type", sensor_type.to_string()),
+        ],
+        _ => vec![],
+    }
+}
+
+/// A failure in one of the background threads, surfaced in the GUI instead of being dropped. It
+/// is logged as it is made, where it happened, whether or not anything shows it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorReport {
+    pub time: SystemTime,
+    /// Serial number of the device, or the part of wrangler the error came from.
+    pub source: String,
+    pub message: String,
+    /// What the user can try to resolve it.
+    pub suggestion: Option<String>,
+}
+impl ErrorReport {
+    #[track_caller]
+    pub fn new(source: impl Into<String>, message: impl Into<String>) -> Self {
+        let (source, message) = (source.into(), message.into());
+        warn!(%source, at = %Location::caller(), "{message}");
+        Self {
+            time: SystemTime::now(),
+            source,
+            message,
+            suggestion: None,
+        }
+    }
+    pub fn suggest(mut self, suggestion: impl Into<String>) -> Self {
+        self.suggestion = Some(suggestion.into());
+        self
+    }
+}
@@ -0,0 +1,172 @@
+use nalgebra::{Matrix3, Matrix3x4, Matrix4, Matrix4x3, SMatrix, SVector, UnitQuaternion, Vector3};
+
+type Matrix7 = SMatrix<f64, 7, 7>;
+
+// Tuning for the Joy-Con IMU, in radians/s, radians/s per sqrt(s) and G.
+const GYRO_NOISE: f64 = 0.01;
+const GYRO_BIAS_WALK: f64 = 0.0005;
+const ACCEL_NOISE: f64 = 0.05;
+// Accelerometer readings further than this from 1G are treated as motion and skipped.
+const ACCEL_REJECT: f64 = 0.2;
+
+/// Extended Kalman filter estimating the orientation quaternion together with the gyro bias.
+///
+/// The state is `[w, i, j, k, bx, by, bz]`. Gyro readings drive the prediction and the
+/// direction of gravity from the accelerometer corrects pitch and roll, z is up.
+pub struct Ekf {
+    dt: f64,
+    x: SVector<f64, 7>,
+    p: Matrix7,
+    initialized: bool,
+}
+
+impl Ekf {
+    pub fn new(dt: f64) -> Self {
+        let mut x = SVector::zeros();
+        x[0] = 1.0;
+        let mut p = Matrix7::identity() * 1e-4;
+        p.fixed_view_mut::<4, 4>(0, 0).fill_diagonal(0.1);
+        Self {
+            dt,
+            x,
+            p,
+            initialized: false,
+        }
+    }
+
+    pub fn rotation(&self) -> UnitQuaternion<f64> {
+        UnitQuaternion::from_quaternion(nalgebra::Quaternion::new(
+            self.x[0], self.x[1], self.x[2], self.x[3],
+        ))
+    }
+
+    pub fn update(&mut self, gyro: Vector3<f64>, acc: Vector3<f64>) {
+        if !self.initialized {
+            // Start level with the first gravity reading instead of waiting for convergence.
+            if let Some(q) = UnitQuaternion::rotation_between(&acc, &Vector3::z()) {
+                self.set_rotation(&q);
+            }
+            self.initialized = true;
+        }
+        self.predict(gyro);
+        if (acc.norm() - 1.0).abs() < ACCEL_REJECT {
+            self.correct(acc.normalize());
+        }
+    }
+
+    fn set_rotation(&mut self, q: &UnitQuaternion<f64>) {
+        let q = q.quaternion();
+        self.x[0] = q.w;
+        self.x[1] = q.i;
+        self.x[2] = q.j;
+        self.x[3] = q.k;
+    }
+
+    fn normalize(&mut self) {
+        let q = self.rotation();
+        self.set_rotation(&q);
+    }
+
+    fn predict(&mut self, gyro: Vector3<f64>) {
+        let w = gyro - self.x.fixed_rows::<3>(4);
+        let (q0, q1, q2, q3) = (self.x[0], self.x[1], self.x[2], self.x[3]);
+
+        // q' = q + dt/2 * q ⊗ (0, w)
+        #[rustfmt::skip]
+        let omega = Matrix4::new(
+            0.0, -w.x, -w.y, -w.z,
+            w.x,  0.0,  w.z, -w.y,
+            w.y, -w.z,  0.0,  w.x,
+            w.z,  w.y, -w.x,  0.0,
+        );
+        #[rustfmt::skip]
+        let xi = Matrix4x3::new(
+            -q1, -q2, -q3,
+             q0, -q3,  q2,
+             q3,  q0, -q1,
+            -q2,  q1,  q0,
+        );
+        let half_dt = 0.5 * self.dt;
+
+        let f_q = Matrix4::identity() + omega * half_dt;
+        let q = f_q * self.x.fixed_rows::<4>(0);
+        self.x.fixed_rows_mut::<4>(0).copy_from(&q);
+
+        let mut f = Matrix7::identity();
+        f.fixed_view_mut::<4, 4>(0, 0).copy_from(&f_q);
+        f.fixed_view_mut::<4, 3>(0, 4).copy_from(&(-xi * half_dt));
+
+        let mut q_noise = Matrix7::zeros();
+        q_noise
+            .fixed_view_mut::<4, 4>(0, 0)
+            .copy_from(&(xi * xi.transpose() * (GYRO_NOISE * half_dt).powi(2)));
+        q_noise
+            .fixed_view_mut::<3, 3>(4, 4)
+            .fill_diagonal(GYRO_BIAS_WALK.powi(2) * self.dt);
+
+        self.p = f * self.p * f.transpose() + q_noise;
+        self.normalize();
+    }
+
+    fn correct(&mut self, acc: Vector3<f64>) {
+        let (q0, q1, q2, q3) = (self.x[0], self.x[1], self.x[2], self.x[3]);
+
+        // Gravity (world z) expressed in the sensor frame.
+        let h = Vector3::new(
+            2.0 * (q1 * q3 - q0 * q2),
+            2.0 * (q2 * q3 + q0 * q1),
+            q0 * q0 - q1 * q1 - q2 * q2 + q3 * q3,
+        );
+        #[rustfmt::skip]
+        let h_q = Matrix3x4::new(
+            -2.0 * q2,  2.0 * q3, -2.0 * q0, 2.0 * q1,
+             2.0 * q1,  2.0 * q0,  2.0 * q3, 2.0 * q2,
+             2.0 * q0, -2.0 * q1, -2.0 * q2, 2.0 * q3,
+        );
+        let mut jacobian = SMatrix::<f64, 3, 7>::zeros();
+        jacobian.fixed_view_mut::<3, 4>(0, 0).copy_from(&h_q);
+
+        let s =
+            jacobian * self.p * jacobian.transpose() + Matrix3::identity() * ACCEL_NOISE.powi(2);
+        let Some(s_inv) = s.try_inverse() else {
+            return;
+        };
+        let k = self.p * jacobian.transpose() * s_inv;
+
+        self.x += k * (acc - h);
+        self.p = (Matrix7::identity() - k * jacobian) * self.p;
+        self.normalize();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: f64 = 0.005;
+
+    #[test]
+    fn stationary_converges_to_gravity() {
+        let mut ekf = Ekf::new(DT);
+        // Starts level instead of from the first reading, to converge to a tilt of 20°.
+        ekf.initialized = true;
+        let tilt = UnitQuaternion::from_euler_angles(20f64.to_radians(), 0.0, 0.0);
+        let acc = tilt.inverse() * Vector3::z();
+        for _ in 0..2000 {
+            ekf.update(Vector3::zeros(), acc);
+        }
+        let up = ekf.rotation() * acc;
+        assert!(up.angle(&Vector3::z()) < 0.5f64.to_radians(), "{up}");
+    }
+
+    #[test]
+    fn constant_rate_integrates() {
+        let mut ekf = Ekf::new(DT);
+        // 0.5 radians/s around the vertical for 2s, where gravity can't correct it.
+        for _ in 0..400 {
+            ekf.update(Vector3::new(0.0, 0.0, 0.5), Vector3::z());
+        }
+        let (_, _, yaw) = ekf.rotation().euler_angles();
+        assert!((yaw - 1.0).abs() < 0.01, "{yaw}");
+    }
+}
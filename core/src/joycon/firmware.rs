@@ -0,0 +1,38 @@
+//! Controller firmware versions, and the ones known to cause trouble for tracking.
+
+use joycon_rs::joycon::device_info::JoyConDeviceInfo;
+use joycon_rs::prelude::input_report_mode::sub_command_mode::SubCommandReplyData;
+use joycon_rs::prelude::*;
+
+// Oldest firmware without known problems. The launch firmware drops reports over Bluetooth,
+// which looks like a laggy IMU. Versions are major in the high byte, minor in the low byte.
+const FIRST_GOOD: u16 = 0x0348;
+
+/// Firmware version as reported by the controller, shown like the Switch does, e.g. "4.33".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FirmwareVersion(pub u16);
+impl FirmwareVersion {
+    /// Why this version is a problem, `None` if it is fine.
+    pub fn problem(self) -> Option<&'static str> {
+        (self.0 < FIRST_GOOD).then_some("This firmware is known to drop IMU reports")
+    }
+}
+impl std::fmt::Display for FirmwareVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let [major, minor] = self.0.to_be_bytes();
+        write!(f, "{major}.{minor:02}")
+    }
+}
+
+/// Asks the controller for its firmware version, before the input report mode is set up.
+pub(crate) fn read_version(driver: &mut SimpleJoyConDriver) -> JoyConResult<FirmwareVersion> {
+    match JoyConDeviceInfo::once(driver)? {
+        SubCommandReply::Checked(report) => {
+            Ok(FirmwareVersion(report.extra.reply.firmware_version))
+        }
+        SubCommandReply::Unchecked => Err(JoyConError::SubCommandError(
+            SubCommand::RequestDeviceInfo as u8,
+            Vec::new(),
+        )),
+    }
+}
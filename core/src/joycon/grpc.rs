@@ -0,0 +1,172 @@
+// tonic::Status is large, but it is what the generated service trait returns.
+#![allow(clippy::result_large_err)]
+
+use std::{net::SocketAddr, pin::Pin, sync::Arc, time::Duration};
+
+use tokio_stream::{wrappers::IntervalStream, Stream, StreamExt};
+use tonic::{transport::Server, Request, Response, Status};
+
+use super::control::Control;
+use crate::settings::Fusion;
+
+mod proto {
+    tonic::include_proto!("wrangler.v1");
+}
+use proto::{
+    wrangler_server::{Wrangler, WranglerServer},
+    Device, DeviceList, Empty, GroupRequest, PauseRequest, Quaternion, Settings, SettingsUpdate,
+};
+
+const SUBSCRIBE_INTERVAL: Duration = Duration::from_millis(100);
+
+struct Service(Arc<Control>);
+
+fn device_list(control: &Control) -> DeviceList {
+    let settings = control.settings().load();
+    let devices = control
+        .statuses()
+        .into_iter()
+        .map(|status| Device {
+            nickname: settings
+                .joycon_nickname_get(&status.serial_number)
+                .to_owned(),
+            group: settings.joycon_group_get(&status.serial_number).to_owned(),
+            serial: status.serial_number,
+            status: status.status.to_string(),
+            battery: format!("{:?}", status.battery),
+            rate: status.rate as u32,
+            drift: status.drift,
+            rotation: Some(Quaternion {
+                w: status.orientation.w,
+                i: status.orientation.i,
+                j: status.orientation.j,
+                k: status.orientation.k,
+            }),
+        })
+        .collect();
+    DeviceList {
+        devices,
+        paused: control.paused(),
+        paused_groups: control.paused_groups(),
+    }
+}
+
+fn settings(control: &Control) -> Settings {
+    let settings = control.settings().load();
+    Settings {
+        address: settings.address.clone(),
+        send_reset: settings.send_reset,
+        sleep_minutes: settings.sleep_minutes,
+        auto_level: settings.auto_level,
+        fusion: match settings.fusion {
+            Fusion::Vqf => "vqf",
+            Fusion::Ekf => "ekf",
+            Fusion::Madgwick => "madgwick",
+            Fusion::Mahony => "mahony",
+        }
+        .into(),
+    }
+}
+
+#[tonic::async_trait]
+impl Wrangler for Service {
+    async fn list_devices(&self, _: Request<Empty>) -> Result<Response<DeviceList>, Status> {
+        Ok(Response::new(device_list(&self.0)))
+    }
+
+    type SubscribeStatusStream = Pin<Box<dyn Stream<Item = Result<DeviceList, Status>> + Send>>;
+    async fn subscribe_status(
+        &self,
+        _: Request<Empty>,
+    ) -> Result<Response<Self::SubscribeStatusStream>, Status> {
+        let control = self.0.clone();
+        let stream = IntervalStream::new(tokio::time::interval(SUBSCRIBE_INTERVAL))
+            .map(move |_| Ok(device_list(&control)));
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn reset(&self, _: Request<Empty>) -> Result<Response<Empty>, Status> {
+        self.0.reset();
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn set_paused(&self, request: Request<PauseRequest>) -> Result<Response<Empty>, Status> {
+        let request = request.into_inner();
+        if request.group.is_empty() {
+            self.0.set_paused(request.paused);
+        } else {
+            self.0.set_group_paused(&request.group, request.paused);
+        }
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn reset_group(&self, request: Request<GroupRequest>) -> Result<Response<Empty>, Status> {
+        self.0.reset_group(&request.into_inner().group);
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn identify_group(
+        &self,
+        request: Request<GroupRequest>,
+    ) -> Result<Response<Empty>, Status> {
+        self.0.identify_group(&request.into_inner().group);
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn rescan(&self, _: Request<Empty>) -> Result<Response<Empty>, Status> {
+        self.0.rescan();
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn get_settings(&self, _: Request<Empty>) -> Result<Response<Settings>, Status> {
+        Ok(Response::new(settings(&self.0)))
+    }
+
+    async fn update_settings(
+        &self,
+        request: Request<SettingsUpdate>,
+    ) -> Result<Response<Settings>, Status> {
+        let update = request.into_inner();
+        let fusion = match update.fusion.as_deref() {
+            None => None,
+            Some("vqf") => Some(Fusion::Vqf),
+            Some("ekf") => Some(Fusion::Ekf),
+            Some("madgwick") => Some(Fusion::Madgwick),
+            Some("mahony") => Some(Fusion::Mahony),
+            Some(other) => {
+                return Err(Status::invalid_argument(format!(
+                "unknown fusion {other:?}, expected \"vqf\", \"ekf\", \"madgwick\" or \"mahony\""
+            )))
+            }
+        };
+        self.0.settings().change(|ws| {
+            if let Some(address) = update.address {
+                ws.address = address;
+            }
+            if let Some(send_reset) = update.send_reset {
+                ws.send_reset = send_reset;
+            }
+            if let Some(sleep_minutes) = update.sleep_minutes {
+                ws.sleep_minutes = sleep_minutes;
+            }
+            if let Some(auto_level) = update.auto_level {
+                ws.auto_level = auto_level;
+            }
+            if let Some(fusion) = fusion {
+                ws.fusion = fusion;
+            }
+        });
+        Ok(Response::new(settings(&self.0)))
+    }
+}
+
+/// Serves the API from `proto/wrangler.proto` until the process exits. Returns early if the
+/// address can't be bound.
+#[tokio::main]
+pub async fn serve(control: Arc<Control>, address: SocketAddr) {
+    Server::builder()
+        .add_service(WranglerServer::new(Service(control)))
+        .serve(address)
+        .await
+        .ok();
+}
@@ -0,0 +1,105 @@
+use nalgebra::{Quaternion, UnitQuaternion, Vector3};
+use vqf_cxx::{VQFBuilder, VQF};
+
+use super::ahrs::{Madgwick, Mahony};
+use super::ekf::Ekf;
+use crate::settings::Fusion;
+
+/// One IMU frame, acceleration in G and rotation in radians/s.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JoyconAxisData {
+    pub accel_x: f64,
+    pub accel_y: f64,
+    pub accel_z: f64,
+    pub gyro_x: f64,
+    pub gyro_y: f64,
+    pub gyro_z: f64,
+}
+
+impl JoyconAxisData {
+    pub fn mean(frames: &[JoyconAxisData; 3]) -> Self {
+        let avg = |axis: fn(&JoyconAxisData) -> f64| frames.iter().map(axis).sum::<f64>() / 3.0;
+        Self {
+            accel_x: avg(|f| f.accel_x),
+            accel_y: avg(|f| f.accel_y),
+            accel_z: avg(|f| f.accel_z),
+            gyro_x: avg(|f| f.gyro_x),
+            gyro_y: avg(|f| f.gyro_y),
+            gyro_z: avg(|f| f.gyro_z),
+        }
+    }
+}
+
+enum Backend {
+    Vqf(VQF),
+    Ekf(Box<Ekf>),
+    Madgwick(Madgwick),
+    Mahony(Mahony),
+}
+
+/// Orientation of one device, updated frame by frame with the selected [`Fusion`].
+pub struct Imu {
+    backend: Backend,
+    pub rotation: UnitQuaternion<f64>,
+}
+impl Imu {
+    pub fn new(fusion: Fusion) -> Self {
+        Self {
+            backend: match fusion {
+                Fusion::Vqf => Backend::Vqf(VQFBuilder::new(0.005f64).build()),
+                Fusion::Ekf => Backend::Ekf(Box::new(Ekf::new(0.005f64))),
+                Fusion::Madgwick => {
+                    Backend::Madgwick(Madgwick::new(0.005f64, Madgwick::DEFAULT_BETA))
+                }
+                Fusion::Mahony => Backend::Mahony(Mahony::new(0.005f64, Mahony::DEFAULT_KP)),
+            },
+            rotation: UnitQuaternion::new_unchecked(Quaternion::new(
+                1.0f64, 0.0f64, 0.0f64, 0.0f64,
+            )),
+        }
+    }
+    pub fn fusion(&self) -> Fusion {
+        match self.backend {
+            Backend::Vqf(_) => Fusion::Vqf,
+            Backend::Ekf(_) => Fusion::Ekf,
+            Backend::Madgwick(_) => Fusion::Madgwick,
+            Backend::Mahony(_) => Fusion::Mahony,
+        }
+    }
+    /// Sets the gain of a fusion that has one, see [`Fusion::default_gain`]. Takes effect from
+    /// the next frame, without starting over.
+    pub fn set_gain(&mut self, gain: f64) {
+        match &mut self.backend {
+            Backend::Vqf(_) | Backend::Ekf(_) => {}
+            Backend::Madgwick(madgwick) => madgwick.beta = gain,
+            Backend::Mahony(mahony) => mahony.kp = gain,
+        }
+    }
+    pub fn update(&mut self, frame: JoyconAxisData) {
+        let gyro = Vector3::new(frame.gyro_x, frame.gyro_y, frame.gyro_z);
+        let acc = Vector3::new(frame.accel_x, frame.accel_y, frame.accel_z);
+        self.rotation = match &mut self.backend {
+            Backend::Vqf(vqf) => {
+                vqf.update_6dof(&gyro.data.0[0], &acc.data.0[0]);
+                UnitQuaternion::new_unchecked(vqf.get_quat_6d().into())
+            }
+            Backend::Ekf(ekf) => {
+                ekf.update(gyro, acc);
+                ekf.rotation()
+            }
+            Backend::Madgwick(madgwick) => {
+                madgwick.update(gyro, acc);
+                madgwick.rotation()
+            }
+            Backend::Mahony(mahony) => {
+                mahony.update(gyro, acc);
+                mahony.rotation()
+            }
+        };
+    }
+    // euler_angles: roll, pitch, yaw
+    pub fn euler_angles_deg(&self) -> (f64, f64, f64) {
+        let ea = self.rotation.euler_angles();
+        (ea.0.to_degrees(), ea.1.to_degrees(), ea.2.to_degrees())
+    }
+}
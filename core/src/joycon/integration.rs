@@ -0,0 +1,982 @@
+use super::calibration::{self, CalibrationState, CalibrationStep, GyroMeasurement, Region};
+use super::combo::HeldButtons;
+use super::communication::{frame_queue, ChannelData, DeviceSender, FrameProducer};
+use super::firmware;
+use super::imu::JoyconAxisData;
+use super::profile::{self, Stage};
+use super::queue::{bounded, Overflow, Queue, COMMAND_QUEUE_SIZE};
+use super::{Battery, ChannelInfo, DeviceCommand, ErrorReport, JoyconDesign, JoyconDesignType};
+use crate::settings::{self, HomeLight, LightPattern, LightState, PlayerLights, WranglerSettings};
+use joycon_rs::joycon::device::calibration::imu::IMUCalibration;
+use joycon_rs::joycon::input_report_mode::standard_full_mode::AxisData;
+use joycon_rs::joycon::joycon_features::imu_sensitivity::{
+    AccelerometerAntiAliasingFilterBandwidth, GyroscopePerformanceRate,
+};
+use joycon_rs::joycon::joycon_features::IMUConfig;
+use joycon_rs::joycon::lights::home_button::{LightEmittingPattern, LightEmittingPhase};
+use joycon_rs::joycon::lights::{Flash, LightUp, Lights};
+use joycon_rs::prelude::input_report_mode::{BatteryLevel, PushedButtons};
+use joycon_rs::prelude::*;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use thread_priority::{set_current_thread_priority, ThreadPriority, ThreadPriorityValue};
+use tracing::{info, warn};
+
+// Gyro: 2000dps
+// Accel: 8G
+// https://github.com/dekuNukem/Nintendo_Switch_Reverse_Engineering/blob/master/imu_sensor_notes.md
+
+// Raw to acceleration in G, 16000/65535/1000
+const ACC_SCALE: f64 = 0.00024414435f64;
+
+// Raw to degrees/s, 4588/65535
+// NOTE: 13371 is technically a value present in flash, in practice it seems to be constant.
+// (936.0 / (13371 - offset)) would be the exact factor.
+const GYRO_SCALE: f64 = 0.07000839246f64;
+
+// Low battery warning: three short buzzes, with pauses as long as the buzzes in between.
+const BUZZ_STEPS: usize = 5;
+const BUZZ_STEP: Duration = Duration::from_millis(150);
+const BUZZ_FREQUENCY: f32 = 160.0;
+const BUZZ_AMPLITUDE: f32 = 0.8;
+
+// Another program like Steam may switch the report mode, it is set back after this long without
+// full reports. In shared access that mode is kept, only a controller that went silent altogether
+// is set back.
+const FULL_REPORT_TIMEOUT: Duration = Duration::from_secs(1);
+// Input report with IMU data, and the argument of SetInputReportMode that asks for it.
+const FULL_REPORT: u8 = 0x30;
+
+// The report timer counts 5ms ticks, so it moves by about three between full reports.
+const TIMER_TICKS_PER_REPORT: usize = 3;
+const LOST_INTERVAL: Duration = Duration::from_secs(1);
+
+// How long the identify lights stay on.
+const IDENTIFY_TIME: Duration = Duration::from_secs(5);
+
+// Argument of the SetHCIState subcommand that disconnects and turns the controller off.
+const HCI_DISCONNECT: u8 = 0x00;
+
+// The temperature of the IMU is in its OUT_TEMP registers, read with ReadIMURegisters this often.
+// The reply takes the place of a full report, it warms up slowly enough for a report lost now and
+// then. 16 per °C from 25°C, as in the LSM6DS3 datasheet.
+const TEMPERATURE_REGISTER: u8 = 0x20;
+const TEMPERATURE_INTERVAL: Duration = Duration::from_secs(30);
+const SUBCOMMAND_REPLY: u8 = 0x21;
+const TEMPERATURE_SCALE: f64 = 1.0 / 16.0;
+const TEMPERATURE_OFFSET: f64 = 25.0;
+
+/// The bytes after the common part of a report, which the driver always reads as IMU data. Each
+/// value was read as little endian, so this gives back the bytes as they came.
+fn report_bytes(data: &[AxisData; 3]) -> [u8; 36] {
+    let mut bytes = [0; 36];
+    for (chunk, frame) in bytes.chunks_exact_mut(12).zip(data) {
+        let values = [
+            frame.accel_x,
+            frame.accel_y,
+            frame.accel_z,
+            frame.gyro_1,
+            frame.gyro_2,
+            frame.gyro_3,
+        ];
+        for (pair, value) in chunk.chunks_exact_mut(2).zip(values) {
+            pair.copy_from_slice(&value.to_le_bytes());
+        }
+    }
+    bytes
+}
+
+/// The temperature in °C of a reply to reading [`TEMPERATURE_REGISTER`], if `reply` is one. The
+/// reply has the ack, the subcommand, the address and count read, then the two registers.
+fn temperature_reply(reply: &[u8]) -> Option<f64> {
+    let &[ack, subcommand, address, count, low, high, ..] = reply else {
+        return None;
+    };
+    let matches = ack & 0x80 != 0
+        && subcommand == SubCommand::ReadIMURegisters as u8
+        && address == TEMPERATURE_REGISTER
+        && count == 2;
+    matches.then(|| {
+        f64::from(i16::from_le_bytes([low, high])) * TEMPERATURE_SCALE + TEMPERATURE_OFFSET
+    })
+}
+
+/// Per-device factors for turning raw Joy-Con samples into G and radians/s.
+///
+/// Calibration offsets, the user's gyro scale and the mirrored axes of the right Joy-Con are
+/// folded into per-axis arrays once, instead of being looked up for every sample. They are laid
+/// out for the 18 values of a whole report, so [`RawConversion::convert`] is one loop over flat
+/// arrays that the compiler turns into SIMD instructions. "imu conversion" in the hot path
+/// benchmarks compares it against converting frame by frame. The hid-nintendo driver on Linux
+/// reports scaled values and doesn't use it.
+#[derive(Debug, Clone, Copy)]
+pub struct RawConversion {
+    offset: [i32; REPORT_VALUES],
+    scale: [f64; REPORT_VALUES],
+    gyro_scale_factor: f64,
+}
+
+// Acceleration X, Y and Z followed by the three gyro axes, for each of the three frames.
+const FRAME_VALUES: usize = 6;
+const REPORT_VALUES: usize = FRAME_VALUES * 3;
+
+impl RawConversion {
+    /// `calib` is the accelerometer and gyro offsets, `mirrored` is set for a right Joy-Con.
+    pub fn new(calib: &([i16; 3], [i16; 3]), mirrored: bool, gyro_scale_factor: f64) -> Self {
+        let sign = if mirrored { -1.0 } else { 1.0 };
+        let gyro = GYRO_SCALE.to_radians() * gyro_scale_factor;
+        let (ao, go) = calib;
+        let offset = [ao[0], ao[1], ao[2], go[0], go[1], go[2]].map(i32::from);
+        let scale = [
+            ACC_SCALE,
+            ACC_SCALE * sign,
+            ACC_SCALE * sign,
+            gyro,
+            gyro * sign,
+            gyro * sign,
+        ];
+        Self {
+            offset: std::array::from_fn(|i| offset[i % FRAME_VALUES]),
+            scale: std::array::from_fn(|i| scale[i % FRAME_VALUES]),
+            gyro_scale_factor,
+        }
+    }
+
+    /// Converts the three frames of a report, each as acceleration X, Y and Z followed by the
+    /// three gyro axes, all at once.
+    pub fn convert(&self, frames: &[[i16; FRAME_VALUES]; 3]) -> [JoyconAxisData; 3] {
+        let mut raw = [0; REPORT_VALUES];
+        for (chunk, frame) in raw.chunks_exact_mut(FRAME_VALUES).zip(frames) {
+            chunk.copy_from_slice(frame);
+        }
+        let mut out = [0.0; REPORT_VALUES];
+        for i in 0..REPORT_VALUES {
+            // Same saturation as subtracting in i16.
+            let n = (i32::from(raw[i]) - self.offset[i]).clamp(-32768, 32767);
+            out[i] = f64::from(n) * self.scale[i];
+        }
+        std::array::from_fn(|frame| axis_data(&out[frame * FRAME_VALUES..]))
+    }
+
+    /// Like [`RawConversion::convert`], one value at a time, which is what the benchmarks
+    /// measure it against.
+    pub fn convert_per_frame(&self, frames: &[[i16; FRAME_VALUES]; 3]) -> [JoyconAxisData; 3] {
+        frames.map(|raw| {
+            let out: [f64; FRAME_VALUES] = std::array::from_fn(|i| {
+                let n = (i32::from(raw[i]) - self.offset[i]).clamp(-32768, 32767);
+                f64::from(n) * self.scale[i]
+            });
+            axis_data(&out)
+        })
+    }
+}
+
+fn axis_data(values: &[f64]) -> JoyconAxisData {
+    JoyconAxisData {
+        accel_x: values[0],
+        accel_y: values[1],
+        accel_z: values[2],
+        gyro_x: values[3],
+        gyro_y: values[4],
+        gyro_z: values[5],
+    }
+}
+
+/// Counts the reports that got lost on the way, from gaps in the report timer.
+struct LossCounter {
+    last_timer: Option<u8>,
+    lost: usize,
+    since: Instant,
+}
+impl LossCounter {
+    fn new() -> Self {
+        Self {
+            last_timer: None,
+            lost: 0,
+            since: Instant::now(),
+        }
+    }
+    /// Returns how many reports got lost right before this one.
+    fn report(&mut self, timer: u8) -> usize {
+        let lost = self.last_timer.map_or(0, |last| {
+            let ticks = usize::from(timer.wrapping_sub(last));
+            // Rounded, the timer jitters by a tick.
+            let reports = (ticks + TIMER_TICKS_PER_REPORT / 2) / TIMER_TICKS_PER_REPORT;
+            reports.saturating_sub(1)
+        });
+        self.lost += lost;
+        self.last_timer = Some(timer);
+        lost
+    }
+    /// Reports in another mode don't count, the timer may move differently there.
+    fn interrupt(&mut self) {
+        self.last_timer = None;
+    }
+    /// The count since the last call, once [`LOST_INTERVAL`] passed.
+    fn take(&mut self) -> Option<usize> {
+        if self.since.elapsed() < LOST_INTERVAL {
+            return None;
+        }
+        self.since = Instant::now();
+        Some(std::mem::take(&mut self.lost))
+    }
+}
+
+fn offsets(calib: &IMUCalibration) -> ([i16; 3], [i16; 3]) {
+    match calib {
+        IMUCalibration::Available {
+            acc_origin_position: ao,
+            gyro_origin_position: go,
+            ..
+        } => ([ao.x, ao.y, ao.z], [go.x, go.y, go.z]),
+        IMUCalibration::Unavailable => ([0, 0, 0], [0, 0, 0]),
+    }
+}
+
+// Saves the calibration the controller had first, nothing is written without a backup.
+fn write_calibration(
+    driver: &mut SimpleJoyConDriver,
+    serial_number: &str,
+    region: &Region,
+) -> Result<(), ErrorReport> {
+    let retry = "Move the controller closer to the Bluetooth adapter and try again.";
+    let original = calibration::read_region(driver).map_err(|_| {
+        ErrorReport::new(
+            serial_number,
+            "Could not read the calibration of the controller",
+        )
+        .suggest(retry)
+    })?;
+    calibration::save_backup(serial_number, &original).map_err(|e| {
+        ErrorReport::new(
+            serial_number,
+            format!("Could not back up the calibration, nothing was written: {e}"),
+        )
+        .suggest("Check that the wrangler config folder is writable.")
+    })?;
+    calibration::write_region(driver, region).map_err(|_| {
+        ErrorReport::new(
+            serial_number,
+            "Could not write the calibration to the controller",
+        )
+        .suggest(retry)
+    })
+}
+
+fn restore_calibration(
+    driver: &mut SimpleJoyConDriver,
+    serial_number: &str,
+) -> Result<Region, ErrorReport> {
+    let original = calibration::load_backup(serial_number).map_err(|e| {
+        ErrorReport::new(
+            serial_number,
+            format!("Could not load the calibration backup: {e}"),
+        )
+    })?;
+    calibration::write_region(driver, &original).map_err(|_| {
+        ErrorReport::new(
+            serial_number,
+            "Could not restore the calibration of the controller",
+        )
+        .suggest("Move the controller closer to the Bluetooth adapter and try again.")
+    })?;
+    Ok(original)
+}
+
+fn convert_battery(battery: BatteryLevel) -> Battery {
+    match battery {
+        BatteryLevel::Empty => Battery::Empty,
+        BatteryLevel::Critical => Battery::Critical,
+        BatteryLevel::Low => Battery::Low,
+        BatteryLevel::Medium => Battery::Medium,
+        BatteryLevel::Full => Battery::Full,
+    }
+}
+
+fn convert_design(device_type: &JoyConDeviceType) -> JoyconDesignType {
+    match device_type {
+        JoyConDeviceType::JoyConL => JoyconDesignType::Left,
+        JoyConDeviceType::JoyConR => JoyconDesignType::Right,
+        JoyConDeviceType::ProCon => JoyconDesignType::Pro,
+    }
+}
+
+fn report(tx: &DeviceSender, serial_number: &str, report: ErrorReport) {
+    tx.send(ChannelData::new(
+        serial_number.to_owned(),
+        ChannelInfo::Error(report),
+    ))
+    .ok();
+}
+
+// Intensity 0-15, fade and duration in multiples of the 175ms cycle used below.
+fn home_phase(intensity: u8, fade: u8, duration: u8) -> LightEmittingPhase {
+    LightEmittingPhase {
+        led_intensity: intensity.into(),
+        fading_transition_duration: fade.into(),
+        led_duration: duration.into(),
+    }
+}
+
+fn home_pattern(home: HomeLight) -> LightEmittingPattern {
+    let phases = match home {
+        HomeLight::Off => return LightEmittingPattern::new(0, 0, 0.into()),
+        HomeLight::On => return LightEmittingPattern::once(175, 100, 100, 0, 0),
+        // A third of a second on and off, or about a second of fading each way.
+        HomeLight::Blink => [home_phase(0xF, 0, 2), home_phase(0, 0, 2)],
+        HomeLight::Pulse => [home_phase(0xF, 6, 0), home_phase(0, 6, 0)],
+    };
+    // Repeated forever.
+    let mut pattern = LightEmittingPattern::new(175, 0, 0.into());
+    for phase in phases {
+        pattern.push_phase(phase);
+    }
+    pattern
+}
+
+// Trackers with an accent color show its number (1-8) in binary on the player LEDs.
+// Returns whether the controller accepted the new pattern.
+fn set_lights(
+    driver: &mut SimpleJoyConDriver,
+    asleep: bool,
+    accent: Option<u8>,
+    pattern: LightPattern,
+) -> bool {
+    if asleep {
+        return driver.set_player_lights(&[], &[Flash::LED0]).is_ok();
+    }
+    let accent = PlayerLights::accent_mask(accent);
+    let (mask, blink) = match pattern.player {
+        PlayerLights::Accent => (accent, false),
+        PlayerLights::AccentBlink => (accent, true),
+        PlayerLights::All => (0b1111, false),
+        PlayerLights::AllBlink => (0b1111, true),
+        PlayerLights::Off => (0, false),
+    };
+    let leds = [
+        (LightUp::LED0, Flash::LED0),
+        (LightUp::LED1, Flash::LED1),
+        (LightUp::LED2, Flash::LED2),
+        (LightUp::LED3, Flash::LED3),
+    ];
+    let (mut on, mut flash) = (vec![], vec![]);
+    for (bit, (light, flashing)) in leds.into_iter().enumerate() {
+        if mask & (1 << bit) == 0 {
+            continue;
+        }
+        if blink {
+            flash.push(flashing);
+        } else {
+            on.push(light);
+        }
+    }
+    if driver.joycon().device_type() != JoyConDeviceType::JoyConL {
+        // Cosmetic, and the result can't be told apart from a controller without the light.
+        driver.set_home_light(&home_pattern(pattern.home)).ok();
+    }
+    driver.set_player_lights(&on, &flash).is_ok()
+}
+
+fn set_rumble(driver: &mut SimpleJoyConDriver, on: bool) {
+    let rumble = if on {
+        Rumble::new(BUZZ_FREQUENCY, BUZZ_AMPLITUDE)
+    } else {
+        Rumble::stop()
+    };
+    driver.rumble((Some(rumble), Some(rumble))).ok();
+    if !on {
+        // Otherwise every later command carries the stop pattern along.
+        driver.set_rumble_status((None, None));
+    }
+}
+
+fn held_buttons(pushed: &PushedButtons) -> HeldButtons {
+    let any = |buttons: &[Buttons]| buttons.iter().any(|button| pushed.contains(*button));
+    HeldButtons {
+        menu: any(&[Buttons::Capture, Buttons::Home]),
+        stick: any(&[Buttons::LStick, Buttons::RStick]),
+        minus_plus: any(&[Buttons::Minus, Buttons::Plus]),
+        shoulder: any(&[Buttons::L, Buttons::R]),
+        trigger: any(&[Buttons::ZL, Buttons::ZR]),
+        side: pushed.contains(Buttons::SL) && pushed.contains(Buttons::SR),
+    }
+}
+
+fn imu_config(settings: &WranglerSettings) -> IMUConfig {
+    IMUConfig {
+        gyroscope_performance_rate: if settings.imu_gyro_high_performance {
+            GyroscopePerformanceRate::F833Hz
+        } else {
+            GyroscopePerformanceRate::F208Hz
+        },
+        accelerometer_anti_aliasing_filter_bandwidth: if settings.imu_accel_wide_filter {
+            AccelerometerAntiAliasingFilterBandwidth::F200Hz
+        } else {
+            AccelerometerAntiAliasingFilterBandwidth::F100Hz
+        },
+        // Sensitivities stay at the defaults, the conversions above assume 2000dps and 8G.
+        ..IMUConfig::default()
+    }
+}
+
+fn joycon_listen_loop(
+    mut standard: StandardFullMode<SimpleJoyConDriver>,
+    tx: &DeviceSender,
+    control: mpsc::Receiver<DeviceCommand>,
+    mut frames: FrameProducer,
+    mut calib: IMUCalibration,
+    factory: IMUCalibration,
+    settings: &settings::Handler,
+) {
+    let serial_number = standard.driver().joycon().serial_number().to_owned();
+    profile::name_thread(format!("Joy-Con {serial_number}"));
+    let device_type = standard.driver().joycon().device_type();
+    let mut calib_offsets = offsets(&calib);
+    let mut calibration = CalibrationState {
+        step: CalibrationStep::Idle,
+        backup: calibration::has_backup(&serial_number),
+    };
+    let mut measured: Option<Region> = None;
+    // Measured in an earlier session and not written yet.
+    if let Some(origin) = settings.load().joycon_gyro_origin_get(&serial_number) {
+        info!(%serial_number, ?origin, "Loaded the remembered gyro offsets");
+        measured = Some(calibration::with_gyro_origin(&calib, origin));
+        calib_offsets.1 = origin;
+        calibration.step = CalibrationStep::Measured;
+    }
+    let send_calibration = |calibration| {
+        tx.send(ChannelData::new(
+            serial_number.clone(),
+            ChannelInfo::Calibration(calibration),
+        ))
+        .ok();
+    };
+    send_calibration(calibration);
+    let mut measurement: Option<GyroMeasurement> = None;
+    let mirrored = match device_type {
+        JoyConDeviceType::JoyConR => true,
+        JoyConDeviceType::JoyConL | JoyConDeviceType::ProCon => false,
+    };
+    let mut conversion = RawConversion::new(&calib_offsets, mirrored, 1.0);
+    let mut last_battery = None;
+    let mut charging = false;
+    let mut applied_imu_config = None;
+    let mut imu_config_failed = false;
+    let mut asleep = false;
+    let mut applied_lights = None;
+    let mut light_state = LightState::Searching;
+    let mut identify_until: Option<Instant> = None;
+    // Step of the buzz pattern and when it started.
+    let mut buzz: Option<(usize, Instant)> = None;
+    let mut last_full_report = Instant::now();
+    let mut last_report = Instant::now();
+    let mut last_temperature: Option<Instant> = None;
+    let mut held = HeldButtons::default();
+    let mut loss = LossCounter::new();
+    loop {
+        // Released like a disconnect, the outer loop leaves it alone until it is unblocked.
+        if settings.load().joycon_blocked_get(&serial_number) {
+            tx.send(ChannelData::new(serial_number, ChannelInfo::Disconnected))
+                .unwrap();
+            return;
+        }
+        // In shared access, whatever the other program set is kept and nothing is written.
+        let shared = settings.load().shared_access;
+        for command in control.try_iter() {
+            match command {
+                DeviceCommand::Sleep(sleep) => asleep = sleep,
+                // Reads fail once it is gone, which ends the loop like any disconnect.
+                DeviceCommand::PowerOff if !charging && !shared => {
+                    standard
+                        .driver_mut()
+                        .send_command_raw(
+                            Command::RumbleAndSubCommand as u8,
+                            SubCommand::SetHCIState as u8,
+                            &[HCI_DISCONNECT],
+                        )
+                        .ok();
+                }
+                DeviceCommand::PowerOff => {}
+                DeviceCommand::Lights(state) => light_state = state,
+                DeviceCommand::Identify => identify_until = Some(Instant::now() + IDENTIFY_TIME),
+                DeviceCommand::Rumble if !shared => {
+                    set_rumble(standard.driver_mut(), true);
+                    buzz = Some((0, Instant::now()));
+                }
+                DeviceCommand::Rumble => {}
+                DeviceCommand::Calibrate => {
+                    measurement = Some(GyroMeasurement::default());
+                    calibration.step = CalibrationStep::Measuring;
+                }
+                DeviceCommand::WriteCalibration | DeviceCommand::RestoreCalibration if shared => {
+                    report(
+                        tx,
+                        &serial_number,
+                        ErrorReport::new(&serial_number, "Not writing to a shared controller")
+                            .suggest("Turn off sharing controllers with Steam and games in the settings, and reconnect the controller."),
+                    );
+                }
+                DeviceCommand::WriteCalibration => {
+                    let Some(region) = measured else {
+                        continue;
+                    };
+                    match write_calibration(standard.driver_mut(), &serial_number, &region) {
+                        Ok(()) => {
+                            info!(%serial_number, "Wrote the measured calibration");
+                            calib = calibration::parse(&region).unwrap_or(calib);
+                            measured = None;
+                            settings.change(|ws| {
+                                ws.joycon_gyro_origin_set(serial_number.clone(), None)
+                            });
+                            calibration = CalibrationState {
+                                step: CalibrationStep::Written,
+                                backup: true,
+                            };
+                        }
+                        Err(e) => report(tx, &serial_number, e),
+                    }
+                }
+                DeviceCommand::RestoreCalibration => {
+                    match restore_calibration(standard.driver_mut(), &serial_number) {
+                        Ok(original) => {
+                            info!(%serial_number, "Restored the calibration backup");
+                            calib = calibration::parse(&original).unwrap_or(factory.clone());
+                            calib_offsets = offsets(&calib);
+                            conversion = RawConversion::new(
+                                &calib_offsets,
+                                mirrored,
+                                conversion.gyro_scale_factor,
+                            );
+                            measured = None;
+                            settings.change(|ws| {
+                                ws.joycon_gyro_origin_set(serial_number.clone(), None)
+                            });
+                            calibration.step = CalibrationStep::Idle;
+                        }
+                        Err(e) => report(tx, &serial_number, e),
+                    }
+                }
+            }
+            send_calibration(calibration);
+        }
+        if let Some((step, since)) = buzz {
+            if since.elapsed() >= BUZZ_STEP {
+                let next = step + 1;
+                set_rumble(standard.driver_mut(), next < BUZZ_STEPS && next % 2 == 0);
+                buzz = (next < BUZZ_STEPS).then(|| (next, Instant::now()));
+            }
+        }
+        let identifying = identify_until.map_or(false, |until| Instant::now() < until);
+        let state = if identifying {
+            LightState::Identify
+        } else {
+            light_state
+        };
+        let lights = (
+            asleep && !identifying,
+            settings.load().joycon_accent_get(&serial_number),
+            settings.load().lights.get(state),
+        );
+        if shared {
+            // Applied again once shared access is turned off.
+            applied_lights = None;
+            applied_imu_config = None;
+        }
+        if !shared && applied_lights != Some(lights) {
+            if !set_lights(standard.driver_mut(), lights.0, lights.1, lights.2) {
+                report(
+                    tx,
+                    &serial_number,
+                    ErrorReport::new(&serial_number, "Could not set the player lights")
+                        .suggest("The lights are cosmetic only, tracking is unaffected."),
+                );
+            }
+            applied_lights = Some(lights);
+        }
+        let config = imu_config(&settings.load());
+        if !shared && applied_imu_config != Some(config) {
+            let data: [u8; 4] = config.into();
+            if standard
+                .driver_mut()
+                .send_sub_command(SubCommand::SetIMUSensitivity, &data)
+                .is_ok()
+            {
+                applied_imu_config = Some(config);
+                imu_config_failed = false;
+            } else if !imu_config_failed {
+                imu_config_failed = true;
+                report(
+                    tx,
+                    &serial_number,
+                    ErrorReport::new(&serial_number, "Could not apply the IMU settings, retrying")
+                        .suggest("Move the controller closer to the Bluetooth adapter."),
+                );
+            }
+        }
+        let report = {
+            let _measure = profile::measure(Stage::ReadHid);
+            standard.read_input_report()
+        };
+        match report {
+            Ok(report) => {
+                last_report = Instant::now();
+                if report.common.input_report_id == FULL_REPORT {
+                    last_full_report = Instant::now();
+                    let lost = loss.report(report.common.timer);
+                    if let Some(lost) = loss.take() {
+                        tx.send(ChannelData::new(
+                            serial_number.clone(),
+                            ChannelInfo::Lost(lost),
+                        ))
+                        .unwrap();
+                    }
+                    charging = report.common.battery.is_charging;
+                    if Some(report.common.battery.level) != last_battery {
+                        last_battery = Some(report.common.battery.level);
+                        tx.send(ChannelData::new(
+                            serial_number.clone(),
+                            ChannelInfo::Battery(convert_battery(report.common.battery.level)),
+                        ))
+                        .unwrap();
+                    }
+                    let buttons = held_buttons(&report.common.pushed_buttons);
+                    if buttons != held {
+                        held = buttons;
+                        tx.send(ChannelData::new(
+                            serial_number.clone(),
+                            ChannelInfo::Buttons(held),
+                        ))
+                        .unwrap();
+                    }
+                    if report.common.pushed_buttons.contains(Buttons::Up)
+                        || report.common.pushed_buttons.contains(Buttons::B)
+                    {
+                        tx.send(ChannelData::new(serial_number.clone(), ChannelInfo::Reset))
+                            .unwrap();
+                    }
+                    if let Some(measuring) = &mut measurement {
+                        measuring.add(&report.extra.data);
+                        if measuring.done() {
+                            match measuring.origin() {
+                                Some(origin) => {
+                                    info!(%serial_number, ?origin, "Measured the gyro offsets");
+                                    measured = Some(calibration::with_gyro_origin(&calib, origin));
+                                    calib_offsets.1 = origin;
+                                    settings.change(|ws| {
+                                        ws.joycon_gyro_origin_set(
+                                            serial_number.clone(),
+                                            Some(origin),
+                                        )
+                                    });
+                                    conversion = RawConversion::new(
+                                        &calib_offsets,
+                                        mirrored,
+                                        conversion.gyro_scale_factor,
+                                    );
+                                    calibration.step = CalibrationStep::Measured;
+                                }
+                                None => {
+                                    // `report` is the input report in here.
+                                    self::report(
+                                        tx,
+                                        &serial_number,
+                                        ErrorReport::new(
+                                            &serial_number,
+                                            "The controller moved during calibration",
+                                        )
+                                        .suggest("Lay it down on a table and try again."),
+                                    );
+                                    calibration.step = CalibrationStep::Idle;
+                                }
+                            }
+                            measurement = None;
+                            send_calibration(calibration);
+                        }
+                    }
+                    let gyro_scale_factor = settings.load().joycon_scale_get(&serial_number);
+                    if gyro_scale_factor != conversion.gyro_scale_factor {
+                        conversion =
+                            RawConversion::new(&calib_offsets, mirrored, gyro_scale_factor);
+                    }
+                    let imu_data = {
+                        let _measure = profile::measure(Stage::ReadHid);
+                        conversion.convert(&report.extra.data.map(|f| {
+                            [
+                                f.accel_x, f.accel_y, f.accel_z, f.gyro_1, f.gyro_2, f.gyro_3,
+                            ]
+                        }))
+                    };
+                    // Each lost report held three frames.
+                    frames.push_after_gap(imu_data, lost * 3);
+                } else if report.common.input_report_id == SUBCOMMAND_REPLY {
+                    // A reply takes the place of a full report, the counter keeps running so the
+                    // next one counts it as lost and the fusion steps over its frames.
+                    if let Some(temperature) = temperature_reply(&report_bytes(&report.extra.data))
+                    {
+                        tx.send(ChannelData::new(
+                            serial_number.clone(),
+                            ChannelInfo::Temperature(temperature),
+                        ))
+                        .unwrap();
+                    }
+                } else {
+                    loss.interrupt();
+                }
+            }
+            Err(JoyConError::Disconnected) => {
+                tx.send(ChannelData::new(serial_number, ChannelInfo::Disconnected))
+                    .unwrap();
+                return;
+            }
+            _ => {}
+        }
+        let silent = if shared {
+            last_report.elapsed() >= FULL_REPORT_TIMEOUT
+        } else {
+            last_full_report.elapsed() >= FULL_REPORT_TIMEOUT
+        };
+        if silent {
+            last_full_report = Instant::now();
+            last_report = Instant::now();
+            let driver = standard.driver_mut();
+            driver
+                .send_sub_command(SubCommand::SetInputReportMode, &[FULL_REPORT])
+                .ok();
+            driver.send_sub_command(SubCommand::EnableIMU, &[0x01]).ok();
+        }
+        // Only the gyro bias refinement uses the temperature. Sent without waiting for the reply,
+        // the reads above pick it up.
+        let wants_temperature = {
+            let settings = settings.load();
+            settings.gyro_bias_refinement && !settings.shared_access
+        };
+        if wants_temperature
+            && last_temperature.map_or(true, |last| last.elapsed() >= TEMPERATURE_INTERVAL)
+        {
+            last_temperature = Some(Instant::now());
+            standard
+                .driver_mut()
+                .send_command_raw(
+                    Command::RumbleAndSubCommand as u8,
+                    SubCommand::ReadIMURegisters as u8,
+                    &[TEMPERATURE_REGISTER, 2],
+                )
+                .ok();
+        }
+    }
+}
+
+// Above normal, not the realtime or time critical priority of `ThreadPriority::Max`, which could
+// starve the rest of the system with many controllers.
+const READER_PRIORITY: u8 = 70;
+
+/// Keeps report latency down when the game pegs the CPU, for the reader threads of both drivers.
+pub(crate) fn prioritize_reader(settings: &WranglerSettings) {
+    if settings.reader_priority {
+        if let Ok(priority) = ThreadPriorityValue::try_from(READER_PRIORITY) {
+            set_current_thread_priority(ThreadPriority::Crossplatform(priority)).ok();
+        }
+    }
+    if let Some(core) = settings.reader_core {
+        match core_affinity::get_core_ids().and_then(|ids| ids.get(core).copied()) {
+            Some(id) => {
+                core_affinity::set_for_current(id);
+            }
+            None => warn!(core, "No such CPU core to pin the controller readers to"),
+        }
+    }
+}
+
+fn joycon_thread(d: Arc<Mutex<JoyConDevice>>, tx: DeviceSender, settings: settings::Handler) {
+    prioritize_reader(&settings.load());
+    let mut open_failed = false;
+    loop {
+        let (connected, blocked) = {
+            let d = match d.lock() {
+                Ok(d) => d,
+                Err(d) => d.into_inner(),
+            };
+            let blocked = settings.load().joycon_blocked_get(d.serial_number());
+            (d.is_connected(), blocked)
+        };
+        if connected && !blocked {
+            let driver = SimpleJoyConDriver::new(&d);
+            if driver.is_err() && !open_failed {
+                report(
+                    &tx,
+                    "Joy-Con",
+                    ErrorReport::new("Joy-Con", "Could not open a connected controller, retrying")
+                        .suggest("Re-pair the controller, or close other programs using it."),
+                );
+            }
+            open_failed = driver.is_err();
+            if let Ok(mut driver) = driver {
+                let joycon = driver.joycon();
+                let color = joycon.color().clone();
+                let design = JoyconDesign {
+                    color: format!(
+                        "#{:02x}{:02x}{:02x}",
+                        color.body[0], color.body[1], color.body[2]
+                    ),
+                    design_type: convert_design(&joycon.device_type()),
+                };
+
+                let factory = joycon.imu_factory_calibration().clone();
+                let mut calib = joycon.imu_user_calibration().clone();
+                if calib == IMUCalibration::Unavailable {
+                    calib = factory.clone();
+                }
+
+                let serial_number = joycon.serial_number().to_owned();
+                let (control_tx, control_rx) =
+                    bounded(COMMAND_QUEUE_SIZE, Overflow::Drop(Queue::Commands));
+                let (frames_tx, frames_rx) = frame_queue();
+                tx.send(ChannelData::new(
+                    serial_number.clone(),
+                    ChannelInfo::Connected(design),
+                ))
+                .unwrap();
+                tx.send(ChannelData::new(
+                    serial_number.clone(),
+                    ChannelInfo::Control(control_tx),
+                ))
+                .unwrap();
+                tx.send(ChannelData::new(
+                    serial_number.clone(),
+                    ChannelInfo::Stream(frames_rx),
+                ))
+                .unwrap();
+
+                drop(joycon);
+
+                if !settings.load().shared_access {
+                    let accent = settings.load().joycon_accent_get(&serial_number);
+                    let searching = settings.load().lights.get(LightState::Searching);
+                    set_lights(&mut driver, false, accent, searching);
+                }
+
+                // Not every controller answers, tracking works without it.
+                if let Ok(version) = firmware::read_version(&mut driver) {
+                    tx.send(ChannelData::new(
+                        serial_number.clone(),
+                        ChannelInfo::Firmware(version),
+                    ))
+                    .unwrap();
+                    if let Some(problem) = version.problem() {
+                        let problem = format!("Firmware {version}: {problem}");
+                        let fix = "Update it on a Switch, under Controllers and Sensors in the System Settings.";
+                        report(
+                            &tx,
+                            &serial_number,
+                            ErrorReport::new(&serial_number, problem).suggest(fix),
+                        );
+                    }
+                }
+
+                match StandardFullMode::new(driver) {
+                    Ok(standard) => joycon_listen_loop(
+                        standard, &tx, control_rx, frames_tx, calib, factory, &settings,
+                    ),
+                    Err(_) => report(
+                        &tx,
+                        &serial_number,
+                        ErrorReport::new(&serial_number, "Could not start IMU streaming, retrying")
+                            .suggest("Reconnect the controller if this keeps happening."),
+                    ),
+                }
+            }
+        }
+        // Joycon was disconnected, check for reconnection after 1 second
+        thread::sleep(Duration::from_millis(1000));
+    }
+}
+
+/// Looks for new Joy-Cons right away instead of waiting for the next background scan.
+pub fn rescan(tx: &DeviceSender, settings: &settings::Handler) {
+    let devices = match JoyConManager::get_instance().lock() {
+        Ok(mut manager) => manager.scan().unwrap_or_default(),
+        Err(_) => vec![],
+    };
+    for d in devices {
+        let tx = tx.clone();
+        let settings = settings.clone();
+        thread::spawn(move || joycon_thread(d, tx, settings));
+    }
+}
+
+pub fn spawn_thread(tx: DeviceSender, settings: settings::Handler) {
+    let manager = JoyConManager::get_instance();
+    let devices = {
+        let lock = manager.lock();
+        match lock {
+            Ok(manager) => manager.new_devices(),
+            Err(_) => {
+                report(
+                    &tx,
+                    "Joy-Con",
+                    ErrorReport::new("Joy-Con", "Could not search for Joy-Cons")
+                        .suggest("Restart wrangler."),
+                );
+                return;
+            }
+        }
+    };
+    for d in devices.iter() {
+        let tx = tx.clone();
+        let settings = settings.clone();
+        thread::spawn(move || joycon_thread(d, tx, settings));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batched_conversion_matches_per_frame() {
+        let conversion = RawConversion::new(&([350, -20, 11], [-6, 14, 3]), true, 1.02);
+        let frames = [
+            [312, -45, 4090, 12, -8, 3],
+            [i16::MIN, i16::MAX, 0, -32000, 32000, 1],
+            [315, -47, 4088, 14, -6, 2],
+        ];
+        assert_eq!(
+            conversion.convert(&frames),
+            conversion.convert_per_frame(&frames)
+        );
+    }
+
+    // A reply to reading the temperature, from the ack on, at 20.0°C. It goes through the
+    // driver's parsing like a real one, which reads it as the IMU data of a full report.
+    const TEMPERATURE_REPLY: [u8; 36] = [
+        0xC0, 0x43, 0x20, 0x02, 0xB0, 0xFF, 0, 0, 0, 0, 0, 0, //
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    ];
+
+    fn as_imu_data(bytes: &[u8; 36]) -> [AxisData; 3] {
+        std::array::from_fn(|frame| {
+            let mut chunk = [0; 12];
+            chunk.copy_from_slice(&bytes[frame * 12..][..12]);
+            AxisData::from(chunk)
+        })
+    }
+
+    #[test]
+    fn temperature_parsed() {
+        let data = as_imu_data(&TEMPERATURE_REPLY);
+        assert_eq!(report_bytes(&data), TEMPERATURE_REPLY);
+        assert_eq!(temperature_reply(&TEMPERATURE_REPLY), Some(20.0));
+    }
+
+    #[test]
+    fn other_replies_ignored() {
+        for (index, value) in [(0, 0x00), (1, 0x10), (2, 0x22), (3, 0x01)] {
+            let mut reply = TEMPERATURE_REPLY;
+            reply[index] = value;
+            assert_eq!(temperature_reply(&reply), None);
+        }
+        assert_eq!(temperature_reply(&TEMPERATURE_REPLY[..5]), None);
+    }
+}
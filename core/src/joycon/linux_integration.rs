@@ -1,9 +1,10 @@
 use std::{
     collections::HashSet,
-    sync::{mpsc, Arc},
+    sync::Arc,
     time::{Duration, Instant, SystemTime},
 };
 use tokio::{sync::Mutex, time::interval};
+use tracing::{error, warn};
 
 use evdev::{enumerate, EventStream, InputEventKind, Key};
 use upower_dbus::{DeviceProxy, UPowerProxy};
@@ -11,7 +12,8 @@ use upower_dbus::{DeviceProxy, UPowerProxy};
 use crate::settings;
 
 use super::{
-    imu::JoyconAxisData, Battery, ChannelData, ChannelInfo, JoyconDesign, JoyconDesignType,
+    combo::HeldButtons, frame_queue, imu::JoyconAxisData, integration::prioritize_reader, Battery,
+    ChannelData, ChannelInfo, DeviceSender, JoyconDesign, JoyconDesignType,
 };
 
 // Resolution definitions from hid-nintendo.c from linux:
@@ -24,6 +26,10 @@ fn gyro(n: i32, scale: f64) -> f64 {
         .to_radians()
 }
 
+// BlueZ has the signal strength of a connected device only while scanning, or with some
+// adapters. It is read again this often.
+const RSSI_INTERVAL: Duration = Duration::from_secs(5);
+
 const USB_VENDOR_ID_NINTENDO: u16 = 0x057e;
 // Soon™️
 #[allow(dead_code)]
@@ -54,11 +60,33 @@ fn convert_battery(battery: upower_dbus::BatteryLevel) -> Battery {
     }
 }
 
-async fn joycon_listener(tx: mpsc::Sender<ChannelData>, mut input: EventStream) {
+async fn joycon_listener(tx: DeviceSender, settings: settings::Handler, mut input: EventStream) {
     let mac = input.device().unique_name().unwrap().to_string(); // Joycons always have unique name
 
+    let mut held = HeldButtons::default();
     while let Ok(ev) = input.next_event().await {
+        // Dropping the stream releases the grab.
+        if settings.load().joycon_blocked_get(&mac) {
+            break;
+        }
         if let InputEventKind::Key(key) = ev.kind() {
+            let before = held;
+            let down = ev.value() != 0;
+            match key {
+                Key::BTN_Z | Key::KEY_RECORD | Key::BTN_MODE => held.menu = down,
+                Key::BTN_THUMBL | Key::BTN_THUMBR => held.stick = down,
+                Key::BTN_SELECT | Key::BTN_START => held.minus_plus = down,
+                Key::BTN_TL | Key::BTN_TR => held.shoulder = down,
+                Key::BTN_TL2 | Key::BTN_TR2 => held.trigger = down,
+                _ => {}
+            }
+            if held != before {
+                tx.send(ChannelData {
+                    serial_number: mac.clone(),
+                    info: ChannelInfo::Buttons(held),
+                })
+                .unwrap();
+            }
             // if DPAD_UP or BTN_SOUTH and button is lifted
             if (key == Key::BTN_DPAD_UP || key == Key::BTN_SOUTH) && ev.value() == 0 {
                 tx.send(ChannelData {
@@ -77,11 +105,7 @@ async fn joycon_listener(tx: mpsc::Sender<ChannelData>, mut input: EventStream)
     .unwrap();
 }
 
-async fn imu_listener(
-    tx: mpsc::Sender<ChannelData>,
-    settings: settings::Handler,
-    mut input: EventStream,
-) {
+async fn imu_listener(tx: DeviceSender, settings: settings::Handler, mut input: EventStream) {
     let mac = input.device().unique_name().unwrap().to_string(); // Joycons always have unique name
     let mut imu_array = [JoyconAxisData {
         accel_x: 0.0,
@@ -94,8 +118,17 @@ async fn imu_listener(
     let mut count = 0;
     let mut sys_time = SystemTime::now();
     let mut last_event = input.device().get_abs_state().unwrap();
+    let (mut frames, frames_rx) = frame_queue();
+    tx.send(ChannelData {
+        serial_number: mac.clone(),
+        info: ChannelInfo::Stream(frames_rx),
+    })
+    .unwrap();
 
     while let Ok(ev) = input.next_event().await {
+        if settings.load().joycon_blocked_get(&mac) {
+            break;
+        }
         // If it's the same timestamp, just skip and remember the event
         if ev.timestamp() == sys_time {
             last_event = input.device().get_abs_state().unwrap();
@@ -122,16 +155,12 @@ async fn imu_listener(
         count += 1;
         if count == 3 {
             count = 0;
-            tx.send(ChannelData {
-                serial_number: mac.clone(),
-                info: ChannelInfo::ImuData(imu_array),
-            })
-            .unwrap();
+            frames.push(imu_array);
         }
     }
 }
 
-async fn check_batteries(tx: mpsc::Sender<ChannelData>, macs: &HashSet<String>) {
+async fn check_batteries(tx: DeviceSender, macs: &HashSet<String>) {
     let Ok(connection) = zbus::Connection::system().await else {
         return;
     };
@@ -154,21 +183,71 @@ async fn check_batteries(tx: mpsc::Sender<ChannelData>, macs: &HashSet<String>)
     }
 }
 
-#[tokio::main]
-pub async fn spawn_thread(tx: mpsc::Sender<ChannelData>, settings: settings::Handler) {
+// Sends the signal strength of the devices in `macs` that BlueZ has one for.
+async fn check_rssi(tx: &DeviceSender, macs: &HashSet<String>) {
+    let Ok(connection) = zbus::Connection::system().await else {
+        return;
+    };
+    let Ok(builder) = zbus::fdo::ObjectManagerProxy::builder(&connection)
+        .destination("org.bluez")
+        .and_then(|builder| builder.path("/"))
+    else {
+        return;
+    };
+    let Ok(manager) = builder.build().await else {
+        return;
+    };
+    let Ok(objects) = manager.get_managed_objects().await else {
+        return;
+    };
+    for interfaces in objects.values() {
+        let Some(device) = interfaces.iter().find_map(|(name, properties)| {
+            (name.as_str() == "org.bluez.Device1").then_some(properties)
+        }) else {
+            continue;
+        };
+        let address = device
+            .get("Address")
+            .and_then(|v| <&str>::try_from(&**v).ok());
+        let rssi = device.get("RSSI").and_then(|v| i16::try_from(&**v).ok());
+        let (Some(address), Some(rssi)) = (address, rssi) else {
+            continue;
+        };
+        if let Some(mac) = macs.iter().find(|mac| mac.eq_ignore_ascii_case(address)) {
+            tx.send(ChannelData {
+                serial_number: mac.clone(),
+                info: ChannelInfo::Rssi(rssi),
+            })
+            .ok();
+        }
+    }
+}
+
+pub fn spawn_thread(tx: DeviceSender, settings: settings::Handler) {
+    let reader_settings = settings.clone();
+    // The devices are read on the worker threads of the runtime.
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .on_thread_start(move || prioritize_reader(&reader_settings.load()))
+        .build()
+        .expect("Failed building the Runtime")
+        .block_on(listen(tx, settings));
+}
+
+async fn listen(tx: DeviceSender, settings: settings::Handler) {
     if !users::group_access_list()
         .unwrap_or_default()
         .iter()
         .any(|group| group.name() == "input")
     {
-        println!("\x1b[0;31m[ERROR]\x1b[0m Current user not in \"input\" group.");
-        println!("You need to add your user to the \"input\" group to use Wrangler.");
+        error!("The current user is not in the \"input\" group, add it to use wrangler");
     }
 
     let mut slow_stream = interval(Duration::from_secs(2));
     let paths = Arc::new(Mutex::new(HashSet::new()));
     let mut battery_macs = HashSet::new();
     let mut battery_check = Instant::now();
+    let mut rssi_check = Instant::now();
 
     loop {
         // Wait 2 seconds for enumerating
@@ -186,11 +265,16 @@ pub async fn spawn_thread(tx: mpsc::Sender<ChannelData>, settings: settings::Han
                 continue;
             }
 
-            if device.grab().is_err() {
-                println!(
-                    "Joycon {:?} is in use by another program.",
-                    device.unique_name()
-                );
+            let blocked = device
+                .unique_name()
+                .map_or(false, |mac| settings.load().joycon_blocked_get(mac));
+            if blocked {
+                continue;
+            }
+
+            // Without the grab, other programs keep getting the buttons too.
+            if !settings.load().shared_access && device.grab().is_err() {
+                warn!(device = ?device.unique_name(), "Controller is in use by another program");
                 continue;
             }
 
@@ -226,7 +310,7 @@ pub async fn spawn_thread(tx: mpsc::Sender<ChannelData>, settings: settings::Han
 
                 let paths = paths.clone();
                 tokio::spawn(async move {
-                    joycon_listener(tx, stream).await;
+                    joycon_listener(tx, settings, stream).await;
                     paths.lock().await.remove(&path);
                 });
 
@@ -239,5 +323,9 @@ pub async fn spawn_thread(tx: mpsc::Sender<ChannelData>, settings: settings::Han
             battery_check += Duration::from_secs(60 * 5);
             check_batteries(tx.clone(), &battery_macs).await;
         }
+        if rssi_check <= Instant::now() {
+            rssi_check += RSSI_INTERVAL;
+            check_rssi(&tx, &battery_macs).await;
+        }
     }
 }
@@ -0,0 +1,79 @@
+//mod ui;
+mod accel_calibration;
+mod ahrs;
+pub use ahrs::{Madgwick, Mahony};
+mod ekf;
+pub use ekf::Ekf;
+mod imu;
+pub use imu::{Imu, JoyconAxisData};
+mod orientation;
+pub use orientation::{euler_angles, in_frame, predict, GyroBias};
+mod script;
+
+mod calibration;
+pub use calibration::{CalibrationState, CalibrationStep};
+mod combo;
+pub use combo::HeldButtons;
+mod communication;
+pub use communication::*;
+mod diagnostics;
+pub use diagnostics::*;
+mod firmware;
+pub use firmware::FirmwareVersion;
+mod profile;
+pub use profile::{profile, set_profiling, Stage, ThreadProfile};
+mod queue;
+pub use queue::*;
+#[cfg(feature = "relay")]
+pub mod relay;
+mod resolver;
+mod selftest;
+pub use selftest::*;
+mod sender;
+mod session;
+pub use session::*;
+mod vr_session;
+mod watchdog;
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "windows",
+    feature = "grpc",
+    feature = "mqtt"
+))]
+mod control;
+#[cfg(target_os = "linux")]
+mod dbus;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod integration;
+pub use integration::RawConversion;
+#[cfg(target_os = "linux")]
+mod linux_integration;
+#[cfg(feature = "mqtt")]
+mod mqtt;
+#[cfg(any(
+    target_os = "linux",
+    target_os = "windows",
+    feature = "grpc",
+    feature = "mqtt"
+))]
+mod osc;
+mod osc_output;
+#[cfg(target_os = "windows")]
+mod pipe;
+use integration::{rescan, spawn_thread};
+mod test_integration;
+
+mod source;
+pub use source::*;
+#[cfg(feature = "plugins")]
+mod plugin;
+#[cfg(feature = "plugins")]
+pub use plugin::*;
+
+mod wrapper;
+pub use wrapper::*;
+
+mod design;
+pub use design::*;
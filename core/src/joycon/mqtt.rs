@@ -0,0 +1,53 @@
+use std::{collections::HashMap, sync::Arc, thread, time::Duration};
+
+use rumqttc::{Client, MqttOptions, QoS};
+use serde_json::json;
+
+use super::control::Control;
+
+const PUBLISH_INTERVAL: Duration = Duration::from_secs(1);
+const DEFAULT_PORT: u16 = 1883;
+
+/// Publishes the status and battery of every tracker to `<topic>/<serial>` on the broker, as
+/// retained JSON, every time they change. Never returns.
+pub fn publish(control: Arc<Control>, broker: String, topic: String) {
+    let (host, port) = match broker.rsplit_once(':') {
+        Some((host, port)) => (host.to_owned(), port.parse().unwrap_or(DEFAULT_PORT)),
+        None => (broker, DEFAULT_PORT),
+    };
+    let mut options = MqttOptions::new("slimevr-wrangler", host, port);
+    options.set_keep_alive(Duration::from_secs(30));
+    let (mut client, mut connection) = Client::new(options, 64);
+    // The connection has to be polled for anything to be sent, it reconnects on its own.
+    thread::spawn(move || {
+        for event in connection.iter() {
+            if event.is_err() {
+                thread::sleep(Duration::from_secs(5));
+            }
+        }
+    });
+
+    let mut published = HashMap::new();
+    loop {
+        let settings = control.settings().load();
+        for status in control.statuses() {
+            let payload = json!({
+                "nickname": settings.joycon_nickname_get(&status.serial_number),
+                "status": status.status.to_string(),
+                "battery": status.battery.name(),
+            })
+            .to_string();
+            if published.get(&status.serial_number) == Some(&payload) {
+                continue;
+            }
+            let device_topic = format!("{topic}/{}", status.serial_number);
+            if client
+                .try_publish(device_topic, QoS::AtLeastOnce, true, payload.clone())
+                .is_ok()
+            {
+                published.insert(status.serial_number, payload);
+            }
+        }
+        thread::sleep(PUBLISH_INTERVAL);
+    }
+}
@@ -0,0 +1,222 @@
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+use nalgebra::{Matrix3, Quaternion, Rotation3, UnitQuaternion, Vector3};
+
+use super::imu::JoyconAxisData;
+use crate::settings::{EulerOrder, Handedness};
+
+// A rest period has to last this long before its yaw change is trusted as drift.
+const DRIFT_MIN_REST: Duration = Duration::from_secs(10);
+// A report counts as lying still when its gyro, less the bias found so far, stays below this and
+// its acceleration within this of where the rest started. The bias is learned once a rest lasted
+// long enough, moving this fraction of the way to the gyro reading with every report, which
+// takes it there within several seconds.
+const REST_GYRO: f64 = 0.035; // radians/s, 2°/s
+const REST_ACCEL: f64 = 0.03; // G
+const BIAS_MIN_REST: Duration = Duration::from_secs(2);
+const BIAS_RATE: f64 = 0.002;
+// While the IMU tells its temperature, the bias is also learned per this many degrees, and
+// interpolated in between.
+const TEMPERATURE_STEP: f64 = 1.0; // °C
+                                   // The face of a tracker lying flat is at most this far from horizontal.
+const FLAT_TOLERANCE_DEG: f64 = 15.0;
+// Bending forward has to tilt a tracker at least this much to tell which way is forward.
+const MIN_BEND_DEG: f64 = 45.0;
+
+/// Rotation of `rotation` around the vertical (z) axis, i.e. its heading.
+pub fn yaw_component(rotation: &UnitQuaternion<f64>) -> UnitQuaternion<f64> {
+    let q = rotation.quaternion();
+    let twist = Quaternion::new(q.w, 0.0, 0.0, q.k);
+    if twist.norm() < 1e-6 {
+        // Pointing straight down/up with a half turn, heading is undefined.
+        return UnitQuaternion::identity();
+    }
+    UnitQuaternion::from_quaternion(twist)
+}
+
+/// Splits `rotation` into three angles in radians around the axes of `order`, so that it equals
+/// rotating by the first angle, then the second, then the third.
+pub fn euler_angles(rotation: &UnitQuaternion<f64>, order: EulerOrder) -> [f64; 3] {
+    let m = rotation.to_rotation_matrix().into_inner();
+    let [i, j, k] = order.axes();
+    // Odd permutations of XYZ flip the signs.
+    let sign = if (i + 1) % 3 == j { 1.0 } else { -1.0 };
+    [
+        (-sign * m[(j, k)]).atan2(m[(k, k)]),
+        (sign * m[(i, k)]).clamp(-1.0, 1.0).asin(),
+        (-sign * m[(i, j)]).atan2(m[(i, i)]),
+    ]
+}
+
+/// `rotation` as seen in the frame of `handedness`. Unity swaps Y and Z, which is a mirror that
+/// also flips the direction of every rotation.
+pub fn in_frame(rotation: &UnitQuaternion<f64>, handedness: Handedness) -> UnitQuaternion<f64> {
+    match handedness {
+        Handedness::Right => *rotation,
+        Handedness::Left => {
+            let q = rotation.quaternion();
+            UnitQuaternion::from_quaternion(Quaternion::new(q.w, -q.i, -q.k, -q.j))
+        }
+    }
+}
+
+/// `rotation` carried forward by `ahead` at the angular velocity `gyro`, in radians/s around the
+/// axes of the device. Hides some of the latency of Bluetooth, at the cost of overshooting when
+/// a movement stops.
+pub fn predict(
+    rotation: &UnitQuaternion<f64>,
+    gyro: &Vector3<f64>,
+    ahead: Duration,
+) -> UnitQuaternion<f64> {
+    rotation * UnitQuaternion::from_scaled_axis(gyro * ahead.as_secs_f64())
+}
+
+/// Offset that, applied on the sensor side, turns `rotation` into a level pose while keeping
+/// its heading. Used to capture the current pose as the neutral mounting.
+pub fn neutral_offset(rotation: &UnitQuaternion<f64>) -> UnitQuaternion<f64> {
+    rotation.inverse() * yaw_component(rotation)
+}
+
+/// Offset that, applied directly on the fused rotation, makes a tracker lying flat (face up or
+/// face down) exactly level. Returns `None` if the tracker isn't lying roughly flat.
+pub fn level_offset(rotation: &UnitQuaternion<f64>) -> Option<UnitQuaternion<f64>> {
+    let up = rotation.inverse() * Vector3::z();
+    if up.z.abs() < FLAT_TOLERANCE_DEG.to_radians().cos() {
+        return None;
+    }
+    let face = Vector3::z() * up.z.signum();
+    UnitQuaternion::rotation_between(&face, &up)
+}
+
+/// Mounting offset from the rotation of a tracker while standing straight and after bending
+/// forward 90°. With it applied, the tracker is level while standing and its +y axis points
+/// forward. Returns `None` if the two poses are too similar.
+pub fn two_pose_offset(
+    standing: &UnitQuaternion<f64>,
+    bent: &UnitQuaternion<f64>,
+) -> Option<UnitQuaternion<f64>> {
+    let up = standing.inverse() * Vector3::z();
+    // Bending forward tips the body's forward direction towards the ground.
+    let down = -(bent.inverse() * Vector3::z());
+    let forward = down - up * up.dot(&down);
+    if forward.norm() < MIN_BEND_DEG.to_radians().sin() {
+        return None;
+    }
+    let forward = forward.normalize();
+    let axes = Matrix3::from_columns(&[forward.cross(&up), forward, up]);
+    Some(UnitQuaternion::from_rotation_matrix(
+        &Rotation3::from_matrix_unchecked(axes),
+    ))
+}
+
+/// Estimates heading drift by watching how much the yaw changes while the tracker lies still.
+#[derive(Debug, Default)]
+pub struct DriftEstimator {
+    rest_start: Option<(Instant, f64)>,
+    rate: Option<f64>,
+}
+
+impl DriftEstimator {
+    pub fn update(&mut self, rotation: &UnitQuaternion<f64>, moving: bool) {
+        if moving {
+            self.rest_start = None;
+            return;
+        }
+        let (_, _, yaw) = yaw_component(rotation).euler_angles();
+        let yaw = yaw.to_degrees();
+        match self.rest_start {
+            None => self.rest_start = Some((Instant::now(), yaw)),
+            Some((start, start_yaw)) => {
+                let elapsed = start.elapsed();
+                if elapsed >= DRIFT_MIN_REST {
+                    let delta = (yaw - start_yaw + 180.0).rem_euclid(360.0) - 180.0;
+                    self.rate = Some(delta / elapsed.as_secs_f64() * 60.0);
+                }
+            }
+        }
+    }
+    /// Last measured drift in degrees per minute, `None` until a long enough rest period.
+    pub fn rate(&self) -> Option<f64> {
+        self.rate
+    }
+}
+
+/// Refines the gyro bias while the tracker lies still, so the heading doesn't drift away as the
+/// bias of the controller changes with temperature over a long session.
+///
+/// With the temperature of the IMU, the bias is learned for each temperature it lay still at, so
+/// a controller that warms up in use gets the bias of its new temperature right away, from an
+/// earlier rest. Without it, the latest bias learned is used.
+///
+/// A slow steady turn below [`REST_GYRO`] can't be told from bias, a controller that is further
+/// off than that needs a gyro calibration instead.
+#[derive(Debug, Default)]
+pub struct GyroBias {
+    bias: Vector3<f64>,
+    /// Learned at each [`TEMPERATURE_STEP`].
+    by_temperature: BTreeMap<i32, Vector3<f64>>,
+    /// Since when the tracker lies still, and its acceleration then.
+    rest: Option<(Instant, Vector3<f64>)>,
+}
+
+impl GyroBias {
+    /// Learns from the frames of a report, while it is still. `temperature` is that of the IMU
+    /// in °C, if known.
+    pub fn update(&mut self, frames: &[JoyconAxisData; 3], temperature: Option<f64>) {
+        let mean = JoyconAxisData::mean(frames);
+        let gyro = Vector3::new(mean.gyro_x, mean.gyro_y, mean.gyro_z);
+        let acc = Vector3::new(mean.accel_x, mean.accel_y, mean.accel_z);
+        let bias = self.at(temperature);
+        let still = (gyro - bias).norm() < REST_GYRO
+            && (acc.norm() - 1.0).abs() < REST_ACCEL
+            && self
+                .rest
+                .map_or(true, |(_, start)| (acc - start).norm() < REST_ACCEL);
+        if !still {
+            self.rest = None;
+            return;
+        }
+        let (since, _) = *self.rest.get_or_insert((Instant::now(), acc));
+        if since.elapsed() >= BIAS_MIN_REST {
+            self.bias += (gyro - self.bias) * BIAS_RATE;
+            if let Some(temperature) = temperature {
+                let step = (temperature / TEMPERATURE_STEP).round() as i32;
+                let learned = self.by_temperature.entry(step).or_insert(bias);
+                *learned += (gyro - *learned) * BIAS_RATE;
+            }
+        }
+    }
+    /// The bias at `temperature`: between the two nearest temperatures it was learned at, or the
+    /// nearest one outside of them. Before any, and without a temperature, the latest bias.
+    pub fn at(&self, temperature: Option<f64>) -> Vector3<f64> {
+        let step = match temperature {
+            Some(temperature) => temperature / TEMPERATURE_STEP,
+            None => return self.bias,
+        };
+        let below = self
+            .by_temperature
+            .range(..=step.floor() as i32)
+            .next_back();
+        let above = self.by_temperature.range(step.ceil() as i32..).next();
+        match (below, above) {
+            (Some((&low, at_low)), Some((&high, at_high))) if low != high => {
+                at_low + (at_high - at_low) * ((step - f64::from(low)) / f64::from(high - low))
+            }
+            (Some((_, bias)), _) | (None, Some((_, bias))) => *bias,
+            (None, None) => self.bias,
+        }
+    }
+    /// The frame with the bias at `temperature` taken out of its gyro.
+    pub fn correct(&self, frame: JoyconAxisData, temperature: Option<f64>) -> JoyconAxisData {
+        let bias = self.at(temperature);
+        JoyconAxisData {
+            gyro_x: frame.gyro_x - bias.x,
+            gyro_y: frame.gyro_y - bias.y,
+            gyro_z: frame.gyro_z - bias.z,
+            ..frame
+        }
+    }
+}
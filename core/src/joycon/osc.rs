@@ -0,0 +1,182 @@
+//! Open Sound Control input, so VRChat avatar buttons and other OSC tools can control wrangler.
+//!
+//! Commands are accepted on `/wrangler/<command>` and, for VRChat avatar parameters, on
+//! `/avatar/parameters/Wrangler<Command>`. See [`run`] for the commands.
+//! https://opensoundcontrol.stanford.edu/spec-1_0.html
+
+use std::{net::UdpSocket, sync::Arc};
+
+use super::control::Control;
+
+const PREFIX: &str = "/wrangler/";
+const AVATAR_PREFIX: &str = "/avatar/parameters/Wrangler";
+const BUNDLE: &[u8] = b"#bundle\0";
+// Bundles inside bundles, deeper than this is not worth following.
+const MAX_DEPTH: usize = 4;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Arg {
+    Int(i32),
+    Float(f32),
+    Str(String),
+    Bool(bool),
+}
+impl Arg {
+    // VRChat sends buttons as bools, ints or floats depending on the parameter type.
+    fn truthy(&self) -> bool {
+        match self {
+            Arg::Int(i) => *i != 0,
+            Arg::Float(f) => *f != 0.0,
+            Arg::Str(s) => !s.is_empty(),
+            Arg::Bool(b) => *b,
+        }
+    }
+}
+
+/// Listens for OSC messages on `port` of localhost until the process exits. Returns early if the
+/// port is taken.
+pub fn serve(control: Arc<Control>, port: u16) {
+    let Ok(socket) = UdpSocket::bind(("127.0.0.1", port)) else {
+        return;
+    };
+    let mut buf = [0; 1536];
+    loop {
+        let Ok(len) = socket.recv(&mut buf) else {
+            continue;
+        };
+        let mut messages = Vec::new();
+        parse_packet(&buf[..len], 0, &mut messages);
+        for (address, args) in messages {
+            run(&control, &address, &args);
+        }
+    }
+}
+
+/// Runs the command of an OSC message, with the first argument as its value:
+/// - `reset` does a yaw reset when the value is true or missing, so a button does one reset.
+/// - `pause` pauses all trackers while the value is true or missing, and resumes them on false.
+/// - `resume` resumes all trackers when the value is true or missing.
+/// - `identify` shows the identify lights on the tracker with the serial number or nickname given
+///   as a string, or the n-th tracker for an int, counting from 1.
+///
+/// `reset`, `pause`, `resume` and `identify` followed by `/<group>`, like `/wrangler/pause/legs`,
+/// act on the trackers of that group only, with the same values.
+fn run(control: &Control, address: &str, args: &[Arg]) {
+    let Some(command) = address
+        .strip_prefix(PREFIX)
+        .or_else(|| address.strip_prefix(AVATAR_PREFIX))
+    else {
+        return;
+    };
+    let value = args.first();
+    let pressed = value.map_or(true, Arg::truthy);
+    if let Some((command, group)) = command.split_once('/') {
+        match command.to_ascii_lowercase().as_str() {
+            "reset" if pressed => control.reset_group(group),
+            "pause" => control.set_group_paused(group, pressed),
+            "resume" if pressed => control.set_group_paused(group, false),
+            "identify" if pressed => control.identify_group(group),
+            _ => {}
+        }
+        return;
+    }
+    match command.to_ascii_lowercase().as_str() {
+        "reset" if pressed => control.reset(),
+        "pause" => control.set_paused(pressed),
+        "resume" if pressed => control.set_paused(false),
+        "identify" => {
+            let statuses = control.statuses();
+            let serial_number = match value {
+                Some(Arg::Str(name)) => {
+                    let settings = control.settings().load();
+                    statuses
+                        .into_iter()
+                        .map(|status| status.serial_number)
+                        .find(|serial| {
+                            serial == name || settings.joycon_nickname_get(serial) == *name
+                        })
+                }
+                Some(Arg::Int(n)) if *n > 0 => statuses
+                    .into_iter()
+                    .nth(*n as usize - 1)
+                    .map(|status| status.serial_number),
+                _ => None,
+            };
+            if let Some(serial_number) = serial_number {
+                control.identify(serial_number);
+            }
+        }
+        _ => {}
+    }
+}
+
+// Collects the messages of a packet, which is a message or a bundle of packets. Anything
+// malformed is skipped.
+fn parse_packet(packet: &[u8], depth: usize, messages: &mut Vec<(String, Vec<Arg>)>) {
+    if let Some(mut rest) = packet.strip_prefix(BUNDLE) {
+        // The time tag is ignored, commands run as soon as they arrive.
+        if depth >= MAX_DEPTH || rest.len() < 8 {
+            return;
+        }
+        rest = &rest[8..];
+        while let Some((size, after)) = read_i32(rest) {
+            let Some(element) = after.get(..size as usize) else {
+                return;
+            };
+            parse_packet(element, depth + 1, messages);
+            rest = &after[size as usize..];
+        }
+    } else if let Some(message) = parse_message(packet) {
+        messages.push(message);
+    }
+}
+
+fn parse_message(packet: &[u8]) -> Option<(String, Vec<Arg>)> {
+    let (address, rest) = read_str(packet)?;
+    if !address.starts_with('/') {
+        return None;
+    }
+    // Very old senders leave out the type tags, treat that as no arguments.
+    let Some((tags, mut rest)) = read_str(rest) else {
+        return Some((address, Vec::new()));
+    };
+    let mut args = Vec::new();
+    for tag in tags.strip_prefix(',')?.chars() {
+        let arg = match tag {
+            'i' => {
+                let (value, after) = read_i32(rest)?;
+                rest = after;
+                Arg::Int(value)
+            }
+            'f' => {
+                let (value, after) = read_i32(rest)?;
+                rest = after;
+                Arg::Float(f32::from_bits(value as u32))
+            }
+            's' => {
+                let (value, after) = read_str(rest)?;
+                rest = after;
+                Arg::Str(value)
+            }
+            'T' => Arg::Bool(true),
+            'F' => Arg::Bool(false),
+            // The size of other types is unknown, stop at the first one.
+            _ => break,
+        };
+        args.push(arg);
+    }
+    Some((address, args))
+}
+
+fn read_i32(data: &[u8]) -> Option<(i32, &[u8])> {
+    let bytes = data.get(..4)?;
+    Some((i32::from_be_bytes(bytes.try_into().ok()?), &data[4..]))
+}
+
+// A string ends with a nul and is padded with more of them to a multiple of four bytes.
+fn read_str(data: &[u8]) -> Option<(String, &[u8])> {
+    let len = data.iter().position(|&b| b == 0)?;
+    let padded = (len / 4 + 1) * 4;
+    let value = std::str::from_utf8(&data[..len]).ok()?.to_owned();
+    Some((value, data.get(padded..)?))
+}
@@ -0,0 +1,83 @@
+//! Rotations of the trackers as VRChat OSC trackers, next to or instead of the SlimeVR server.
+//!
+//! VRChat reads `/tracking/trackers/<1-8>/rotation` as Euler angles in degrees, in Unity's frame:
+//! left-handed with Y up, rotating around Z, then X, then Y. Wrangler only knows rotations, the
+//! positions are left to whatever else feeds VRChat.
+//! https://docs.vrchat.com/docs/osc-trackers
+
+use nalgebra::UnitQuaternion;
+
+use super::orientation;
+use crate::settings::Handedness;
+
+/// The OSC message with the rotation of tracker `index`, counting from 1.
+pub(crate) fn tracker_rotation(index: u32, rotation: &UnitQuaternion<f64>) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(64);
+    push_str(&mut packet, &format!("/tracking/trackers/{index}/rotation"));
+    push_str(&mut packet, ",fff");
+    for angle in unity_euler(rotation) {
+        packet.extend_from_slice(&(angle as f32).to_be_bytes());
+    }
+    packet
+}
+
+// X, Y and Z in degrees the way Unity applies them: around Z, then X, then Y.
+fn unity_euler(rotation: &UnitQuaternion<f64>) -> [f64; 3] {
+    let unity = orientation::in_frame(rotation, Handedness::Left);
+    let m = unity.to_rotation_matrix().into_inner();
+    [
+        (-m[(1, 2)]).clamp(-1.0, 1.0).asin().to_degrees(),
+        m[(0, 2)].atan2(m[(2, 2)]).to_degrees(),
+        m[(1, 0)].atan2(m[(1, 1)]).to_degrees(),
+    ]
+}
+
+// OSC strings end with a nul and are padded with more of them to a multiple of four bytes.
+fn push_str(packet: &mut Vec<u8>, value: &str) {
+    packet.extend_from_slice(value.as_bytes());
+    let padding = 4 - value.len() % 4;
+    packet.extend(std::iter::repeat(0).take(padding));
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+
+    use super::*;
+
+    fn assert_angles(angles: [f64; 3], expected: [f64; 3]) {
+        for (angle, expected) in angles.into_iter().zip(expected) {
+            assert!(
+                (angle - expected).abs() < 1e-9,
+                "{angles:?} != {expected:?}"
+            );
+        }
+    }
+
+    fn around(axis: usize, degrees: f64) -> UnitQuaternion<f64> {
+        UnitQuaternion::from_axis_angle(&Vector3::ith_axis(axis), degrees.to_radians())
+    }
+
+    #[test]
+    fn level() {
+        assert_angles(unity_euler(&UnitQuaternion::identity()), [0.0; 3]);
+    }
+
+    #[test]
+    fn single_axes() {
+        // Wrangler's Z up is Unity's Y, and counterclockwise from above turns clockwise there.
+        assert_angles(unity_euler(&around(2, 90.0)), [0.0, -90.0, 0.0]);
+        assert_angles(unity_euler(&around(0, 30.0)), [-30.0, 0.0, 0.0]);
+        // Wrangler's Y forward is Unity's Z.
+        assert_angles(unity_euler(&around(1, 45.0)), [0.0, 0.0, -45.0]);
+    }
+
+    #[test]
+    fn unity_order() {
+        let [x, y, z] = [20.0_f64, -35.0_f64, 50.0_f64];
+        // Built in Unity's frame, around Z, then X, then Y.
+        let unity = around(1, y) * around(0, x) * around(2, z);
+        let rotation = orientation::in_frame(&unity, Handedness::Left);
+        assert_angles(unity_euler(&rotation), [x, y, z]);
+    }
+}
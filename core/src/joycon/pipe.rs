@@ -0,0 +1,87 @@
+use std::sync::Arc;
+
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::windows::named_pipe::{NamedPipeServer, ServerOptions},
+};
+
+use serde_json::json;
+
+use super::control::Control;
+
+const PIPE_NAME: &str = r"\\.\pipe\slimevr-wrangler";
+
+/// Serves [`Control`] on a named pipe until the process exits, one text command per line, see
+/// [`command`]. Returns early if another wrangler already owns the pipe.
+#[tokio::main]
+pub async fn serve(control: Arc<Control>) {
+    let mut server = match ServerOptions::new()
+        .first_pipe_instance(true)
+        .create(PIPE_NAME)
+    {
+        Ok(server) => server,
+        Err(_) => return,
+    };
+    loop {
+        if server.connect().await.is_err() {
+            continue;
+        }
+        // Each client gets its own instance, a new one waits for the next client.
+        let client = server;
+        server = match ServerOptions::new().create(PIPE_NAME) {
+            Ok(server) => server,
+            Err(_) => return,
+        };
+        tokio::spawn(handle(client, control.clone()));
+    }
+}
+
+async fn handle(pipe: NamedPipeServer, control: Arc<Control>) {
+    let (read, mut write) = tokio::io::split(pipe);
+    let mut lines = BufReader::new(read).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let reply = command(&control, line.trim());
+        if write
+            .write_all(format!("{reply}\n").as_bytes())
+            .await
+            .is_err()
+        {
+            break;
+        }
+    }
+}
+
+/// Runs a text command (`reset`, `pause`, `resume`, `rescan` or `status`) and returns the
+/// one-line reply. `reset`, `pause`, `resume` and `identify` followed by a group name act on the
+/// trackers of that group only.
+fn command(control: &Control, command: &str) -> String {
+    let (command, group) = command.split_once(' ').unwrap_or((command, ""));
+    let group = group.trim();
+    match (command, group) {
+        ("reset", "") => control.reset(),
+        ("pause", "") => control.set_paused(true),
+        ("resume", "") => control.set_paused(false),
+        ("reset", group) => control.reset_group(group),
+        ("pause", group) => control.set_group_paused(group, true),
+        ("resume", group) => control.set_group_paused(group, false),
+        ("identify", group) if !group.is_empty() => control.identify_group(group),
+        ("rescan", "") => control.rescan(),
+        ("status", "") => {
+            let devices: Vec<_> = control
+                .devices()
+                .into_iter()
+                .map(|(serial, status, battery, rate)| {
+                    json!({ "serial": serial, "status": status, "battery": battery, "rate": rate })
+                })
+                .collect();
+            return json!({
+                "paused": control.paused(),
+                "paused_groups": control.paused_groups(),
+                "devices": devices,
+            })
+            .to_string();
+        }
+        _ => return format!("error: unknown command {command:?}"),
+    }
+    "ok".into()
+}
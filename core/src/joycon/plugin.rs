@@ -0,0 +1,191 @@
+//! Input sources loaded from dynamic libraries in the `plugins` folder next to the settings.
+//!
+//! A plugin exports `wrangler_plugin_run` with the signature of [`PluginRun`]. It gets called on
+//! a thread of its own and reports its devices through the callbacks in [`PluginHost`]. Only C
+//! types cross the boundary, so plugins can be written in any language.
+
+use std::{
+    env::consts::DLL_EXTENSION,
+    ffi::{c_char, c_void, CStr, OsStr},
+    fs,
+    path::PathBuf,
+    sync::Mutex,
+};
+
+use libloading::{Library, Symbol};
+
+use super::{
+    Battery, ChannelData, ChannelInfo, DeviceSender, ErrorReport, JoyconAxisData, JoyconDesign,
+    JoyconDesignType, MotionSource,
+};
+use crate::settings;
+
+/// Bumped whenever [`PluginHost`] or [`PluginRun`] change incompatibly.
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+/// One IMU frame, acceleration in G and rotation in radians/s.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct PluginFrame {
+    pub accel: [f64; 3],
+    pub gyro: [f64; 3],
+}
+
+/// Callbacks handed to a plugin, the Rust side of [`ChannelInfo`].
+///
+/// `serial` is a NUL-terminated UTF-8 string identifying the device, `context` has to be passed
+/// back unchanged. The callbacks may be called from any thread until `wrangler_plugin_run`
+/// returns.
+#[repr(C)]
+pub struct PluginHost {
+    pub context: *mut c_void,
+    /// `design` is 0 for a left Joy-Con, 1 for a right one and 2 for a Pro Controller, `color`
+    /// a "#rrggbb" string.
+    pub connected: unsafe extern "C" fn(*mut c_void, *const c_char, u8, *const c_char),
+    /// Three consecutive frames, 5ms apart.
+    pub imu_data: unsafe extern "C" fn(*mut c_void, *const c_char, *const PluginFrame),
+    /// From 0 (empty) to 4 (full).
+    pub battery: unsafe extern "C" fn(*mut c_void, *const c_char, u8),
+    pub reset: unsafe extern "C" fn(*mut c_void, *const c_char),
+    pub disconnected: unsafe extern "C" fn(*mut c_void, *const c_char),
+}
+
+/// Signature of the `wrangler_plugin_run` export. Runs the plugin on the calling thread until it
+/// has nothing more to report, returning 0 on success.
+pub type PluginRun = unsafe extern "C" fn(abi_version: u32, host: *const PluginHost) -> i32;
+
+struct Context {
+    tx: Mutex<DeviceSender>,
+}
+
+unsafe fn string(ptr: *const c_char) -> String {
+    if ptr.is_null() {
+        return String::new();
+    }
+    CStr::from_ptr(ptr).to_string_lossy().into_owned()
+}
+
+unsafe fn send(context: *mut c_void, serial: *const c_char, info: ChannelInfo) {
+    let context = &*(context as *const Context);
+    if let Ok(tx) = context.tx.lock() {
+        tx.send(ChannelData::new(string(serial), info)).ok();
+    }
+}
+
+unsafe extern "C" fn connected(
+    context: *mut c_void,
+    serial: *const c_char,
+    design: u8,
+    color: *const c_char,
+) {
+    let design_type = match design {
+        1 => JoyconDesignType::Right,
+        2 => JoyconDesignType::Pro,
+        _ => JoyconDesignType::Left,
+    };
+    let design = JoyconDesign {
+        color: string(color),
+        design_type,
+    };
+    send(context, serial, ChannelInfo::Connected(design));
+}
+
+unsafe extern "C" fn imu_data(
+    context: *mut c_void,
+    serial: *const c_char,
+    frames: *const PluginFrame,
+) {
+    if frames.is_null() {
+        return;
+    }
+    let frames = std::slice::from_raw_parts(frames, 3);
+    let data = std::array::from_fn(|i| JoyconAxisData {
+        accel_x: frames[i].accel[0],
+        accel_y: frames[i].accel[1],
+        accel_z: frames[i].accel[2],
+        gyro_x: frames[i].gyro[0],
+        gyro_y: frames[i].gyro[1],
+        gyro_z: frames[i].gyro[2],
+    });
+    send(context, serial, ChannelInfo::ImuData(data));
+}
+
+unsafe extern "C" fn battery(context: *mut c_void, serial: *const c_char, level: u8) {
+    let battery = match level {
+        0 => Battery::Empty,
+        1 => Battery::Critical,
+        2 => Battery::Low,
+        3 => Battery::Medium,
+        _ => Battery::Full,
+    };
+    send(context, serial, ChannelInfo::Battery(battery));
+}
+
+unsafe extern "C" fn reset(context: *mut c_void, serial: *const c_char) {
+    send(context, serial, ChannelInfo::Reset);
+}
+
+unsafe extern "C" fn disconnected(context: *mut c_void, serial: *const c_char) {
+    send(context, serial, ChannelInfo::Disconnected);
+}
+
+struct Plugin {
+    path: PathBuf,
+}
+impl Plugin {
+    fn load_and_run(&self, tx: &DeviceSender) -> Result<(), String> {
+        // Plugins are trusted, the user put them into the plugins folder.
+        let library = unsafe { Library::new(&self.path) }
+            .map_err(|e| format!("Could not load the plugin: {e}"))?;
+        let run: Symbol<PluginRun> = unsafe { library.get(b"wrangler_plugin_run\0") }
+            .map_err(|_| "The plugin does not export wrangler_plugin_run".to_string())?;
+        let context = Context {
+            tx: Mutex::new(tx.clone()),
+        };
+        let host = PluginHost {
+            context: &context as *const Context as *mut c_void,
+            connected,
+            imu_data,
+            battery,
+            reset,
+            disconnected,
+        };
+        match unsafe { run(PLUGIN_ABI_VERSION, &host) } {
+            0 => Ok(()),
+            code => Err(format!("The plugin stopped with error code {code}")),
+        }
+    }
+}
+impl MotionSource for Plugin {
+    fn name(&self) -> String {
+        self.path
+            .file_stem()
+            .map_or_else(String::new, |name| name.to_string_lossy().into_owned())
+    }
+    fn run(self: Box<Self>, tx: DeviceSender, _settings: settings::Handler) {
+        if let Err(message) = self.load_and_run(&tx) {
+            let name = self.name();
+            let report = ErrorReport::new(&name, message)
+                .suggest("Check that the plugin is built for this version of wrangler.");
+            tx.send(ChannelData::new(name, ChannelInfo::Error(report)))
+                .ok();
+        }
+    }
+}
+
+/// Folder plugins are loaded from, next to the settings file in use.
+pub fn plugin_dir() -> Option<PathBuf> {
+    settings::config_dir().map(|dir| dir.join("plugins"))
+}
+
+/// A source for every dynamic library in [`plugin_dir`]. Nothing is loaded until they are run.
+pub fn load_plugins() -> Vec<Box<dyn MotionSource>> {
+    plugin_dir()
+        .and_then(|dir| fs::read_dir(dir).ok())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension() == Some(OsStr::new(DLL_EXTENSION)))
+        .map(|path| Box::new(Plugin { path }) as Box<dyn MotionSource>)
+        .collect()
+}
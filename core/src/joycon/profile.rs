@@ -0,0 +1,171 @@
+//! Self-profiling of the background threads: CPU time spent in each [`Stage`] of the pipeline.
+//!
+//! Threads register themselves the first time they measure something. Only CPU time is counted,
+//! a thread waiting on the controller or a socket costs nothing. Windows reports thread times in
+//! scheduler ticks, so short stages only average out over a few seconds there.
+//!
+//! Reading the thread time is a system call on some platforms, twice per stage and packet, so
+//! nothing is measured until [`set_profiling`] turns it on.
+
+use std::{
+    cell::RefCell,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, Weak,
+    },
+    time::Duration,
+};
+
+use cpu_time::ThreadTime;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Reading and converting reports from the controller.
+    ReadHid,
+    /// Sensor fusion.
+    Fusion,
+    /// Turning packets into bytes.
+    Serialize,
+    /// Handing packets to the network.
+    Send,
+}
+impl Stage {
+    pub const ALL: [Stage; 4] = [Stage::ReadHid, Stage::Fusion, Stage::Serialize, Stage::Send];
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::ReadHid => "reading HID",
+            Stage::Fusion => "fusion",
+            Stage::Serialize => "serializing",
+            Stage::Send => "sending",
+        }
+    }
+}
+
+#[derive(Default)]
+struct Entry {
+    name: Mutex<String>,
+    total: AtomicU64,
+    stages: [AtomicU64; Stage::ALL.len()],
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+// Weak, so threads that exited drop out of the snapshots.
+static THREADS: Mutex<Vec<Weak<Entry>>> = Mutex::new(Vec::new());
+
+thread_local! {
+    static ENTRY: RefCell<Option<Arc<Entry>>> = const { RefCell::new(None) };
+}
+
+fn with_entry(f: impl FnOnce(&Entry)) {
+    ENTRY.with(|entry| {
+        let mut entry = entry.borrow_mut();
+        let entry = entry.get_or_insert_with(|| {
+            let thread = std::thread::current();
+            let name = match thread.name() {
+                Some(name) => name.to_owned(),
+                None => format!("{:?}", thread.id()),
+            };
+            let new = Arc::new(Entry {
+                name: Mutex::new(name),
+                ..Default::default()
+            });
+            if let Ok(mut threads) = THREADS.lock() {
+                threads.retain(|thread| thread.strong_count() > 0);
+                threads.push(Arc::downgrade(&new));
+            }
+            new
+        });
+        f(entry);
+    });
+}
+
+/// Name the current thread is listed under, threads rename themselves when their device changes.
+pub(crate) fn name_thread(name: impl Into<String>) {
+    let name = name.into();
+    with_entry(|entry| {
+        if let Ok(mut current) = entry.name.lock() {
+            *current = name;
+        }
+    });
+}
+
+/// Starts or stops measuring, e.g. while the numbers are shown. Totals are kept in between.
+pub fn set_profiling(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Adds the CPU time until the guard is dropped to `stage` of the current thread, if profiling.
+pub(crate) fn measure(stage: Stage) -> Measure {
+    let start = if ENABLED.load(Ordering::Relaxed) {
+        ThreadTime::try_now().ok()
+    } else {
+        None
+    };
+    Measure { stage, start }
+}
+
+pub(crate) struct Measure {
+    stage: Stage,
+    start: Option<ThreadTime>,
+}
+impl Drop for Measure {
+    fn drop(&mut self) {
+        let (Some(start), Ok(now)) = (self.start, ThreadTime::try_now()) else {
+            return;
+        };
+        let spent = now.duration_since(start).as_nanos() as u64;
+        let total = now.as_duration().as_nanos() as u64;
+        with_entry(|entry| {
+            entry.stages[self.stage as usize].fetch_add(spent, Ordering::Relaxed);
+            entry.total.store(total, Ordering::Relaxed);
+        });
+    }
+}
+
+/// CPU time of one thread since it started, in total and per [`Stage`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ThreadProfile {
+    pub thread: String,
+    pub total: Duration,
+    pub stages: [Duration; Stage::ALL.len()],
+}
+impl ThreadProfile {
+    /// Share of one core used in total and per stage between `earlier` and this snapshot,
+    /// `elapsed` apart.
+    pub fn usage_since(
+        &self,
+        earlier: &ThreadProfile,
+        elapsed: Duration,
+    ) -> (f64, [f64; Stage::ALL.len()]) {
+        let share = |now: Duration, then: Duration| {
+            now.saturating_sub(then).as_secs_f64() / elapsed.as_secs_f64().max(f64::EPSILON)
+        };
+        (
+            share(self.total, earlier.total),
+            std::array::from_fn(|i| share(self.stages[i], earlier.stages[i])),
+        )
+    }
+}
+
+/// Current totals of every profiled thread that is still running.
+pub fn profile() -> Vec<ThreadProfile> {
+    let threads = match THREADS.lock() {
+        Ok(threads) => threads.iter().filter_map(Weak::upgrade).collect::<Vec<_>>(),
+        Err(_) => return vec![],
+    };
+    threads
+        .iter()
+        .map(|entry| ThreadProfile {
+            thread: entry
+                .name
+                .lock()
+                .map(|name| name.clone())
+                .unwrap_or_default(),
+            total: Duration::from_nanos(entry.total.load(Ordering::Relaxed)),
+            stages: std::array::from_fn(|i| {
+                Duration::from_nanos(entry.stages[i].load(Ordering::Relaxed))
+            }),
+        })
+        .collect()
+}
@@ -0,0 +1,142 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    mpsc, Arc, Mutex,
+};
+
+// Device messages and actions wait for the communication thread, which never stalls for long.
+pub(crate) const DEVICE_QUEUE_SIZE: usize = 256;
+pub(crate) const ACTION_QUEUE_SIZE: usize = 64;
+// Commands are rare, a full queue means the device thread is stuck anyway.
+pub(crate) const COMMAND_QUEUE_SIZE: usize = 8;
+// Statuses are resent several times a second, a backlog of them is stale already.
+pub(crate) const STATUS_QUEUE_SIZE: usize = 8;
+pub(crate) const ERROR_QUEUE_SIZE: usize = 64;
+// A few reports of one tracker, a sender that fell further behind would only send stale ones.
+pub(crate) const PACKET_QUEUE_SIZE: usize = 16;
+
+/// Queues that drop messages instead of growing when their receiver falls behind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Queue {
+    /// IMU reports of a device, see [`frame_queue`](super::frame_queue).
+    Frames,
+    /// Commands from the communication thread to a device.
+    Commands,
+    /// Statuses on their way to the GUI.
+    Statuses,
+    /// Server connection state, only the newest one is kept.
+    Server,
+    /// Handshake logs, only the newest one is kept.
+    Handshake,
+    /// Error reports on their way to the GUI.
+    Errors,
+    /// Tracker packets on their way to the sender thread of their device.
+    Packets,
+    /// Packets the send buffer of a socket had no room for.
+    Socket,
+}
+impl Queue {
+    pub const ALL: [Queue; 8] = [
+        Queue::Frames,
+        Queue::Commands,
+        Queue::Statuses,
+        Queue::Server,
+        Queue::Handshake,
+        Queue::Errors,
+        Queue::Packets,
+        Queue::Socket,
+    ];
+}
+
+// Only used to fill the array below, one counter per queue.
+#[allow(clippy::declare_interior_mutable_const)]
+const NONE_DROPPED: AtomicU64 = AtomicU64::new(0);
+static DROPPED: [AtomicU64; Queue::ALL.len()] = [NONE_DROPPED; Queue::ALL.len()];
+
+pub(crate) fn count_drop(queue: Queue) {
+    DROPPED[queue as usize].fetch_add(1, Ordering::Relaxed);
+}
+
+/// Messages `queue` dropped since startup, over all devices.
+pub fn dropped(queue: Queue) -> u64 {
+    DROPPED[queue as usize].load(Ordering::Relaxed)
+}
+
+/// What a [`BoundedSender`] does when its queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overflow {
+    /// Wait for the receiver, for messages that must not get lost.
+    Block,
+    /// Throw the new message away and count it.
+    Drop(Queue),
+}
+
+/// Sending end of a channel that holds at most a fixed number of messages.
+#[derive(Debug)]
+pub struct BoundedSender<T> {
+    tx: mpsc::SyncSender<T>,
+    overflow: Overflow,
+}
+impl<T> Clone for BoundedSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+            overflow: self.overflow,
+        }
+    }
+}
+impl<T> BoundedSender<T> {
+    /// Fails only once the receiver is gone, a dropped message is not an error.
+    pub fn send(&self, value: T) -> Result<(), mpsc::SendError<T>> {
+        match self.overflow {
+            Overflow::Block => self.tx.send(value),
+            Overflow::Drop(queue) => match self.tx.try_send(value) {
+                Err(mpsc::TrySendError::Full(_)) => {
+                    count_drop(queue);
+                    Ok(())
+                }
+                Err(mpsc::TrySendError::Disconnected(value)) => Err(mpsc::SendError(value)),
+                Ok(()) => Ok(()),
+            },
+        }
+    }
+}
+
+pub fn bounded<T>(capacity: usize, overflow: Overflow) -> (BoundedSender<T>, mpsc::Receiver<T>) {
+    let (tx, rx) = mpsc::sync_channel(capacity);
+    (BoundedSender { tx, overflow }, rx)
+}
+
+/// Slot for state that only matters in its newest version. Sending replaces an unread value,
+/// which is counted as dropped.
+#[derive(Debug)]
+pub struct Latest<T> {
+    slot: Arc<Mutex<Option<T>>>,
+    queue: Queue,
+}
+impl<T> Clone for Latest<T> {
+    fn clone(&self) -> Self {
+        Self {
+            slot: self.slot.clone(),
+            queue: self.queue,
+        }
+    }
+}
+impl<T> Latest<T> {
+    pub fn new(queue: Queue) -> Self {
+        Self {
+            slot: Arc::new(Mutex::new(None)),
+            queue,
+        }
+    }
+    pub fn send(&self, value: T) {
+        if let Ok(mut slot) = self.slot.lock() {
+            if slot.replace(value).is_some() {
+                count_drop(self.queue);
+            }
+        }
+    }
+    /// The newest value, if one was sent since the last take.
+    pub fn take(&self) -> Option<T> {
+        self.slot.lock().ok()?.take()
+    }
+}
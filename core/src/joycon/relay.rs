@@ -0,0 +1,299 @@
+//! Tunnels the connection to the SlimeVR server through a relay over QUIC, for a server on
+//! another network than the Joy-Cons.
+//!
+//! On the server's network, `slimevr-wrangler --relay <listen address>` runs the relay, see
+//! [`serve`]. It forwards to the server address of its own settings and prints what the other
+//! side needs: the fingerprint of its certificate and the token. Wrangler then sends to a local
+//! socket, which [`spawn_client`] forwards as QUIC datagrams. The relay is authenticated by its
+//! pinned certificate, wrangler by the token.
+
+use std::{
+    fs, io,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket as StdUdpSocket},
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use bytes::Bytes;
+use directories::ProjectDirs;
+use quinn::{Connection, Endpoint, IdleTimeout, TransportConfig};
+use rustls::client::{ServerCertVerified, ServerCertVerifier};
+use rustls::{Certificate, PrivateKey, ServerName};
+use tokio::net::UdpSocket;
+
+use super::queue::BoundedSender;
+use super::ErrorReport;
+use crate::settings::{self, WranglerSettings};
+
+const ALPN: &[u8] = b"slimevr-relay";
+// Name in the certificate, it is pinned by fingerprint, so the name doesn't matter.
+const SERVER_NAME: &str = "slimevr-relay";
+const MAX_TOKEN_SIZE: usize = 256;
+const KEEP_ALIVE: Duration = Duration::from_secs(5);
+const IDLE_TIMEOUT: Duration = Duration::from_secs(20);
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(5);
+
+fn other(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, e)
+}
+
+fn transport() -> Arc<TransportConfig> {
+    let mut transport = TransportConfig::default();
+    transport.keep_alive_interval(Some(KEEP_ALIVE));
+    transport.max_idle_timeout(IdleTimeout::try_from(IDLE_TIMEOUT).ok());
+    Arc::new(transport)
+}
+
+/// SHA-256 of a certificate as colon separated hex, the way it is shown and entered.
+fn fingerprint(cert: &[u8]) -> String {
+    ring::digest::digest(&ring::digest::SHA256, cert)
+        .as_ref()
+        .iter()
+        .map(|b| format!("{b:02X}"))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+// Fingerprints compare without separators or case.
+fn normalize(fingerprint: &str) -> String {
+    fingerprint
+        .chars()
+        .filter(char::is_ascii_hexdigit)
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+/// Accepts only the relay certificate with the configured fingerprint.
+struct Pinned(String);
+impl ServerCertVerifier for Pinned {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if normalize(&fingerprint(&end_entity.0)) == self.0 {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::General(
+                "the relay certificate does not match the fingerprint".into(),
+            ))
+        }
+    }
+}
+
+/// Starts forwarding to the relay of the settings, if one is set. Returns the local address to
+/// send server packets to instead.
+pub(crate) fn spawn_client(
+    settings: &WranglerSettings,
+    errors_tx: BoundedSender<ErrorReport>,
+) -> Option<SocketAddr> {
+    if settings.relay_address.is_empty() {
+        return None;
+    }
+    let local = StdUdpSocket::bind(SocketAddr::from(([127, 0, 0, 1], 0))).ok()?;
+    let local_address = local.local_addr().ok()?;
+    let relay = settings.relay_address.clone();
+    let pinned = normalize(&settings.relay_fingerprint);
+    let token = settings.relay_token.clone();
+    std::thread::spawn(move || run_client(local, relay, pinned, token, errors_tx));
+    Some(local_address)
+}
+
+#[tokio::main]
+async fn run_client(
+    local: StdUdpSocket,
+    relay: String,
+    pinned: String,
+    token: String,
+    errors_tx: BoundedSender<ErrorReport>,
+) {
+    let report = |message: String| {
+        errors_tx
+            .send(ErrorReport::new("Relay", message).suggest(
+                "Check the relay address, fingerprint and token in the settings, and that the relay is running.",
+            ))
+            .ok();
+    };
+    let local = match local
+        .set_nonblocking(true)
+        .and_then(|()| UdpSocket::from_std(local))
+    {
+        Ok(local) => local,
+        Err(e) => return report(format!("Could not open the local relay socket: {e}")),
+    };
+    let mut crypto = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(Arc::new(Pinned(pinned)))
+        .with_no_client_auth();
+    crypto.alpn_protocols = vec![ALPN.to_vec()];
+    let mut config = quinn::ClientConfig::new(Arc::new(crypto));
+    config.transport_config(transport());
+    let mut endpoint = match Endpoint::client(SocketAddr::from(([0, 0, 0, 0], 0))) {
+        Ok(endpoint) => endpoint,
+        Err(e) => return report(format!("Could not open the relay socket: {e}")),
+    };
+    endpoint.set_default_client_config(config);
+
+    // Only the first failure in a row is reported, it keeps retrying quietly.
+    let mut failing = false;
+    loop {
+        if let Err(e) = tunnel(&endpoint, &relay, &token, &local, &mut failing).await {
+            if !failing {
+                report(format!("Lost the connection to the relay: {e}"));
+            }
+            failing = true;
+        }
+        tokio::time::sleep(RECONNECT_INTERVAL).await;
+    }
+}
+
+// Forwards between the local socket and the relay until the connection drops, which is the only
+// way it returns. Clears `failing` once connected.
+async fn tunnel(
+    endpoint: &Endpoint,
+    relay: &str,
+    token: &str,
+    local: &UdpSocket,
+    failing: &mut bool,
+) -> io::Result<()> {
+    let address = settings::resolve(relay)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "relay address not found"))?;
+    let connection = endpoint
+        .connect(address, SERVER_NAME)
+        .map_err(other)?
+        .await?;
+    let mut auth = connection.open_uni().await?;
+    auth.write_all(token.as_bytes()).await?;
+    auth.finish().await?;
+    *failing = false;
+
+    let mut buf = [0; 512];
+    // Where wrangler sends from, replies go back there.
+    let mut peer = None;
+    loop {
+        tokio::select! {
+            received = local.recv_from(&mut buf) => {
+                let (len, from) = received?;
+                peer = Some(from);
+                send(&connection, &buf[..len])?;
+            }
+            datagram = connection.read_datagram() => {
+                let datagram = datagram?;
+                if let Some(peer) = peer {
+                    local.send_to(&datagram, peer).await?;
+                }
+            }
+        }
+    }
+}
+
+fn send(connection: &Connection, packet: &[u8]) -> io::Result<()> {
+    connection
+        .send_datagram(Bytes::copy_from_slice(packet))
+        .map_err(other)
+}
+
+/// Runs the relay end on `listen`, forwarding every authenticated wrangler to the server of the
+/// settings. Creates the certificate and token on first use. Only returns on errors.
+#[tokio::main]
+pub async fn serve(listen: &str, settings: settings::Handler) -> io::Result<()> {
+    let listen = listen
+        .to_socket_addrs()?
+        .next()
+        .ok_or(io::ErrorKind::NotFound)?;
+    let (cert, key) = certificate()?;
+    if settings.load().relay_token.is_empty() {
+        settings.change(|ws| ws.relay_token = settings::random_token());
+    }
+    let token = settings.load().relay_token.clone();
+    let server = settings
+        .load()
+        .resolve_address()
+        .unwrap_or_else(|| settings.load().get_socket_address());
+
+    println!("Relay listening on {listen}, forwarding to the SlimeVR server at {server}.");
+    println!("Set these in the settings of wrangler on the other network:");
+    println!(
+        "  relay_address: <this PC's public address>:{}",
+        listen.port()
+    );
+    println!("  relay_fingerprint: {}", fingerprint(&cert));
+    println!("  relay_token: {token}");
+
+    let mut crypto = rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(vec![Certificate(cert)], PrivateKey(key))
+        .map_err(other)?;
+    crypto.alpn_protocols = vec![ALPN.to_vec()];
+    let mut config = quinn::ServerConfig::with_crypto(Arc::new(crypto));
+    config.transport = transport();
+    let endpoint = Endpoint::server(config, listen)?;
+    while let Some(connecting) = endpoint.accept().await {
+        let token = token.clone();
+        tokio::spawn(async move {
+            let Ok(connection) = connecting.await else {
+                return;
+            };
+            let remote = connection.remote_address();
+            match forward(connection, &token, server).await {
+                Ok(()) => println!("{remote} disconnected."),
+                Err(e) => println!("{remote} disconnected: {e}"),
+            }
+        });
+    }
+    Ok(())
+}
+
+// One wrangler on the relay. It gets a socket of its own, so the server tells them apart.
+async fn forward(connection: Connection, token: &str, server: SocketAddr) -> io::Result<()> {
+    let mut auth = connection.accept_uni().await?;
+    let received = auth.read_to_end(MAX_TOKEN_SIZE).await.map_err(other)?;
+    if ring::constant_time::verify_slices_are_equal(&received, token.as_bytes()).is_err() {
+        connection.close(1u32.into(), b"wrong token");
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "wrong token",
+        ));
+    }
+    println!("{} connected.", connection.remote_address());
+
+    let socket = UdpSocket::bind(SocketAddr::from(([0, 0, 0, 0], 0))).await?;
+    socket.connect(server).await?;
+    let mut buf = [0; 512];
+    loop {
+        tokio::select! {
+            datagram = connection.read_datagram() => {
+                socket.send(&datagram?).await?;
+            }
+            received = socket.recv(&mut buf) => {
+                send(&connection, &buf[..received?])?;
+            }
+        }
+    }
+}
+
+fn cert_dir() -> Option<PathBuf> {
+    ProjectDirs::from("", "", "SlimeVR Wrangler").map(|pd| pd.config_dir().join("relay"))
+}
+
+// The certificate and key of the relay, made on first use and kept so the fingerprint stays.
+fn certificate() -> io::Result<(Vec<u8>, Vec<u8>)> {
+    let dir = cert_dir().ok_or(io::ErrorKind::NotFound)?;
+    let (cert_file, key_file) = (dir.join("cert.der"), dir.join("key.der"));
+    if let (Ok(cert), Ok(key)) = (fs::read(&cert_file), fs::read(&key_file)) {
+        return Ok((cert, key));
+    }
+    let generated = rcgen::generate_simple_self_signed(vec![SERVER_NAME.into()]).map_err(other)?;
+    let cert = generated.serialize_der().map_err(other)?;
+    let key = generated.serialize_private_key_der();
+    fs::create_dir_all(&dir)?;
+    fs::write(&cert_file, &cert)?;
+    fs::write(&key_file, &key)?;
+    Ok((cert, key))
+}
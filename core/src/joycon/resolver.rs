@@ -0,0 +1,46 @@
+//! Keeps the server address looked up, so a hostname keeps working when the IP of the server PC
+//! changes, e.g. through DHCP.
+
+use std::{sync::Arc, thread, time::Duration};
+
+use super::queue::BoundedSender;
+use super::watchdog::{lock, Durable};
+use super::ErrorReport;
+use crate::settings;
+
+const RESOLVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Looks up the server address of the settings every [`RESOLVE_INTERVAL`] and hands it to the
+/// communication thread. A failed lookup keeps the last address. Never returns.
+pub(crate) fn watch(
+    durable: Arc<Durable>,
+    settings: settings::Handler,
+    errors_tx: BoundedSender<ErrorReport>,
+) {
+    // Only the first failure in a row is reported, the lookup is retried quietly.
+    let mut failing = false;
+    loop {
+        let settings = settings.load();
+        match settings.resolve_address() {
+            Some(address) => {
+                failing = false;
+                *lock(&durable.server_address) = address;
+            }
+            None if !failing && settings.address_is_valid() => {
+                failing = true;
+                errors_tx
+                    .send(
+                        ErrorReport::new(
+                            "Server",
+                            format!("Could not look up the server \"{}\"", settings.address),
+                        )
+                        .suggest("Check that the server PC is on the network and its name."),
+                    )
+                    .ok();
+            }
+            None => {}
+        }
+        drop(settings);
+        thread::sleep(RESOLVE_INTERVAL);
+    }
+}
@@ -0,0 +1,86 @@
+use nalgebra::{UnitQuaternion, Vector3};
+
+/// A per-device Rhai script run on every sample before it is sent to the server.
+///
+/// The script sees `rotation` as `[w, i, j, k]`, `accel` as `[x, y, z]` in G and `serial` as the
+/// device's serial number, and may change `rotation` and `accel`. `state` is a map kept between
+/// runs, for things like smoothing.
+#[cfg(feature = "scripting")]
+pub struct Script {
+    engine: rhai::Engine,
+    ast: rhai::AST,
+    scope: rhai::Scope<'static>,
+}
+#[cfg(feature = "scripting")]
+impl Script {
+    pub fn load(path: &str, serial_number: &str) -> Result<Self, String> {
+        let engine = rhai::Engine::new();
+        let ast = engine
+            .compile_file(path.into())
+            .map_err(|e| e.to_string())?;
+        let mut scope = rhai::Scope::new();
+        scope.push_constant("serial", serial_number.to_owned());
+        scope.push("state", rhai::Map::new());
+        Ok(Self { engine, ast, scope })
+    }
+    pub fn transform(
+        &mut self,
+        rotation: UnitQuaternion<f64>,
+        accel: Vector3<f64>,
+    ) -> Result<(UnitQuaternion<f64>, Vector3<f64>), String> {
+        let q = rotation.quaternion();
+        self.scope
+            .set_value("rotation", to_array(&[q.w, q.i, q.j, q.k]));
+        self.scope.set_value("accel", to_array(accel.as_slice()));
+        self.engine
+            .run_ast_with_scope(&mut self.scope, &self.ast)
+            .map_err(|e| e.to_string())?;
+
+        let [w, i, j, k] = self.read("rotation")?;
+        let rotation = nalgebra::Quaternion::new(w, i, j, k);
+        if rotation.norm() < 1e-6 {
+            return Err("rotation has to be a non-zero quaternion".into());
+        }
+        let [x, y, z] = self.read("accel")?;
+        Ok((
+            UnitQuaternion::from_quaternion(rotation),
+            Vector3::new(x, y, z),
+        ))
+    }
+    fn read<const N: usize>(&self, name: &str) -> Result<[f64; N], String> {
+        let error = || format!("{name} has to be an array of {N} numbers");
+        let array = self
+            .scope
+            .get_value::<rhai::Array>(name)
+            .filter(|array| array.len() == N)
+            .ok_or_else(error)?;
+        let mut values = [0.0; N];
+        for (value, item) in values.iter_mut().zip(array) {
+            *value = item
+                .as_float()
+                .or_else(|_| item.as_int().map(|i| i as f64))
+                .map_err(|_| error())?;
+        }
+        Ok(values)
+    }
+}
+#[cfg(feature = "scripting")]
+fn to_array(values: &[f64]) -> rhai::Array {
+    values.iter().map(|&v| v.into()).collect()
+}
+
+#[cfg(not(feature = "scripting"))]
+pub struct Script;
+#[cfg(not(feature = "scripting"))]
+impl Script {
+    pub fn load(_path: &str, _serial_number: &str) -> Result<Self, String> {
+        Err("this build of wrangler doesn't support scripts".into())
+    }
+    pub fn transform(
+        &mut self,
+        rotation: UnitQuaternion<f64>,
+        accel: Vector3<f64>,
+    ) -> Result<(UnitQuaternion<f64>, Vector3<f64>), String> {
+        Ok((rotation, accel))
+    }
+}
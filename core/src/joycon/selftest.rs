@@ -0,0 +1,432 @@
+//! Quick checks of the setup on launch, so problems show up before anything is strapped on.
+//! [`diagnose_network`] digs deeper when the server doesn't answer.
+
+use std::{
+    collections::BTreeSet,
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket},
+    thread,
+    time::{Duration, Instant},
+};
+
+use protocol::{
+    deku::{DekuContainerRead, DekuContainerWrite},
+    PacketType,
+};
+
+use super::communication::{handshake_packet, LOCAL_PORT};
+use crate::settings;
+
+// A closed port is refused within milliseconds. Silence can be a server that doesn't answer a
+// ping from an unknown tracker, it is asked with a handshake then.
+const PROBE_TIMEOUT: Duration = Duration::from_millis(500);
+const PROBE_INTERVAL: Duration = Duration::from_millis(250);
+// The test handshake of the network diagnosis is repeated this often, for this long.
+const HANDSHAKE_RESEND: Duration = Duration::from_millis(500);
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Outcome of one check of the [`self_test`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub passed: bool,
+    /// Not passed, but not known to have failed either, like a server that stays silent.
+    pub warning: bool,
+    /// What was found, also when the check passed.
+    pub detail: String,
+    /// What the user can do about a failed check.
+    pub fix: Option<String>,
+}
+impl CheckResult {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            passed: true,
+            warning: false,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+    fn fail(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            passed: false,
+            warning: false,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+    fn warn(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            warning: true,
+            ..Self::fail(name, detail, fix)
+        }
+    }
+    /// "passed", "warning" or "failed".
+    pub fn outcome(&self) -> &'static str {
+        match (self.passed, self.warning) {
+            (true, _) => "passed",
+            (false, true) => "warning",
+            (false, false) => "failed",
+        }
+    }
+}
+
+/// Checks controller access, the local socket and the Bluetooth adapter. Run it before the
+/// [`Wrapper`](super::Wrapper) starts, the HID check needs the controllers to itself. See
+/// [`probe_server`] for the server.
+pub fn self_test() -> Vec<CheckResult> {
+    let mut checks = vec![hid_access(), udp_socket()];
+    checks.extend(bluetooth_adapter());
+    checks
+}
+
+#[cfg(target_os = "linux")]
+fn hid_access() -> CheckResult {
+    const NAME: &str = "Controller access";
+    let in_input = users::group_access_list()
+        .unwrap_or_default()
+        .iter()
+        .any(|group| group.name() == "input");
+    if !in_input {
+        return CheckResult::fail(
+            NAME,
+            "Your user is not in the \"input\" group",
+            "Run \"sudo usermod -aG input $USER\", then log out and back in.",
+        );
+    }
+    if !std::path::Path::new("/sys/bus/hid/drivers/nintendo").exists() {
+        return CheckResult::fail(
+            NAME,
+            "The hid-nintendo driver is not loaded",
+            "Run \"sudo modprobe hid_nintendo\", it needs Linux 5.16 or newer.",
+        );
+    }
+    CheckResult::pass(NAME, "Controllers can be read")
+}
+
+#[cfg(not(target_os = "linux"))]
+fn hid_access() -> CheckResult {
+    const NAME: &str = "Controller access";
+    match joycon_rs::prelude::HidApi::new() {
+        Ok(_) => CheckResult::pass(NAME, "Controllers can be read"),
+        Err(e) => CheckResult::fail(
+            NAME,
+            format!("Could not access HID devices: {e}"),
+            if cfg!(target_os = "macos") {
+                "Allow wrangler under Input Monitoring in the Privacy & Security settings."
+            } else {
+                "Restart wrangler, and your computer if that doesn't help."
+            },
+        ),
+    }
+}
+
+fn udp_socket() -> CheckResult {
+    const NAME: &str = "Network socket";
+    let fix = "Allow wrangler through your firewall or security software.";
+    match UdpSocket::bind(SocketAddr::from(([0, 0, 0, 0], LOCAL_PORT))) {
+        Ok(_) => CheckResult::pass(NAME, format!("Port {LOCAL_PORT} is free")),
+        Err(e) if e.kind() == io::ErrorKind::AddrInUse => {
+            match UdpSocket::bind(SocketAddr::from(([0, 0, 0, 0], 0))) {
+                Ok(_) => CheckResult::pass(
+                    NAME,
+                    format!("Port {LOCAL_PORT} is taken, is wrangler already running?"),
+                ),
+                Err(e) => CheckResult::fail(NAME, format!("Could not open a socket: {e}"), fix),
+            }
+        }
+        Err(e) => CheckResult::fail(NAME, format!("Could not open a socket: {e}"), fix),
+    }
+}
+
+const BLUETOOTH: &str = "Bluetooth adapter";
+const BLUETOOTH_FIX: &str = "Turn Bluetooth on, or plug in a Bluetooth adapter.";
+
+#[cfg(target_os = "linux")]
+fn bluetooth_adapter() -> Option<CheckResult> {
+    let adapters: Vec<_> = std::fs::read_dir("/sys/class/bluetooth")
+        .map(|dir| {
+            dir.filter_map(|entry| entry.ok())
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .filter(|name| !name.contains(':'))
+                .collect()
+        })
+        .unwrap_or_default();
+    Some(if adapters.is_empty() {
+        CheckResult::fail(BLUETOOTH, "No Bluetooth adapter found", BLUETOOTH_FIX)
+    } else {
+        CheckResult::pass(BLUETOOTH, format!("Found {}", adapters.join(", ")))
+    })
+}
+
+#[cfg(target_os = "windows")]
+fn bluetooth_adapter() -> Option<CheckResult> {
+    use windows_sys::Win32::{
+        Devices::Bluetooth::{
+            BluetoothFindFirstRadio, BluetoothFindRadioClose, BLUETOOTH_FIND_RADIO_PARAMS,
+        },
+        Foundation::CloseHandle,
+    };
+    let params = BLUETOOTH_FIND_RADIO_PARAMS {
+        dwSize: std::mem::size_of::<BLUETOOTH_FIND_RADIO_PARAMS>() as u32,
+    };
+    let mut radio = 0;
+    // The handles are only checked and closed again.
+    let found = unsafe {
+        let find = BluetoothFindFirstRadio(&params, &mut radio);
+        if find != 0 {
+            CloseHandle(radio);
+            BluetoothFindRadioClose(find);
+        }
+        find != 0
+    };
+    Some(if found {
+        CheckResult::pass(BLUETOOTH, "Found an adapter")
+    } else {
+        CheckResult::fail(BLUETOOTH, "No Bluetooth adapter found", BLUETOOTH_FIX)
+    })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+fn bluetooth_adapter() -> Option<CheckResult> {
+    None
+}
+
+/// Checks that the server at `address` answers, retrying for up to `within`, for a server that is
+/// still starting. Blocks for at least half a second. Like for [`diagnose_network`],
+/// `mac_address` should differ from the one of the trackers.
+pub fn probe_server(address: &str, mac_address: [u8; 6], within: Duration) -> CheckResult {
+    const NAME: &str = "SlimeVR server";
+    let Some(address) = settings::resolve(address) else {
+        return CheckResult::fail(
+            NAME,
+            format!("\"{address}\" is not a valid address or could not be looked up"),
+            "Enter the address in the settings as ip:port or hostname:port, like 127.0.0.1:6969.",
+        );
+    };
+    let deadline = Instant::now() + within;
+    loop {
+        match probe_once(address) {
+            Ok(true) => return CheckResult::pass(NAME, format!("{address} answered")),
+            Ok(false) => {
+                return match send_test_handshake(address, mac_address) {
+                    Ok(true) => CheckResult::pass(NAME, format!("{address} answered")),
+                    _ => CheckResult::warn(
+                        NAME,
+                        format!("No answer from {address}, nothing refused the packets either"),
+                        "Check that the SlimeVR server is running and uses this address.",
+                    ),
+                };
+            }
+            Err(e) if Instant::now() >= deadline => {
+                return CheckResult::fail(
+                    NAME,
+                    format!("Could not reach {address}: {e}"),
+                    "Start the SlimeVR server, and check the address in the settings.",
+                )
+            }
+            Err(_) => thread::sleep(PROBE_INTERVAL),
+        }
+    }
+}
+
+// Any local address, of the same kind as `address`.
+fn unspecified(address: SocketAddr) -> SocketAddr {
+    if address.is_ipv4() {
+        SocketAddr::from(([0, 0, 0, 0], 0))
+    } else {
+        SocketAddr::from(([0u16; 8], 0))
+    }
+}
+
+// Whether the server answered, refusals are errors.
+fn probe_once(address: SocketAddr) -> io::Result<bool> {
+    let socket = UdpSocket::bind(unspecified(address))?;
+    socket.connect(address)?;
+    socket.set_read_timeout(Some(PROBE_TIMEOUT))?;
+    let ping = PacketType::Ping { id: 0 }.to_bytes().unwrap_or_default();
+    socket.send(&ping)?;
+    match socket.recv(&mut [0; 64]) {
+        Ok(_) => Ok(true),
+        Err(e)
+            if matches!(
+                e.kind(),
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+            ) =>
+        {
+            Ok(false)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Looks for why the server at `address` doesn't answer: whether the address can be looked up,
+/// whether it is in the network of this PC, and how a test handshake fares. Blocks for up to a
+/// few seconds. The handshake uses `mac_address`, which should differ from the one of the
+/// trackers, the server would move them to the test socket otherwise.
+pub fn diagnose_network(address: &str, mac_address: [u8; 6]) -> Vec<CheckResult> {
+    const LOOKUP: &str = "Server address";
+    let Some(server) = settings::resolve(address) else {
+        return vec![CheckResult::fail(
+            LOOKUP,
+            format!("\"{address}\" is not a valid address or could not be looked up"),
+            "Enter the address in the settings as ip:port or hostname:port, like 127.0.0.1:6969.",
+        )];
+    };
+    let found = if address == server.to_string() {
+        address.to_owned()
+    } else {
+        format!("{address} is {server}")
+    };
+    let mut checks = vec![CheckResult::pass(LOOKUP, found)];
+    match local_ip(server) {
+        Ok(local) => checks.push(network(server.ip(), local)),
+        Err(e) => {
+            checks.push(CheckResult::fail(
+                NETWORK,
+                format!("No way to reach {server}: {e}"),
+                "Connect this PC to the network of the server.",
+            ));
+            return checks;
+        }
+    }
+    checks.push(test_handshake(server, mac_address));
+    checks
+}
+
+const NETWORK: &str = "Network";
+
+// The address of this PC in the network the packets to `server` go out on.
+fn local_ip(server: SocketAddr) -> io::Result<IpAddr> {
+    let socket = UdpSocket::bind(unspecified(server))?;
+    socket.connect(server)?;
+    Ok(socket.local_addr()?.ip())
+}
+
+// Home routers hand out 192.168.x.0/24 with one x, a server with another x is behind another one.
+// Other private ranges are split up too differently to tell.
+fn network(server: IpAddr, local: IpAddr) -> CheckResult {
+    if server.is_loopback() {
+        return CheckResult::pass(NETWORK, "The server is on this PC");
+    }
+    let home = |ip: &[u8; 4]| ip[..2] == [192, 168];
+    if let (IpAddr::V4(server), IpAddr::V4(local)) = (server, local) {
+        let (server, local) = (server.octets(), local.octets());
+        if home(&server) && home(&local) && server[2] != local[2] {
+            return CheckResult::fail(
+                NETWORK,
+                format!(
+                    "The server {} is not in the network of this PC, {}",
+                    IpAddr::from(server),
+                    IpAddr::from(local)
+                ),
+                "Check the IP address of the server PC, it may have changed, and that both PCs are on the same router or Wi-Fi.",
+            );
+        }
+    }
+    CheckResult::pass(NETWORK, format!("This PC is {local}"))
+}
+
+fn test_handshake(server: SocketAddr, mac_address: [u8; 6]) -> CheckResult {
+    const NAME: &str = "Test handshake";
+    match send_test_handshake(server, mac_address) {
+        Ok(true) => CheckResult::pass(NAME, format!("{server} answered")),
+        Ok(false) if server.ip().is_loopback() => CheckResult::fail(
+            NAME,
+            "The server on this PC doesn't answer",
+            "Restart the SlimeVR server, and check that it uses the port in the settings of wrangler.",
+        ),
+        Ok(false) => CheckResult::fail(
+            NAME,
+            format!("No answer from {server}"),
+            format!(
+                "Check that the SlimeVR server is running. Allow UDP port {} through the firewall of the server PC, on Windows by setting its network to private. Also allow wrangler through the firewall of this PC.",
+                server.port()
+            ),
+        ),
+        Err(e)
+            if matches!(
+                e.kind(),
+                io::ErrorKind::ConnectionRefused | io::ErrorKind::ConnectionReset
+            ) =>
+        {
+            CheckResult::fail(
+                NAME,
+                format!("Nothing listens on {server}"),
+                "Start the SlimeVR server, and check the port in the settings.",
+            )
+        }
+        Err(e) => CheckResult::fail(
+            NAME,
+            format!("Could not send to {server}: {e}"),
+            "Allow wrangler through the firewall of this PC.",
+        ),
+    }
+}
+
+/// Looks for SlimeVR servers on the port they use by default, by broadcasting a handshake the way
+/// firmware trackers find one, and returns the addresses that answered within `within`. Like for
+/// [`diagnose_network`], `mac_address` should differ from the one of the trackers.
+pub fn discover_servers(mac_address: [u8; 6], within: Duration) -> io::Result<Vec<SocketAddr>> {
+    let socket = UdpSocket::bind(SocketAddr::from(([0, 0, 0, 0], 0)))?;
+    socket.set_broadcast(true)?;
+    socket.set_read_timeout(Some(PROBE_INTERVAL))?;
+    let handshake = handshake_packet(mac_address).to_bytes().unwrap_or_default();
+    // Broadcasts don't come back to a server on this PC everywhere, it is asked directly.
+    let targets = [Ipv4Addr::BROADCAST, Ipv4Addr::LOCALHOST]
+        .map(|ip| SocketAddr::from((ip, settings::DEFAULT_PORT)));
+    let mut found = BTreeSet::new();
+    let mut last_sent: Option<Instant> = None;
+    let deadline = Instant::now() + within;
+    let mut buf = [0; 64];
+    while Instant::now() < deadline {
+        if last_sent.map_or(true, |sent| sent.elapsed() >= HANDSHAKE_RESEND) {
+            last_sent = Some(Instant::now());
+            for target in targets {
+                // A network without broadcasts still has the server on this PC.
+                socket.send_to(&handshake, target).ok();
+            }
+        }
+        match socket.recv_from(&mut buf) {
+            Ok((len, from)) => {
+                if let Ok((_, PacketType::HandshakeResponse)) =
+                    PacketType::from_bytes((&buf[..len], 0))
+                {
+                    found.insert(from);
+                }
+            }
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock
+                        | io::ErrorKind::TimedOut
+                        | io::ErrorKind::ConnectionReset
+                ) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(found.into_iter().collect())
+}
+
+// Whether the server answered, refusals are errors.
+fn send_test_handshake(server: SocketAddr, mac_address: [u8; 6]) -> io::Result<bool> {
+    let socket = UdpSocket::bind(unspecified(server))?;
+    socket.connect(server)?;
+    socket.set_read_timeout(Some(HANDSHAKE_RESEND))?;
+    let handshake = handshake_packet(mac_address).to_bytes().unwrap_or_default();
+    let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+    while Instant::now() < deadline {
+        socket.send(&handshake)?;
+        match socket.recv(&mut [0; 64]) {
+            Ok(_) => return Ok(true),
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(false)
+}
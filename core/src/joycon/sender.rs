@@ -0,0 +1,238 @@
+//! Sends the tracker packets of each device from a thread of its own, so a send that stalls or
+//! fails for one tracker doesn't add latency to the others.
+//!
+//! With output smoothing, the thread is also a small jitter buffer: Bluetooth delivers reports in
+//! bursts, a [`Timeline`] spaces their rotations out at the device's cadence and the thread holds
+//! each packet until it is due.
+
+use std::{
+    cell::RefCell,
+    io,
+    net::{SocketAddr, UdpSocket},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use protocol::{Bundle, PacketBuffer, PacketType};
+
+use super::profile::{self, Stage};
+use super::queue::{bounded, count_drop, BoundedSender, Overflow, Queue, PACKET_QUEUE_SIZE};
+use super::watchdog::lock;
+
+// Windows answers a packet to a port nobody listens on by failing a later call on the socket with
+// a connection reset, whichever thread makes it. Set until the communication thread picks it up.
+static CONNECTION_RESET: AtomicBool = AtomicBool::new(false);
+
+/// Whether a send or receive failed with a connection reset since the last call.
+pub(crate) fn take_connection_reset() -> bool {
+    CONNECTION_RESET.swap(false, Ordering::Relaxed)
+}
+
+/// A connection reset only says an earlier packet got nowhere, it is noted for
+/// [`take_connection_reset`] and otherwise counts as nothing sent.
+fn tolerate_reset(result: io::Result<usize>) -> io::Result<usize> {
+    match result {
+        Err(e) if e.kind() == io::ErrorKind::ConnectionReset => {
+            CONNECTION_RESET.store(true, Ordering::Relaxed);
+            Ok(0)
+        }
+        result => result,
+    }
+}
+
+// Any other failed send, like with the network down. The last one is kept until the
+// communication thread picks it up.
+static SEND_ERROR: Mutex<Option<String>> = Mutex::new(None);
+
+/// Why a send failed since the last call, if one did.
+pub(crate) fn take_send_error() -> Option<String> {
+    lock(&SEND_ERROR).take()
+}
+
+/// Bytes sent, or 0 for a send that failed, which is noted for [`take_send_error`]. The next
+/// packet is tried anyway. A full send buffer only loses the packet, like one dropped on the way.
+fn note_failure(result: io::Result<usize>) -> usize {
+    match tolerate_reset(result) {
+        Ok(size) => size,
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+            count_drop(Queue::Socket);
+            0
+        }
+        Err(e) => {
+            *lock(&SEND_ERROR) = Some(e.to_string());
+            0
+        }
+    }
+}
+
+/// A socket to the server and the ids of the packets sent on it. The server tells connections
+/// apart by their port and checks the order of each on its own.
+pub(crate) struct Connection {
+    socket: UdpSocket,
+    // Id of the next packet. It is held while the packet is sent, so a packet numbered on one
+    // thread can't be overtaken by a later one from another thread, which the server drops.
+    next_id: Mutex<u64>,
+}
+impl Connection {
+    pub(crate) fn new(socket: UdpSocket) -> Self {
+        Self {
+            socket,
+            next_id: Mutex::new(1),
+        }
+    }
+    pub(crate) fn socket(&self) -> &UdpSocket {
+        &self.socket
+    }
+}
+
+/// Sends already serialized bytes as they are, like the answer to a ping.
+pub(crate) fn send_bytes(connection: &Connection, bytes: &[u8], address: SocketAddr) -> usize {
+    note_failure(connection.socket.send_to(bytes, address))
+}
+
+/// Sends the packets in `bundle` as one, numbered like a packet, and empties it.
+pub(crate) fn send_bundle(
+    connection: &Connection,
+    bundle: &mut Bundle,
+    address: SocketAddr,
+) -> usize {
+    let mut next_id = lock(&connection.next_id);
+    let bytes = bundle.take(*next_id);
+    *next_id += 1;
+    note_failure(connection.socket.send_to(&bytes, address))
+}
+
+// How long packets are held back at most to even out the bursts. Reports that come later than
+// this after their turn start a new timeline.
+const JITTER_DELAY: Duration = Duration::from_millis(10);
+
+thread_local! {
+    static PACKET_BUFFER: RefCell<PacketBuffer> = RefCell::new(PacketBuffer::default());
+}
+
+// Serializes into a buffer reused for every packet, this runs a few hundred times a second. The
+// packet gets the next id of the connection on the way.
+// Returns the size of the packet, 0 for a packet that doesn't serialize, which is dropped.
+fn try_send_packet(
+    connection: &Connection,
+    packet: &PacketType,
+    address: SocketAddr,
+) -> io::Result<usize> {
+    PACKET_BUFFER.with(|buffer| {
+        let mut buffer = buffer.borrow_mut();
+        let mut next_id = lock(&connection.next_id);
+        let bytes = {
+            let _measure = profile::measure(Stage::Serialize);
+            match packet.write_numbered(&mut buffer, *next_id) {
+                Ok(bytes) => bytes,
+                Err(_) => return Ok(0),
+            }
+        };
+        *next_id += 1;
+        let _measure = profile::measure(Stage::Send);
+        connection.socket.send_to(bytes, address)?;
+        Ok(bytes.len())
+    })
+}
+
+/// Like [`send_packet`], for a packet to another address than the server's. It failing says
+/// nothing about the connection to the server, so it isn't noted.
+pub(crate) fn send_packet_elsewhere(
+    connection: &Connection,
+    packet: &PacketType,
+    address: SocketAddr,
+) -> usize {
+    tolerate_reset(try_send_packet(connection, packet, address)).unwrap_or(0)
+}
+
+/// Sends a packet on the communication thread. Returns the size of the packet, 0 if it wasn't
+/// sent.
+pub(crate) fn send_packet(
+    connection: &Connection,
+    packet: &PacketType,
+    address: SocketAddr,
+) -> usize {
+    note_failure(try_send_packet(connection, packet, address))
+}
+
+/// Packet for a sender thread, with when it is due if it is held back.
+type Outgoing = (PacketType, SocketAddr, Option<Instant>);
+
+/// The queue to the sender thread of one device. The thread exits once this is dropped.
+pub(crate) struct PacketSender {
+    tx: BoundedSender<Outgoing>,
+    sent: Arc<AtomicUsize>,
+}
+impl PacketSender {
+    /// Starts the sender thread of `serial_number`, on the same connection so the server still
+    /// sees every packet coming from one port, numbered in the order they were sent.
+    pub(crate) fn spawn(connection: &Arc<Connection>, serial_number: &str) -> io::Result<Self> {
+        let connection = connection.clone();
+        let (tx, rx) = bounded(PACKET_QUEUE_SIZE, Overflow::Drop(Queue::Packets));
+        let sent = Arc::new(AtomicUsize::new(0));
+        let name = format!("Sender {serial_number}");
+        let counter = sent.clone();
+        thread::Builder::new().spawn(move || run(connection, rx, counter, name))?;
+        Ok(Self { tx, sent })
+    }
+    pub(crate) fn send(&self, packet: PacketType, address: SocketAddr) {
+        self.tx.send((packet, address, None)).ok();
+    }
+    /// Sends the packet once `due`, after the packets queued before it.
+    pub(crate) fn send_at(&self, packet: PacketType, address: SocketAddr, due: Instant) {
+        self.tx.send((packet, address, Some(due))).ok();
+    }
+    /// Bytes sent since the last call.
+    pub(crate) fn take_sent(&self) -> usize {
+        self.sent.swap(0, Ordering::Relaxed)
+    }
+}
+
+fn run(
+    connection: Arc<Connection>,
+    rx: mpsc::Receiver<Outgoing>,
+    sent: Arc<AtomicUsize>,
+    name: String,
+) {
+    profile::name_thread(name);
+    // Sleeps instead of spinning, like the communication loop, a busy core per tracker would
+    // cost more than the jitter a held packet evens out.
+    let sleeper =
+        spin_sleep::SpinSleeper::new(1).with_spin_strategy(spin_sleep::SpinStrategy::YieldThread);
+    for (packet, address, due) in rx {
+        if let Some(due) = due {
+            sleeper.sleep(due.saturating_duration_since(Instant::now()));
+        }
+        let size = note_failure(try_send_packet(&connection, &packet, address));
+        sent.fetch_add(size, Ordering::Relaxed);
+    }
+}
+
+/// Even send times for the reports of one device.
+#[derive(Debug, Default)]
+pub(crate) struct Timeline {
+    last: Option<Instant>,
+}
+impl Timeline {
+    /// When to send a report that arrived `now`, one `interval` after the previous one. Without a
+    /// known cadence, or for a report too far off it, the timeline starts over [`JITTER_DELAY`]
+    /// after `now`.
+    pub(crate) fn schedule(&mut self, now: Instant, interval: Option<Duration>) -> Instant {
+        let fallback = now + JITTER_DELAY;
+        let due = match (self.last, interval) {
+            (Some(last), Some(interval)) => last + interval,
+            _ => fallback,
+        };
+        let due = if due < now || due > fallback + JITTER_DELAY {
+            fallback
+        } else {
+            due
+        };
+        self.last = Some(due);
+        due
+    }
+}
@@ -0,0 +1,217 @@
+//! Statistics over one run of wrangler, for comparing Bluetooth adapters and mountings.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+use serde_json::json;
+
+use super::communication::{Battery, DeviceStatus, Status};
+
+// The report rate is over the last second, it is not counted until it built up after connecting.
+const RATE_SETTLE_TIME: Duration = Duration::from_secs(2);
+// The controllers report the battery in four steps from full to empty. A drop soon after the
+// start says little about the rate, the level may have been about to drop already.
+const BATTERY_STEP_PERCENT: f64 = 25.0;
+const MIN_BATTERY_TIME: Duration = Duration::from_secs(30 * 60);
+
+/// What happened to one device during the session.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DeviceSession {
+    pub serial_number: String,
+    /// Time connected, in seconds.
+    pub connected_secs: u64,
+    /// Average and lowest reports per second while connected and awake.
+    pub average_rate: Option<f64>,
+    pub min_rate: Option<usize>,
+    pub disconnects: usize,
+    pub reconnects: usize,
+    /// Roughly how much battery is used per hour, once a level dropped after a while without
+    /// charging.
+    pub battery_percent_per_hour: Option<f64>,
+}
+impl Display for DeviceSession {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "connected {}",
+            format_duration(Duration::from_secs(self.connected_secs))
+        )?;
+        if let (Some(average), Some(min)) = (self.average_rate, self.min_rate) {
+            write!(f, ", {average:.1} reports/s on average and at least {min}")?;
+        }
+        write!(
+            f,
+            ", {} disconnects, {} reconnects",
+            self.disconnects, self.reconnects
+        )?;
+        if let Some(drain) = self.battery_percent_per_hour {
+            write!(f, ", battery about {drain:.0}%/h")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct Tracked {
+    last_update: Instant,
+    connected: Duration,
+    connected_since: Option<Instant>,
+    rate_sum: f64,
+    rate_samples: usize,
+    min_rate: Option<usize>,
+    disconnects: usize,
+    reconnects: usize,
+    /// Level and time since it last went up, and the latest drop after that.
+    battery_start: (Instant, Battery),
+    battery_drop: Option<(Instant, Battery)>,
+}
+impl Tracked {
+    fn new(now: Instant, status: &Status) -> Self {
+        Self {
+            last_update: now,
+            connected: Duration::ZERO,
+            connected_since: (status.status != DeviceStatus::Disconnected).then_some(now),
+            rate_sum: 0.0,
+            rate_samples: 0,
+            min_rate: None,
+            disconnects: 0,
+            reconnects: 0,
+            battery_start: (now, status.battery),
+            battery_drop: None,
+        }
+    }
+    fn update(&mut self, now: Instant, status: &Status) {
+        let connected = status.status != DeviceStatus::Disconnected;
+        match (self.connected_since, connected) {
+            (Some(_), true) => self.connected += now - self.last_update,
+            (Some(_), false) => {
+                self.connected_since = None;
+                self.disconnects += 1;
+            }
+            (None, true) => {
+                self.connected_since = Some(now);
+                self.reconnects += 1;
+            }
+            (None, false) => {}
+        }
+        self.last_update = now;
+        let settled = self
+            .connected_since
+            .map_or(false, |since| now - since >= RATE_SETTLE_TIME);
+        if settled && status.status != DeviceStatus::Asleep {
+            self.rate_sum += status.rate as f64;
+            self.rate_samples += 1;
+            self.min_rate = Some(
+                self.min_rate
+                    .map_or(status.rate, |min| min.min(status.rate)),
+            );
+        }
+
+        let level = self.battery_drop.unwrap_or(self.battery_start).1;
+        if status.battery > level {
+            // Charging, the drain is measured again from here.
+            self.battery_start = (now, status.battery);
+            self.battery_drop = None;
+        } else if status.battery < level {
+            self.battery_drop = Some((now, status.battery));
+        }
+    }
+    fn summary(&self, serial_number: &str) -> DeviceSession {
+        let (start, start_level) = self.battery_start;
+        let battery_percent_per_hour = self
+            .battery_drop
+            .filter(|(time, _)| *time - start >= MIN_BATTERY_TIME)
+            .map(|(time, level)| {
+                let steps = start_level as usize - level as usize;
+                steps as f64 * BATTERY_STEP_PERCENT / (time - start).as_secs_f64() * 3600.0
+            });
+        DeviceSession {
+            serial_number: serial_number.to_owned(),
+            connected_secs: self.connected.as_secs(),
+            average_rate: (self.rate_samples > 0).then(|| self.rate_sum / self.rate_samples as f64),
+            min_rate: self.min_rate,
+            disconnects: self.disconnects,
+            reconnects: self.reconnects,
+            battery_percent_per_hour,
+        }
+    }
+}
+
+/// Collects the [`DeviceSession`] of every device from the statuses of the session.
+#[derive(Debug)]
+pub struct SessionStats {
+    start: Instant,
+    devices: BTreeMap<String, Tracked>,
+}
+impl Default for SessionStats {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+            devices: BTreeMap::new(),
+        }
+    }
+}
+impl SessionStats {
+    /// Takes in the latest statuses, as often as they arrive.
+    pub fn update(&mut self, statuses: &[Status]) {
+        let now = Instant::now();
+        for status in statuses {
+            match self.devices.get_mut(&status.serial_number) {
+                Some(tracked) => tracked.update(now, status),
+                None => {
+                    self.devices
+                        .insert(status.serial_number.clone(), Tracked::new(now, status));
+                }
+            }
+        }
+    }
+    pub fn uptime(&self) -> Duration {
+        self.start.elapsed()
+    }
+    pub fn devices(&self) -> Vec<DeviceSession> {
+        self.devices
+            .iter()
+            .map(|(serial_number, tracked)| tracked.summary(serial_number))
+            .collect()
+    }
+    /// Appends the session as one line of JSON to the session log, sessions.jsonl next to the
+    /// settings.
+    pub fn write_log(&self) -> io::Result<()> {
+        let file = session_log_file().ok_or(io::ErrorKind::NotFound)?;
+        if let Some(dir) = file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let end = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let line = json!({
+            "end": end,
+            "uptime_secs": self.uptime().as_secs(),
+            "devices": self.devices(),
+        });
+        let mut log = OpenOptions::new().create(true).append(true).open(file)?;
+        writeln!(log, "{line}")
+    }
+}
+
+/// Where [`SessionStats::write_log`] writes to.
+pub fn session_log_file() -> Option<PathBuf> {
+    crate::settings::config_dir().map(|dir| dir.join("sessions.jsonl"))
+}
+
+/// Hours and minutes, or minutes and seconds for less than an hour.
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}h {}m", secs / 3600, secs / 60 % 60)
+    } else {
+        format!("{}m {}s", secs / 60, secs % 60)
+    }
+}
@@ -0,0 +1,87 @@
+#[cfg(target_os = "linux")]
+use super::linux_integration;
+use super::{
+    spawn_thread,
+    test_integration::{stress_controllers, test_controllers},
+    DeviceSender,
+};
+use crate::settings;
+
+/// Finds devices and reports their IMU data, like the Joy-Con integration does.
+///
+/// Every source runs on a thread of its own and sends [`ChannelData`](super::ChannelData) for each of its devices,
+/// in the order documented on [`ChannelInfo`](super::ChannelInfo).
+pub trait MotionSource: Send {
+    /// Shown to the user next to errors of the source itself.
+    fn name(&self) -> String;
+    /// Runs the source, usually never returns.
+    fn run(self: Box<Self>, tx: DeviceSender, settings: settings::Handler);
+}
+
+struct Joycons;
+impl MotionSource for Joycons {
+    fn name(&self) -> String {
+        "Joy-Con".into()
+    }
+    fn run(self: Box<Self>, tx: DeviceSender, settings: settings::Handler) {
+        spawn_thread(tx, settings);
+    }
+}
+
+// evdev integration
+#[cfg(target_os = "linux")]
+struct Evdev;
+#[cfg(target_os = "linux")]
+impl MotionSource for Evdev {
+    fn name(&self) -> String {
+        "evdev".into()
+    }
+    fn run(self: Box<Self>, tx: DeviceSender, settings: settings::Handler) {
+        linux_integration::spawn_thread(tx, settings);
+    }
+}
+
+struct TestControllers;
+impl MotionSource for TestControllers {
+    fn name(&self) -> String {
+        "Test".into()
+    }
+    fn run(self: Box<Self>, tx: DeviceSender, _settings: settings::Handler) {
+        test_controllers(tx);
+    }
+}
+
+struct StressControllers(usize);
+impl MotionSource for StressControllers {
+    fn name(&self) -> String {
+        "Stress test".into()
+    }
+    fn run(self: Box<Self>, tx: DeviceSender, _settings: settings::Handler) {
+        stress_controllers(tx, self.0);
+    }
+}
+
+// Fake controllers at full rate for `stress`, or `stress=<count>`.
+const STRESS_CONTROLLERS: usize = 16;
+
+/// The sources wrangler starts on its own: Joy-Cons, fake controllers when started with `test`
+/// or `stress`, and any plugins.
+pub fn default_sources() -> Vec<Box<dyn MotionSource>> {
+    let mut sources: Vec<Box<dyn MotionSource>> = Vec::new();
+    for arg in std::env::args() {
+        if arg == "test" {
+            sources.push(Box::new(TestControllers));
+        } else if arg == "stress" {
+            sources.push(Box::new(StressControllers(STRESS_CONTROLLERS)));
+        } else if let Some(count) = arg.strip_prefix("stress=") {
+            let count = count.parse().unwrap_or(STRESS_CONTROLLERS);
+            sources.push(Box::new(StressControllers(count)));
+        }
+    }
+    #[cfg(target_os = "linux")]
+    sources.push(Box::new(Evdev));
+    sources.push(Box::new(Joycons));
+    #[cfg(feature = "plugins")]
+    sources.extend(super::load_plugins());
+    sources
+}
@@ -0,0 +1,124 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::{
+    communication::{frame_queue, ChannelData, ChannelInfo, DeviceSender, FrameProducer},
+    imu::JoyconAxisData,
+    Battery, JoyconDesign, JoyconDesignType,
+};
+
+fn spawn_test(tx: DeviceSender, color: String, sn: String, z_change: f64) {
+    tx.send(ChannelData {
+        serial_number: sn.clone(),
+        info: ChannelInfo::Connected(JoyconDesign {
+            color,
+            design_type: JoyconDesignType::Left,
+        }),
+    })
+    .unwrap();
+    let (mut frames, frames_rx) = frame_queue();
+    tx.send(ChannelData {
+        serial_number: sn.clone(),
+        info: ChannelInfo::Stream(frames_rx),
+    })
+    .unwrap();
+    tx.send(ChannelData {
+        serial_number: sn.clone(),
+        info: ChannelInfo::Battery(Battery::Medium),
+    })
+    .unwrap();
+
+    loop {
+        let d = JoyconAxisData {
+            accel_x: 0.0,
+            accel_y: -1.0,
+            accel_z: 0.0,
+            gyro_x: 0.0,
+            gyro_y: 0.0,
+            gyro_z: z_change,
+        };
+        frames.push([d, d, d]);
+
+        thread::sleep(Duration::from_millis(16));
+        if d.accel_x > 1.0 {
+            break;
+        }
+    }
+}
+
+pub fn test_controllers(tx: DeviceSender) {
+    let controllers = vec![
+        ("#aacc20", "test_0", 0.05),
+        ("#aa20cc", "test_1", 0.04),
+        ("#20aacc", "test_2", 0.06),
+        ("#20ccaa", "test_3", 0.065),
+        ("#ccaa20", "test_4", 0.055),
+        ("#cc20aa", "test_5", 0.045),
+    ];
+    for c in controllers {
+        let tx_clone = tx.clone();
+        thread::spawn(move || spawn_test(tx_clone, c.0.into(), c.1.into(), c.2));
+    }
+}
+
+// Joy-Cons send a report with three frames every 15ms.
+const REPORT_INTERVAL: Duration = Duration::from_millis(15);
+
+/// `count` fake controllers reporting at the full rate of a Joy-Con, to check that wrangler keeps
+/// up with many trackers. One thread drives all of them, so the load is wrangler's own.
+pub fn stress_controllers(tx: DeviceSender, count: usize) {
+    let mut devices: Vec<(FrameProducer, f64)> = (0..count)
+        .map(|i| {
+            let sn = format!("stress_{i}");
+            // Spread around the color wheel, alternating sides, turning at different speeds.
+            let hue = i as f64 / count as f64;
+            let channel = |offset: f64| {
+                let x = ((hue + offset).fract() * 6.0 - 3.0).abs() - 1.0;
+                (x.clamp(0.0, 1.0) * 200.0) as u8 + 40
+            };
+            tx.send(ChannelData::new(
+                sn.clone(),
+                ChannelInfo::Connected(JoyconDesign {
+                    color: format!(
+                        "#{:02x}{:02x}{:02x}",
+                        channel(0.0),
+                        channel(2.0 / 3.0),
+                        channel(1.0 / 3.0)
+                    ),
+                    design_type: if i % 2 == 0 {
+                        JoyconDesignType::Left
+                    } else {
+                        JoyconDesignType::Right
+                    },
+                }),
+            ))
+            .unwrap();
+            let (frames, frames_rx) = frame_queue();
+            tx.send(ChannelData::new(sn.clone(), ChannelInfo::Stream(frames_rx)))
+                .unwrap();
+            tx.send(ChannelData::new(sn, ChannelInfo::Battery(Battery::Full)))
+                .unwrap();
+            (frames, 0.02 + 0.005 * i as f64)
+        })
+        .collect();
+
+    let mut next = Instant::now();
+    loop {
+        for (frames, z_change) in &mut devices {
+            let d = JoyconAxisData {
+                accel_x: 0.0,
+                accel_y: -1.0,
+                accel_z: 0.0,
+                gyro_x: 0.0,
+                gyro_y: 0.0,
+                gyro_z: *z_change,
+            };
+            frames.push([d, d, d]);
+        }
+        // Deadlines instead of plain sleeps, so the rate doesn't sag under load.
+        next += REPORT_INTERVAL;
+        thread::sleep(next.saturating_duration_since(Instant::now()));
+    }
+}
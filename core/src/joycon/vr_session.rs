@@ -0,0 +1,96 @@
+//! Pauses sending while no VR session runs, so controllers left connected all day don't stream
+//! and light up for nothing. A session is recognized by a process of the name in
+//! [`WranglerSettings::vr_process`](crate::settings::WranglerSettings::vr_process), `vrserver`
+//! for SteamVR.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use super::queue::BoundedSender;
+use super::Action;
+use crate::settings;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Checks for the VR process every [`CHECK_INTERVAL`], keeps `vr_idle` up to date and tells the
+/// communication thread when it changes. Never returns.
+pub(crate) fn watch(
+    vr_idle: Arc<AtomicBool>,
+    action_tx: BoundedSender<Action>,
+    settings: settings::Handler,
+) {
+    loop {
+        let process = settings.load().vr_process.clone();
+        // Where processes can't be listed, trackers keep streaming.
+        let idle = !process.is_empty() && running(&process) == Some(false);
+        if vr_idle.swap(idle, Ordering::Relaxed) != idle {
+            action_tx.send(Action::VrIdle(idle)).ok();
+        }
+        thread::sleep(CHECK_INTERVAL);
+    }
+}
+
+// Compared without case and without ".exe", so the same name works on Windows and Linux.
+fn is_named(executable: &str, name: &str) -> bool {
+    fn stem(name: &str) -> &str {
+        let cut = name.len().saturating_sub(4);
+        match name.get(cut..) {
+            Some(ext) if ext.eq_ignore_ascii_case(".exe") => &name[..cut],
+            _ => name,
+        }
+    }
+    stem(executable).eq_ignore_ascii_case(stem(name))
+}
+
+#[cfg(target_os = "linux")]
+fn running(name: &str) -> Option<bool> {
+    // The kernel cuts the names in comm to 15 bytes.
+    let name = name.get(..name.len().min(15)).unwrap_or(name);
+    let found = std::fs::read_dir("/proc")
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| std::fs::read_to_string(entry.path().join("comm")).ok())
+        .any(|comm| is_named(comm.trim_end(), name));
+    Some(found)
+}
+
+#[cfg(target_os = "windows")]
+fn running(name: &str) -> Option<bool> {
+    use windows_sys::Win32::{
+        Foundation::{CloseHandle, INVALID_HANDLE_VALUE},
+        System::Diagnostics::ToolHelp::{
+            CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W,
+            TH32CS_SNAPPROCESS,
+        },
+    };
+    // The snapshot is only walked and closed again.
+    unsafe {
+        let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0);
+        if snapshot == INVALID_HANDLE_VALUE {
+            return None;
+        }
+        let mut entry: PROCESSENTRY32W = std::mem::zeroed();
+        entry.dwSize = std::mem::size_of::<PROCESSENTRY32W>() as u32;
+        let mut found = false;
+        let mut more = Process32FirstW(snapshot, &mut entry) != 0;
+        while more && !found {
+            let exe = &entry.szExeFile;
+            let len = exe.iter().position(|&c| c == 0).unwrap_or(exe.len());
+            found = is_named(&String::from_utf16_lossy(&exe[..len]), name);
+            more = Process32NextW(snapshot, &mut entry) != 0;
+        }
+        CloseHandle(snapshot);
+        Some(found)
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+fn running(_name: &str) -> Option<bool> {
+    None
+}
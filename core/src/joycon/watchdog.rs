@@ -0,0 +1,173 @@
+//! Restarts the communication thread when it panics or stops making progress.
+//!
+//! A stuck thread can't be killed, so it is abandoned instead: the generation is bumped, which
+//! makes it return if it ever wakes up again, and a new one takes over. Everything the new thread
+//! needs to carry on lives in [`Durable`], including the devices that registered so far.
+
+use std::{
+    collections::BTreeSet,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc, Arc, Mutex, MutexGuard, PoisonError,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::{
+    calibration::CalibrationState,
+    communication::MAX_IDLE_SLEEP,
+    firmware::FirmwareVersion,
+    queue::{BoundedSender, Latest},
+    Action, ChannelData, Communication, DeviceCommand, ErrorReport, FrameConsumer, HandshakeLog,
+    JoyconDesign, ServerStatus, Status,
+};
+use crate::settings;
+
+// The loop beats at least every MAX_IDLE_SLEEP, missing this many beats in a row, a few seconds,
+// means it is stuck.
+const STALLED_BEATS: u64 = 2500;
+const STALL_TIMEOUT: Duration =
+    Duration::from_nanos(MAX_IDLE_SLEEP.as_nanos() as u64 * STALLED_BEATS);
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+// A thread that keeps failing is restarted after a wait that doubles each time, up to the
+// longest. Running this long without a restart starts over with no wait.
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(60);
+const STABLE_RUN: Duration = Duration::from_secs(60);
+
+// A thread that panicked while holding a lock is gone, the data is still good.
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Adds `group` to or removes it from the [`Durable::paused_groups`].
+pub(crate) fn set_group_paused(paused_groups: &Mutex<BTreeSet<String>>, group: &str, paused: bool) {
+    let mut paused_groups = lock(paused_groups);
+    if paused {
+        paused_groups.insert(group.to_owned());
+    } else {
+        paused_groups.remove(group);
+    }
+}
+
+/// A device as it registered with the communication thread, replayed after a restart.
+pub(crate) struct Registration {
+    pub serial_number: String,
+    pub design: JoyconDesign,
+    pub control: Option<BoundedSender<DeviceCommand>>,
+    pub stream: Option<Arc<Mutex<FrameConsumer>>>,
+    pub calibration: Option<CalibrationState>,
+    pub firmware: Option<FirmwareVersion>,
+}
+
+/// State of the communication thread that outlives it.
+pub(crate) struct Durable {
+    pub receive: Mutex<mpsc::Receiver<ChannelData>>,
+    pub actions: Mutex<mpsc::Receiver<Action>>,
+    pub registrations: Mutex<Vec<Registration>>,
+    /// Where the server is, kept up to date by [`resolver::watch`](super::resolver::watch).
+    pub server_address: Mutex<SocketAddr>,
+    /// MAC sent to the server, picked by the first communication thread. A restarted thread
+    /// keeps it, even though the socket of the one it replaced may still hold the port.
+    pub mac_address: Mutex<Option<[u8; 6]>>,
+    /// Whether sending tracker data is paused, also read by the GUI and the control surface.
+    pub paused: Arc<AtomicBool>,
+    /// Groups of trackers that are paused on their own, see [`Action::PauseGroup`].
+    pub paused_groups: Arc<Mutex<BTreeSet<String>>>,
+    /// No VR session is running, see [`Action::VrIdle`].
+    pub vr_idle: Arc<AtomicBool>,
+    /// The thread currently in charge, older ones return as soon as they notice.
+    pub generation: AtomicU64,
+    started: Instant,
+    heartbeat: AtomicU64,
+}
+impl Durable {
+    pub fn new(
+        receive: mpsc::Receiver<ChannelData>,
+        actions: mpsc::Receiver<Action>,
+        server_address: SocketAddr,
+        paused: Arc<AtomicBool>,
+        paused_groups: Arc<Mutex<BTreeSet<String>>>,
+        vr_idle: Arc<AtomicBool>,
+    ) -> Self {
+        Self {
+            receive: Mutex::new(receive),
+            actions: Mutex::new(actions),
+            registrations: Mutex::new(Vec::new()),
+            server_address: Mutex::new(server_address),
+            mac_address: Mutex::new(None),
+            paused,
+            paused_groups,
+            vr_idle,
+            generation: AtomicU64::new(0),
+            started: Instant::now(),
+            heartbeat: AtomicU64::new(0),
+        }
+    }
+    /// Called by the communication thread on every iteration.
+    pub fn beat(&self) {
+        let now = self.started.elapsed().as_millis() as u64;
+        self.heartbeat.store(now, Ordering::Relaxed);
+    }
+    fn stalled(&self) -> bool {
+        let last = Duration::from_millis(self.heartbeat.load(Ordering::Relaxed));
+        self.started.elapsed().saturating_sub(last) >= STALL_TIMEOUT
+    }
+}
+
+/// Channels from the communication thread to the [`Wrapper`](super::Wrapper).
+#[derive(Clone)]
+pub(crate) struct Outputs {
+    pub status_tx: BoundedSender<Vec<Status>>,
+    pub server_tx: Latest<ServerStatus>,
+    pub handshake_tx: Latest<HandshakeLog>,
+    pub errors_tx: BoundedSender<ErrorReport>,
+}
+
+/// Runs the communication thread and starts a new one whenever it dies or stalls, never returns.
+pub(crate) fn supervise(durable: Arc<Durable>, outputs: Outputs, settings: settings::Handler) {
+    let mut delay = Duration::ZERO;
+    loop {
+        thread::sleep(delay);
+        let started = Instant::now();
+        let generation = durable.generation.load(Ordering::Relaxed);
+        durable.beat();
+        let communication = {
+            let durable = durable.clone();
+            let outputs = outputs.clone();
+            let settings = settings.clone();
+            thread::spawn(move || Communication::start(durable, generation, outputs, settings))
+        };
+        while !communication.is_finished() && !durable.stalled() {
+            thread::sleep(CHECK_INTERVAL);
+        }
+
+        durable.generation.fetch_add(1, Ordering::Relaxed);
+        let problem = if communication.is_finished() {
+            "crashed"
+        } else {
+            "stopped responding"
+        };
+        delay = if started.elapsed() >= STABLE_RUN {
+            Duration::ZERO
+        } else {
+            (delay * 2).clamp(CHECK_INTERVAL, MAX_RESTART_DELAY)
+        };
+        let restart = if delay.is_zero() {
+            "was restarted".to_owned()
+        } else {
+            format!("is restarted in {}s", delay.as_secs())
+        };
+        outputs
+            .errors_tx
+            .send(
+                ErrorReport::new(
+                    "Wrangler",
+                    format!("The connection to the server {problem} and {restart}"),
+                )
+                .suggest("Please report this, with the log if there is one."),
+            )
+            .ok();
+    }
+}
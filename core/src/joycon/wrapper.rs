@@ -0,0 +1,214 @@
+use std::collections::BTreeSet;
+use std::net::SocketAddr;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc, Arc, Mutex,
+};
+
+use crate::settings::{self, WranglerSettings};
+
+use super::queue::{
+    bounded, BoundedSender, Latest, Overflow, Queue, ACTION_QUEUE_SIZE, DEVICE_QUEUE_SIZE,
+    ERROR_QUEUE_SIZE, STATUS_QUEUE_SIZE,
+};
+use super::watchdog::{lock, set_group_paused, supervise, Durable, Outputs};
+use super::{
+    communication::ServerStatus, default_sources, Action, DeviceSender, ErrorReport, HandshakeLog,
+    MotionSource, Status,
+};
+
+/// Handle to the background threads: Joy-Con integrations and communication with the server.
+pub struct Wrapper {
+    status_rx: mpsc::Receiver<Vec<Status>>,
+    server_rx: Latest<ServerStatus>,
+    handshake_rx: Latest<HandshakeLog>,
+    errors_rx: mpsc::Receiver<ErrorReport>,
+    action_tx: BoundedSender<Action>,
+    device_tx: DeviceSender,
+    paused: Arc<AtomicBool>,
+    paused_groups: Arc<Mutex<BTreeSet<String>>>,
+    vr_idle: Arc<AtomicBool>,
+}
+impl Wrapper {
+    /// Starts the communication thread and searches for Joy-Cons.
+    pub fn new(settings: settings::Handler) -> Self {
+        Self::with_sources(settings, default_sources())
+    }
+    /// Starts the communication thread and runs each of `sources` on a thread of its own.
+    pub fn with_sources(settings: settings::Handler, sources: Vec<Box<dyn MotionSource>>) -> Self {
+        let (status_tx, status_rx) = bounded(STATUS_QUEUE_SIZE, Overflow::Drop(Queue::Statuses));
+        let server_rx = Latest::new(Queue::Server);
+        let server_tx = server_rx.clone();
+        let handshake_rx = Latest::new(Queue::Handshake);
+        let handshake_tx = handshake_rx.clone();
+        let (errors_tx, errors_rx) = bounded(ERROR_QUEUE_SIZE, Overflow::Drop(Queue::Errors));
+        let (action_tx, action_rx) = bounded(ACTION_QUEUE_SIZE, Overflow::Block);
+        let (tx, rx) = bounded(DEVICE_QUEUE_SIZE, Overflow::Block);
+        let paused = Arc::new(AtomicBool::new(false));
+        let paused_groups = Arc::new(Mutex::new(BTreeSet::new()));
+        let vr_idle = Arc::new(AtomicBool::new(false));
+
+        {
+            // Through the relay, the server is at its local end and doesn't need looking up.
+            // Otherwise a hostname is looked up before anything is sent, not only by the first
+            // pass of the resolver, which would leave the first packets going to the default.
+            let relay = spawn_relay(&settings.load(), errors_tx.clone());
+            let address = relay.unwrap_or_else(|| {
+                let settings = settings.load();
+                settings
+                    .resolve_address()
+                    .unwrap_or_else(|| settings.get_socket_address())
+            });
+            let durable = Arc::new(Durable::new(
+                rx,
+                action_rx,
+                address,
+                paused.clone(),
+                paused_groups.clone(),
+                vr_idle.clone(),
+            ));
+            {
+                let vr_idle = vr_idle.clone();
+                let action_tx = action_tx.clone();
+                let settings = settings.clone();
+                std::thread::spawn(move || super::vr_session::watch(vr_idle, action_tx, settings));
+            }
+            if relay.is_none() {
+                let durable = durable.clone();
+                let settings = settings.clone();
+                let errors_tx = errors_tx.clone();
+                std::thread::spawn(move || super::resolver::watch(durable, settings, errors_tx));
+            }
+            let outputs = Outputs {
+                status_tx,
+                server_tx,
+                handshake_tx,
+                errors_tx,
+            };
+            let settings = settings.clone();
+            std::thread::spawn(move || supervise(durable, outputs, settings));
+        }
+
+        for source in sources {
+            let tx = tx.clone();
+            let settings = settings.clone();
+            std::thread::spawn(move || source.run(tx, settings));
+        }
+
+        // IPC control interfaces, D-Bus on Linux, a named pipe on Windows, OSC and optionally gRPC
+        // and MQTT.
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "windows",
+            feature = "grpc",
+            feature = "mqtt"
+        ))]
+        let status_rx = {
+            let (control, status_rx) = super::control::spawn(
+                action_tx.clone(),
+                tx.clone(),
+                settings,
+                status_rx,
+                paused.clone(),
+                paused_groups.clone(),
+            );
+            #[cfg(feature = "grpc")]
+            if let Some(address) = control.settings().load().grpc_target() {
+                let control = control.clone();
+                std::thread::spawn(move || super::grpc::serve(control, address));
+            }
+            #[cfg(feature = "mqtt")]
+            {
+                let settings = control.settings().load();
+                if !settings.mqtt_broker.is_empty() {
+                    let control = control.clone();
+                    let broker = settings.mqtt_broker.clone();
+                    let topic = settings.mqtt_topic.clone();
+                    std::thread::spawn(move || super::mqtt::publish(control, broker, topic));
+                }
+            }
+            let osc_port = control.settings().load().osc_port;
+            if osc_port != 0 {
+                let control = control.clone();
+                std::thread::spawn(move || super::osc::serve(control, osc_port));
+            }
+            #[cfg(target_os = "linux")]
+            std::thread::spawn(move || super::dbus::serve(control));
+            #[cfg(target_os = "windows")]
+            std::thread::spawn(move || super::pipe::serve(control));
+            status_rx
+        };
+
+        Self {
+            status_rx,
+            server_rx,
+            handshake_rx,
+            errors_rx,
+            action_tx,
+            device_tx: tx,
+            paused,
+            paused_groups,
+            vr_idle,
+        }
+    }
+    pub fn action(&self, action: Action) {
+        self.action_tx.send(action).ok();
+    }
+    /// Stops sending tracker data and reports the trackers offline to the server, or resumes.
+    /// The controllers stay connected.
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+        self.action(Action::Pause(paused));
+    }
+    /// Whether tracker data is paused, from here or anywhere else like a button combo.
+    pub fn paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+    /// Like [`Wrapper::set_paused`], for the trackers of `group`.
+    pub fn set_group_paused(&self, group: &str, paused: bool) {
+        if group.is_empty() {
+            return;
+        }
+        set_group_paused(&self.paused_groups, group, paused);
+        self.action(Action::PauseGroup(group.to_owned(), paused));
+    }
+    /// Groups paused on their own, from here or the control interfaces.
+    pub fn paused_groups(&self) -> BTreeSet<String> {
+        lock(&self.paused_groups).clone()
+    }
+    /// Whether sending is paused because no VR session is running, on top of [`Wrapper::paused`].
+    pub fn vr_idle(&self) -> bool {
+        self.vr_idle.load(Ordering::Relaxed)
+    }
+    /// Sender for bridging other devices, they are handled the same as Joy-Cons.
+    pub fn device_sender(&self) -> DeviceSender {
+        self.device_tx.clone()
+    }
+    /// Latest statuses of all devices, if any arrived since the last poll.
+    pub fn poll_status(&self) -> Option<Vec<Status>> {
+        self.status_rx.try_iter().last()
+    }
+    pub fn poll_server(&self) -> Option<ServerStatus> {
+        self.server_rx.take()
+    }
+    /// What was exchanged with the server during the handshake, if it changed since the last poll.
+    pub fn poll_handshake(&self) -> Option<HandshakeLog> {
+        self.handshake_rx.take()
+    }
+    /// Errors reported by the background threads since the last poll, oldest first.
+    pub fn poll_errors(&self) -> Vec<ErrorReport> {
+        self.errors_rx.try_iter().collect()
+    }
+}
+
+#[cfg(feature = "relay")]
+fn spawn_relay(
+    settings: &WranglerSettings,
+    errors_tx: BoundedSender<ErrorReport>,
+) -> Option<SocketAddr> {
+    super::relay::spawn_client(settings, errors_tx)
+}
+#[cfg(not(feature = "relay"))]
+fn spawn_relay(_: &WranglerSettings, _: BoundedSender<ErrorReport>) -> Option<SocketAddr> {
+    None
+}
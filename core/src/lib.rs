@@ -0,0 +1,32 @@
+#![deny(clippy::all)]
+//! Core of SlimeVR Wrangler: everything needed to turn controllers into SlimeVR trackers,
+//! without the GUI.
+//!
+//! - [`joycon`] finds Joy-Cons, runs sensor fusion on their IMU data and streams the result to
+//!   a SlimeVR server. [`joycon::Wrapper`] starts all of it and reports back statuses.
+//! - [`fusion`] holds the orientation filters on their own, for use outside of the pipeline.
+//! - [`protocol`] is the SlimeVR UDP protocol.
+//! - [`settings`] are the persisted settings shared by all of the above.
+//!
+//! Devices other than Joy-Cons can be bridged by sending [`joycon::ChannelData`] into
+//! [`joycon::Wrapper::device_sender`]: a [`joycon::ChannelInfo::Connected`] once, followed by
+//! [`joycon::ChannelInfo::ImuData`] bursts of three frames at 200Hz, with acceleration in G and
+//! rotation in radians/s. Busy sources can push the frames into a [`joycon::frame_queue`] handed
+//! over with [`joycon::ChannelInfo::Stream`] instead.
+//! To have wrangler run such a bridge itself, implement [`joycon::MotionSource`] and pass it to
+//! [`joycon::Wrapper::with_sources`]. With the `plugins` feature, sources are also loaded from
+//! dynamic libraries at runtime, see `joycon::PluginHost`.
+
+pub mod joycon;
+pub mod settings;
+
+pub use protocol;
+
+/// Sensor fusion filters turning raw IMU frames into a rotation, z is up.
+pub mod fusion {
+    pub use crate::joycon::{
+        euler_angles, in_frame, predict, Ekf, GyroBias, Imu, JoyconAxisData, Madgwick, Mahony,
+        RawConversion,
+    };
+    pub use crate::settings::{EulerOrder, Fusion, Handedness};
+}
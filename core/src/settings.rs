@@ -0,0 +1,1464 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs,
+    fs::File,
+    io::{self, BufReader, Write},
+    net::{SocketAddr, ToSocketAddrs},
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use arc_swap::{ArcSwap, Guard};
+use directories::{BaseDirs, ProjectDirs};
+use nalgebra::{Quaternion, UnitQuaternion};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::joycon::{Madgwick, Mahony};
+
+// Set by `--config`, instead of config.json in the usual place.
+static CONFIG_FILE: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Reads and saves the settings in `file` from now on, the backup goes next to it. Call it
+/// before the settings are first loaded.
+pub fn use_config_file(file: PathBuf) {
+    *CONFIG_FILE.lock().unwrap() = Some(file);
+}
+
+// Set by `--server`, the address used instead of the one in the settings, and that one.
+static SERVER_OVERRIDE: Mutex<Option<(String, Option<String>)>> = Mutex::new(None);
+
+/// Uses the server at `address` for this run, the saved settings keep their own until another
+/// address is entered. Call it before the settings are first loaded.
+pub fn override_server(address: String) {
+    *SERVER_OVERRIDE.lock().unwrap() = Some((address, None));
+}
+
+fn file_name() -> Option<PathBuf> {
+    if let Some(file) = CONFIG_FILE.lock().unwrap().clone() {
+        return Some(file);
+    }
+    ProjectDirs::from("", "", "SlimeVR Wrangler").map(|pd| pd.config_dir().join("config.json"))
+}
+
+/// The folder of the settings file in use, for what goes next to it like the logs.
+pub fn config_dir() -> Option<PathBuf> {
+    let file = file_name()?;
+    let dir = file.parent()?;
+    // A bare `--config wrangler.json` is in the working directory.
+    Some(if dir.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        dir.to_owned()
+    })
+}
+
+// The previous good settings, in case config.json gets damaged.
+fn backup_name(file: &Path) -> PathBuf {
+    file.with_extension("json.bak")
+}
+
+fn read_settings(path: &Path) -> Option<WranglerSettings> {
+    let file = File::open(path).ok()?;
+    serde_json::from_reader(BufReader::new(file)).ok()
+}
+
+// The settings in `file`, or in its backup if it is missing or broken.
+fn read_with_backup(file: &Path) -> Option<WranglerSettings> {
+    read_settings(file).or_else(|| read_settings(&backup_name(file)))
+}
+
+// Written to a temporary file first and renamed over the old one, so a crash leaves either the
+// old or the new settings in place, never half of them. The old file is kept as the backup,
+// unless it was already broken. Settings that didn't change aren't written, so the backup stays
+// the settings before the last edit.
+fn write_settings(file: &Path, settings: &WranglerSettings) -> io::Result<()> {
+    let bytes = serde_json::to_vec_pretty(settings)?;
+    let old = fs::read(file).ok();
+    if old.as_deref() == Some(&bytes[..]) {
+        return Ok(());
+    }
+    let dir = file.parent().unwrap();
+    fs::create_dir_all(dir)?;
+    let temp = file.with_extension("json.tmp");
+    {
+        let mut temp = File::create(&temp)?;
+        temp.write_all(&bytes)?;
+        temp.sync_all()?;
+    }
+    let intact = old.map_or(false, |old| {
+        serde_json::from_slice::<WranglerSettings>(&old).is_ok()
+    });
+    if intact {
+        fs::rename(file, backup_name(file))?;
+    }
+    fs::rename(&temp, file)?;
+    #[cfg(unix)]
+    File::open(dir).and_then(|dir| dir.sync_all()).ok();
+    Ok(())
+}
+
+// Changes come in bursts, like from dragging a slider. They are written this long after the
+// first one of a burst, with whatever they are by then.
+const SAVE_DELAY: Duration = Duration::from_millis(500);
+
+enum Save {
+    Settings(PathBuf, Box<WranglerSettings>),
+    Flush(mpsc::Sender<()>),
+}
+
+// The thread that writes the settings, so a change doesn't wait for the disk.
+static SAVER: Mutex<Option<mpsc::Sender<Save>>> = Mutex::new(None);
+
+fn saver() -> mpsc::Sender<Save> {
+    SAVER
+        .lock()
+        .unwrap()
+        .get_or_insert_with(|| {
+            let (tx, rx) = mpsc::channel();
+            thread::Builder::new()
+                .name("Settings writer".into())
+                .spawn(move || run_saver(rx))
+                .unwrap();
+            tx
+        })
+        .clone()
+}
+
+// The settings to write, and when.
+type Pending = Option<(PathBuf, Box<WranglerSettings>, Instant)>;
+
+fn write(pending: &mut Pending) {
+    if let Some((file, settings, _)) = pending.take() {
+        write_settings(&file, &settings).ok();
+    }
+}
+
+fn run_saver(rx: mpsc::Receiver<Save>) {
+    let mut pending: Pending = None;
+    loop {
+        let next = match &pending {
+            Some((_, _, due)) => rx.recv_timeout(due.saturating_duration_since(Instant::now())),
+            None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match next {
+            Ok(Save::Settings(file, settings)) => {
+                // Those for another file are written first, they don't wait for these.
+                if pending.as_ref().map_or(false, |(old, _, _)| *old != file) {
+                    write(&mut pending);
+                }
+                let due = pending
+                    .take()
+                    .map_or_else(|| Instant::now() + SAVE_DELAY, |(_, _, due)| due);
+                pending = Some((file, settings, due));
+            }
+            Ok(Save::Flush(done)) => {
+                write(&mut pending);
+                done.send(()).ok();
+            }
+            Err(RecvTimeoutError::Timeout) => write(&mut pending),
+            Err(RecvTimeoutError::Disconnected) => return write(&mut pending),
+        }
+    }
+}
+
+/// Waits until the settings saved so far are written. Call it before exiting.
+pub fn flush() {
+    let (done_tx, done_rx) = mpsc::channel();
+    if saver().send(Save::Flush(done_tx)).is_ok() {
+        done_rx.recv().ok();
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Joycon {
+    #[serde(default)]
+    pub rotation: i32,
+    #[serde(default = "return_f64_one")]
+    pub gyro_scale_factor: f64,
+    /// Gyro offsets measured by wrangler while the controller lay still, used instead of the
+    /// ones of the controller until they are written to it.
+    #[serde(default)]
+    pub gyro_origin: Option<[i16; 3]>,
+    /// From the six-sided accelerometer calibration, applied on top of the controller's own.
+    #[serde(default)]
+    pub accel_calibration: Option<AccelCalibration>,
+    #[serde(default)]
+    pub keep_id: u8,
+    /// Mounting offset quaternion as `[w, i, j, k]`, applied on the sensor side.
+    #[serde(default = "return_identity")]
+    pub mounting: [f64; 4],
+    /// Heading correction in degrees, from aligning all trackers.
+    #[serde(default)]
+    pub yaw_offset: f64,
+    /// Small heading nudge in degrees on top of [`Joycon::yaw_offset`], from the slider on the
+    /// tracker.
+    #[serde(default)]
+    pub yaw_trim: f64,
+    /// Roll/pitch correction quaternion as `[w, i, j, k]`, captured while lying flat.
+    #[serde(default = "return_identity")]
+    pub level: [f64; 4],
+    #[serde(default)]
+    pub nickname: String,
+    /// Group the tracker is in, like "legs", for acting on several trackers at once. Empty for
+    /// none.
+    #[serde(default)]
+    pub group: String,
+    /// Index of the accent color picked in the GUI, also selects the player LED pattern.
+    #[serde(default)]
+    pub accent: Option<u8>,
+    /// Path of a Rhai script transforming the rotation and acceleration before they are sent,
+    /// empty for none. Needs the `scripting` feature.
+    #[serde(default)]
+    pub script: String,
+    /// Never grabbed by wrangler, for a controller that is kept for playing.
+    #[serde(default)]
+    pub blocked: bool,
+    #[serde(default)]
+    pub frame_mode: FrameMode,
+    /// Sensor fusion of this tracker, the one in the settings without one of its own.
+    #[serde(default)]
+    pub fusion: Option<Fusion>,
+    /// Gain of its fusion, for the ones that have one. Without it, [`Fusion::default_gain`].
+    #[serde(default)]
+    pub fusion_gain: Option<f64>,
+    /// Features the SlimeVR server turned on or off for this tracker, by flag id.
+    #[serde(default)]
+    pub config_flags: BTreeMap<u16, bool>,
+    /// Outputs this tracker is left out of, it is sent to all the others that are on.
+    #[serde(default)]
+    pub excluded_outputs: Vec<Output>,
+}
+fn return_f64_one() -> f64 {
+    1.0
+}
+fn return_identity() -> [f64; 4] {
+    [1.0, 0.0, 0.0, 0.0]
+}
+
+impl Default for Joycon {
+    fn default() -> Self {
+        Joycon {
+            rotation: 0,
+            gyro_scale_factor: 1.0,
+            gyro_origin: None,
+            accel_calibration: None,
+            keep_id: 0,
+            mounting: return_identity(),
+            yaw_offset: 0.0,
+            yaw_trim: 0.0,
+            level: return_identity(),
+            nickname: String::new(),
+            group: String::new(),
+            accent: None,
+            script: String::new(),
+            blocked: false,
+            frame_mode: FrameMode::default(),
+            fusion: None,
+            fusion_gain: None,
+            config_flags: BTreeMap::new(),
+            excluded_outputs: Vec::new(),
+        }
+    }
+}
+
+/// Offset and scale of each accelerometer axis, a reading in G is corrected to
+/// `(reading - offset) * scale`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct AccelCalibration {
+    pub offset: [f64; 3],
+    pub scale: [f64; 3],
+}
+
+/// Flag id of the magnetometer in [`Joycon::config_flags`]. Joy-Cons have none, the state is only
+/// kept and shown.
+pub const MAGNETOMETER_FLAG: u16 = 1;
+
+/// Name of a flag the server can set, see [`Joycon::config_flags`].
+pub fn config_flag_name(flag: u16) -> String {
+    match flag {
+        MAGNETOMETER_FLAG => "Magnetometer".to_string(),
+        flag => format!("Flag {flag}"),
+    }
+}
+
+/// Sensor fusion algorithm turning the raw IMU data into a rotation.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Fusion {
+    #[default]
+    Vqf,
+    Ekf,
+    Madgwick,
+    Mahony,
+}
+impl Fusion {
+    pub const ALL: [Fusion; 4] = [Fusion::Vqf, Fusion::Ekf, Fusion::Madgwick, Fusion::Mahony];
+    /// Beta of Madgwick, the proportional gain of Mahony. The others are tuned for the Joy-Con
+    /// and have none.
+    pub fn default_gain(self) -> Option<f64> {
+        match self {
+            Fusion::Vqf | Fusion::Ekf => None,
+            Fusion::Madgwick => Some(Madgwick::DEFAULT_BETA),
+            Fusion::Mahony => Some(Mahony::DEFAULT_KP),
+        }
+    }
+    /// Gains that make sense for [`Fusion::default_gain`].
+    pub fn gain_range(self) -> RangeInclusive<f64> {
+        match self {
+            Fusion::Vqf | Fusion::Ekf => 0.0..=0.0,
+            Fusion::Madgwick => 0.01..=0.5,
+            Fusion::Mahony => 0.1..=5.0,
+        }
+    }
+}
+impl std::fmt::Display for Fusion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Fusion::Vqf => "VQF",
+            Fusion::Ekf => "Extended Kalman filter",
+            Fusion::Madgwick => "Madgwick",
+            Fusion::Mahony => "Mahony",
+        })
+    }
+}
+
+/// Buttons that trigger an action when held together on one controller. "Menu" is Capture on the
+/// left Joy-Con and HOME on the right, the triggers are the shoulder button and the one behind it.
+/// The side buttons are SL and SR on the rail, which the Pro Controller doesn't have.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ButtonCombo {
+    #[default]
+    Off,
+    MenuTriggers,
+    StickTriggers,
+    MinusPlusTriggers,
+    SideButtons,
+}
+impl ButtonCombo {
+    pub const ALL: [ButtonCombo; 5] = [
+        ButtonCombo::Off,
+        ButtonCombo::MenuTriggers,
+        ButtonCombo::StickTriggers,
+        ButtonCombo::MinusPlusTriggers,
+        ButtonCombo::SideButtons,
+    ];
+}
+impl std::fmt::Display for ButtonCombo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ButtonCombo::Off => "Off",
+            ButtonCombo::MenuTriggers => "Capture/HOME + both triggers",
+            ButtonCombo::StickTriggers => "Stick press + both triggers",
+            ButtonCombo::MinusPlusTriggers => "Minus/Plus + both triggers",
+            ButtonCombo::SideButtons => "SL + SR",
+        })
+    }
+}
+
+/// How the three IMU frames of every report are fed to the sensor fusion. Fewer distinct frames
+/// are smoother, all of them respond fastest.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FrameMode {
+    #[default]
+    All,
+    /// The average of the three.
+    Mean,
+    /// Only the newest one, the others are dropped.
+    Newest,
+}
+impl FrameMode {
+    pub const ALL: [FrameMode; 3] = [FrameMode::All, FrameMode::Mean, FrameMode::Newest];
+}
+impl std::fmt::Display for FrameMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            FrameMode::All => "All 3 frames",
+            FrameMode::Mean => "Mean of 3",
+            FrameMode::Newest => "Newest only",
+        })
+    }
+}
+
+/// Where the motion data of the trackers goes, any number of them can be on at once.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Output {
+    SlimeVr,
+    /// Rotations as VRChat OSC trackers, see [`WranglerSettings::osc_output_address`].
+    Osc,
+}
+impl Output {
+    pub const ALL: [Output; 2] = [Output::SlimeVr, Output::Osc];
+}
+impl std::fmt::Display for Output {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Output::SlimeVr => "SlimeVR server",
+            Output::Osc => "OSC trackers",
+        })
+    }
+}
+
+/// Order of the axes an orientation is split into for the numeric readout. Intrinsic, so `Xyz`
+/// rotates around X first, then around the new Y, then around the new Z.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EulerOrder {
+    Xyz,
+    Xzy,
+    Yxz,
+    Yzx,
+    Zxy,
+    #[default]
+    Zyx,
+}
+impl EulerOrder {
+    pub const ALL: [EulerOrder; 6] = [
+        EulerOrder::Xyz,
+        EulerOrder::Xzy,
+        EulerOrder::Yxz,
+        EulerOrder::Yzx,
+        EulerOrder::Zxy,
+        EulerOrder::Zyx,
+    ];
+    /// Axis indices, 0 for X through 2 for Z, in rotation order.
+    pub fn axes(&self) -> [usize; 3] {
+        match self {
+            EulerOrder::Xyz => [0, 1, 2],
+            EulerOrder::Xzy => [0, 2, 1],
+            EulerOrder::Yxz => [1, 0, 2],
+            EulerOrder::Yzx => [1, 2, 0],
+            EulerOrder::Zxy => [2, 0, 1],
+            EulerOrder::Zyx => [2, 1, 0],
+        }
+    }
+}
+impl std::fmt::Display for EulerOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name: String = self.axes().iter().map(|&a| ['X', 'Y', 'Z'][a]).collect();
+        f.write_str(&name)
+    }
+}
+
+/// Frame the numeric readout is shown in. Wrangler itself is right-handed with Z up, like
+/// Blender; `Left` is Unity's left-handed frame with Y up.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Handedness {
+    #[default]
+    Right,
+    Left,
+}
+impl Handedness {
+    pub const ALL: [Handedness; 2] = [Handedness::Right, Handedness::Left];
+}
+impl std::fmt::Display for Handedness {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Handedness::Right => "Right-handed, Z up",
+            Handedness::Left => "Left-handed, Y up",
+        })
+    }
+}
+
+/// What a controller is doing, each state has its own [`LightPattern`].
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LightState {
+    /// Waiting for the server.
+    Searching,
+    Streaming,
+    /// The IMU stopped reporting or lags behind.
+    Error,
+    /// The user asked which controller this is.
+    Identify,
+    /// Paused because no VR session is running, see [`WranglerSettings::vr_process`].
+    Idle,
+}
+impl LightState {
+    pub const ALL: [LightState; 5] = [
+        LightState::Searching,
+        LightState::Streaming,
+        LightState::Error,
+        LightState::Identify,
+        LightState::Idle,
+    ];
+}
+impl std::fmt::Display for LightState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            LightState::Searching => "Searching",
+            LightState::Streaming => "Streaming",
+            LightState::Error => "Error",
+            LightState::Identify => "Identify",
+            LightState::Idle => "No VR",
+        })
+    }
+}
+
+/// The four player lights. "Accent" shows the accent color number of the tracker in binary, or
+/// the two outer lights without one.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PlayerLights {
+    Accent,
+    AccentBlink,
+    All,
+    AllBlink,
+    Off,
+}
+impl PlayerLights {
+    pub const ALL: [PlayerLights; 5] = [
+        PlayerLights::Accent,
+        PlayerLights::AccentBlink,
+        PlayerLights::All,
+        PlayerLights::AllBlink,
+        PlayerLights::Off,
+    ];
+    /// The lights that are on for `accent`, as bits from the first light up. There are eight
+    /// accents, a larger one from a hand edited config.json gets the last pattern.
+    pub fn accent_mask(accent: Option<u8>) -> u8 {
+        accent.map_or(0b1001, |accent| accent.min(7) + 1)
+    }
+}
+impl std::fmt::Display for PlayerLights {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            PlayerLights::Accent => "Accent",
+            PlayerLights::AccentBlink => "Accent, blinking",
+            PlayerLights::All => "All",
+            PlayerLights::AllBlink => "All, blinking",
+            PlayerLights::Off => "Off",
+        })
+    }
+}
+
+/// The ring around the HOME button, the left Joy-Con doesn't have one.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HomeLight {
+    Off,
+    On,
+    Blink,
+    Pulse,
+}
+impl HomeLight {
+    pub const ALL: [HomeLight; 4] = [
+        HomeLight::Off,
+        HomeLight::On,
+        HomeLight::Blink,
+        HomeLight::Pulse,
+    ];
+}
+impl std::fmt::Display for HomeLight {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            HomeLight::Off => "Off",
+            HomeLight::On => "On",
+            HomeLight::Blink => "Blinking",
+            HomeLight::Pulse => "Pulsing",
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LightPattern {
+    pub player: PlayerLights,
+    pub home: HomeLight,
+}
+
+/// Light pattern for each [`LightState`].
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Lights {
+    pub searching: LightPattern,
+    pub streaming: LightPattern,
+    pub error: LightPattern,
+    pub identify: LightPattern,
+    #[serde(default = "return_idle_lights")]
+    pub idle: LightPattern,
+}
+// Dark, the controllers sit unused until VR starts.
+fn return_idle_lights() -> LightPattern {
+    LightPattern {
+        player: PlayerLights::Off,
+        home: HomeLight::Off,
+    }
+}
+impl Default for Lights {
+    fn default() -> Self {
+        Self {
+            searching: LightPattern {
+                player: PlayerLights::AccentBlink,
+                home: HomeLight::Off,
+            },
+            streaming: LightPattern {
+                player: PlayerLights::Accent,
+                home: HomeLight::Off,
+            },
+            error: LightPattern {
+                player: PlayerLights::AllBlink,
+                home: HomeLight::Blink,
+            },
+            identify: LightPattern {
+                player: PlayerLights::All,
+                home: HomeLight::Pulse,
+            },
+            idle: return_idle_lights(),
+        }
+    }
+}
+impl Lights {
+    pub fn get(&self, state: LightState) -> LightPattern {
+        match state {
+            LightState::Searching => self.searching,
+            LightState::Streaming => self.streaming,
+            LightState::Error => self.error,
+            LightState::Identify => self.identify,
+            LightState::Idle => self.idle,
+        }
+    }
+    pub fn get_mut(&mut self, state: LightState) -> &mut LightPattern {
+        match state {
+            LightState::Searching => &mut self.searching,
+            LightState::Streaming => &mut self.streaming,
+            LightState::Error => &mut self.error,
+            LightState::Identify => &mut self.identify,
+            LightState::Idle => &mut self.idle,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct WranglerSettings {
+    pub address: String,
+    #[serde(default)]
+    pub joycon: HashMap<String, Joycon>,
+    #[serde(default = "return_true")]
+    pub send_reset: bool,
+    #[serde(default = "return_mac")]
+    pub emulated_mac: [u8; 6],
+    /// First three bytes of the MAC sent to the server, replacing the ones of `emulated_mac`.
+    /// Always made locally administered, so it can't be the MAC of a real tracker.
+    #[serde(default)]
+    pub mac_prefix: Option<[u8; 3]>,
+    #[serde(default = "return_false")]
+    pub keep_ids: bool,
+    /// Connect every tracker to the server as a device of its own, with a MAC from its serial
+    /// number, instead of as sensors of one device. Read when wrangler starts, not through a
+    /// relay.
+    #[serde(default)]
+    pub separate_devices: bool,
+    #[serde(default)]
+    pub sleep_minutes: u32,
+    /// Milliseconds the rotations sent out are predicted ahead, 0 for none.
+    #[serde(default)]
+    pub prediction_ms: u32,
+    /// Leave controllers usable in Steam and games: they are not grabbed, and wrangler doesn't
+    /// change their lights, rumble, IMU settings or report mode, power them off or write their
+    /// calibration. It only reads their motion.
+    #[serde(default)]
+    pub shared_access: bool,
+    /// Per-controller settings saved under a name, see [`WranglerSettings::profile_load`].
+    #[serde(default)]
+    pub profiles: BTreeMap<String, HashMap<String, Joycon>>,
+    /// Name of the profile loaded last, empty for none.
+    #[serde(default)]
+    pub active_profile: String,
+    /// Loads the next profile, in the order of their names.
+    #[serde(default)]
+    pub combo_next_profile: ButtonCombo,
+    /// Pauses or resumes sending tracker data.
+    #[serde(default)]
+    pub combo_pause: ButtonCombo,
+    /// Yaw reset on the server, like B or UP with [`WranglerSettings::send_reset`].
+    #[serde(default)]
+    pub combo_reset: ButtonCombo,
+    /// Buzz a controller when its battery gets low, on top of the message in the GUI.
+    #[serde(default = "return_true")]
+    pub low_battery_rumble: bool,
+    /// Turn off controllers that did not move for this long and are not charging, 0 for never.
+    #[serde(default)]
+    pub power_off_minutes: u32,
+    /// Pause sending and dim the lights while no process of this name runs, like
+    /// [`STEAMVR_PROCESS`]. Empty to always send.
+    #[serde(default)]
+    pub vr_process: String,
+    /// Run the gyroscope in its 833Hz high performance mode instead of 208Hz.
+    #[serde(default)]
+    pub imu_gyro_high_performance: bool,
+    /// Use the accelerometer's 200Hz anti-aliasing filter instead of 100Hz.
+    #[serde(default)]
+    pub imu_accel_wide_filter: bool,
+    #[serde(default)]
+    pub fusion: Fusion,
+    #[serde(default)]
+    pub auto_level: bool,
+    /// Learn how far off the gyro is whenever a tracker lies still, see
+    /// [`GyroBias`](crate::joycon::GyroBias).
+    #[serde(default)]
+    pub gyro_bias_refinement: bool,
+    #[serde(default)]
+    pub euler_order: EulerOrder,
+    #[serde(default)]
+    pub euler_radians: bool,
+    #[serde(default)]
+    pub euler_handedness: Handedness,
+    #[serde(default)]
+    pub compact: bool,
+    #[serde(default)]
+    pub lights: Lights,
+    /// How often the GUI redraws, independent of how often trackers report.
+    #[serde(default = "return_refresh_rate")]
+    pub gui_refresh_hz: u32,
+    /// Where to send JSON telemetry with raw and fused data of every device, empty for off.
+    #[serde(default)]
+    pub telemetry_address: String,
+    #[serde(default = "return_telemetry_rate")]
+    pub telemetry_hz: u32,
+    /// Address to serve the gRPC control API on, empty for off. Needs the `grpc` feature.
+    #[serde(default)]
+    pub grpc_address: String,
+    /// MQTT broker as `host` or `host:port` to publish tracker statuses to, empty for off.
+    /// Needs the `mqtt` feature.
+    #[serde(default)]
+    pub mqtt_broker: String,
+    #[serde(default = "return_mqtt_topic")]
+    pub mqtt_topic: String,
+    #[serde(default = "return_true")]
+    pub output_slimevr: bool,
+    /// Also send the raw IMU samples to the server, for its inspection tools.
+    #[serde(default)]
+    pub inspection: bool,
+    /// Send fewer and bundled packets to the server, for weak Wi-Fi.
+    #[serde(default)]
+    pub low_bandwidth: bool,
+    /// Hold rotations back a little to send them evenly spaced, instead of in the bursts
+    /// Bluetooth delivers them in.
+    #[serde(default)]
+    pub output_smoothing: bool,
+    #[serde(default)]
+    pub output_osc: bool,
+    /// Where to send the OSC trackers, VRChat listens on port 9000.
+    #[serde(default = "return_osc_output_address")]
+    pub osc_output_address: String,
+    /// Relay to tunnel the server connection through, as `host:port`, empty for off. Needs the
+    /// `relay` feature, see `--relay`.
+    #[serde(default)]
+    pub relay_address: String,
+    /// Certificate fingerprint the relay printed, it is the only one accepted.
+    #[serde(default)]
+    pub relay_fingerprint: String,
+    /// Shared secret between wrangler and the relay, made by the relay on first start.
+    #[serde(default)]
+    pub relay_token: String,
+    /// Port on localhost to listen for OSC commands on, 0 for off.
+    #[serde(default)]
+    pub osc_port: u16,
+    /// SlimeVR server executable to start together with wrangler, empty for none.
+    #[serde(default)]
+    pub server_path: String,
+    #[serde(default)]
+    pub server_stop_on_exit: bool,
+    /// Run the Joy-Con reader threads at a raised priority.
+    #[serde(default)]
+    pub reader_priority: bool,
+    /// Pin the Joy-Con reader threads to this CPU core.
+    #[serde(default)]
+    pub reader_core: Option<usize>,
+}
+
+fn return_true() -> bool {
+    true
+}
+fn return_false() -> bool {
+    false
+}
+fn return_refresh_rate() -> u32 {
+    20
+}
+fn return_telemetry_rate() -> u32 {
+    50
+}
+fn return_osc_output_address() -> String {
+    "127.0.0.1:9000".to_string()
+}
+fn return_mqtt_topic() -> String {
+    "slimevr-wrangler".into()
+}
+// Locally administered, "WR" for wrangler. Older configs keep the 00:0F prefix they were made with.
+const MAC_PREFIX: [u8; 3] = [0x02, 0x57, 0x52];
+
+fn return_mac() -> [u8; 6] {
+    let mut r = rand::thread_rng();
+    let [a, b, c] = MAC_PREFIX;
+    [a, b, c, r.gen(), r.gen(), r.gen()]
+}
+
+/// A random secret, as hex.
+pub fn random_token() -> String {
+    let bytes: [u8; 16] = rand::thread_rng().gen();
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// `mac` with the last three bytes taken from a hash of `serial_number`, keeping its prefix. The
+/// same controller gets the same MAC every time.
+pub fn serial_mac(mac: [u8; 6], serial_number: &str) -> [u8; 6] {
+    // FNV-1a, which unlike the hasher of the standard library is the same in every build.
+    let hash = serial_number
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+    let [a, b, c, ..] = hash.to_le_bytes();
+    [mac[0], mac[1], mac[2], a, b, c]
+}
+
+/// A MAC of its own for `purpose`, like a second instance, from the MAC of the install. It is the
+/// same every time, so the server doesn't collect a new device on each launch, and differs
+/// between installs.
+pub fn derived_mac(mac: [u8; 6], purpose: &str) -> [u8; 6] {
+    serial_mac(mac, &format!("{:02x?}/{purpose}", &mac[3..]))
+}
+
+/// Purpose of [`derived_mac`] for the connections that only test or look for the server, one
+/// device on the server for all of them.
+pub const TEST_MAC: &str = "server test";
+
+/// The process of SteamVR that runs for as long as a VR session.
+pub const STEAMVR_PROCESS: &str = "vrserver";
+
+const DEFAULT_ADDR: &str = "127.0.0.1:6969";
+/// Port the SlimeVR server listens on unless set up otherwise.
+pub const DEFAULT_PORT: u16 = 6969;
+
+// Where the SlimeVR server keeps its settings, on the PC it runs on.
+fn server_config_file() -> Option<PathBuf> {
+    BaseDirs::new().map(|dirs| {
+        dirs.config_dir()
+            .join("dev.slimevr.SlimeVR")
+            .join("vrconfig.yml")
+    })
+}
+
+/// Address of a SlimeVR server set up on this PC, with the tracker port from its settings.
+/// `None` if it has no settings here.
+pub fn local_server_address() -> Option<String> {
+    let config = fs::read_to_string(server_config_file()?).ok()?;
+    let port = server_tracker_port(&config).unwrap_or(DEFAULT_PORT);
+    Some(format!("127.0.0.1:{port}"))
+}
+
+// `trackerPort` in the `server` section of vrconfig.yml, without a YAML parser for one number.
+fn server_tracker_port(config: &str) -> Option<u16> {
+    let mut in_server = false;
+    for line in config.lines() {
+        let line = line.split_once(" #").map_or(line, |(line, _)| line);
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        if !line.starts_with([' ', '\t']) {
+            in_server = line.trim_end() == "server:";
+        } else if in_server {
+            if let Some(port) = line.trim().strip_prefix("trackerPort:") {
+                return port.trim().parse().ok();
+            }
+        }
+    }
+    None
+}
+
+impl WranglerSettings {
+    /// Saves the settings in the background, shortly after, see [`flush`].
+    pub fn save(&self) {
+        let file = file_name().unwrap();
+        let mut settings = Box::new(self.clone());
+        let mut server_override = SERVER_OVERRIDE.lock().unwrap();
+        match &*server_override {
+            Some((address, Some(saved))) if *address == self.address => {
+                settings.address = saved.clone();
+            }
+            Some((address, None)) if *address == self.address => {}
+            // Entered in the window, it replaces the one from the command line.
+            Some(_) => *server_override = None,
+            None => {}
+        }
+        saver().send(Save::Settings(file, settings)).ok();
+    }
+    /// Loads the settings, falling back to the backup if config.json is missing or broken.
+    pub fn load_and_save() -> Self {
+        let mut settings = file_name()
+            .and_then(|path| read_with_backup(&path))
+            .unwrap_or_else(|| Self {
+                // Without settings of its own yet, it starts out with a server on this PC.
+                address: local_server_address().unwrap_or_else(|| DEFAULT_ADDR.into()),
+                joycon: HashMap::new(),
+                send_reset: true,
+                emulated_mac: return_mac(),
+                mac_prefix: None,
+                keep_ids: false,
+                separate_devices: false,
+                sleep_minutes: 0,
+                prediction_ms: 0,
+                power_off_minutes: 0,
+                vr_process: String::new(),
+                low_battery_rumble: true,
+                shared_access: false,
+                profiles: BTreeMap::new(),
+                active_profile: String::new(),
+                combo_next_profile: ButtonCombo::Off,
+                combo_pause: ButtonCombo::Off,
+                combo_reset: ButtonCombo::Off,
+                imu_gyro_high_performance: false,
+                imu_accel_wide_filter: false,
+                fusion: Fusion::default(),
+                auto_level: false,
+                gyro_bias_refinement: false,
+                euler_order: EulerOrder::default(),
+                euler_radians: false,
+                euler_handedness: Handedness::default(),
+                compact: false,
+                lights: Lights::default(),
+                gui_refresh_hz: return_refresh_rate(),
+                telemetry_address: String::new(),
+                telemetry_hz: return_telemetry_rate(),
+                grpc_address: String::new(),
+                mqtt_broker: String::new(),
+                mqtt_topic: return_mqtt_topic(),
+                output_slimevr: true,
+                inspection: false,
+                low_bandwidth: false,
+                output_smoothing: false,
+                output_osc: false,
+                osc_output_address: return_osc_output_address(),
+                relay_address: String::new(),
+                relay_fingerprint: String::new(),
+                relay_token: String::new(),
+                osc_port: 0,
+                server_path: String::new(),
+                server_stop_on_exit: false,
+                reader_priority: false,
+                reader_core: None,
+            });
+        if let Some((address, saved)) = &mut *SERVER_OVERRIDE.lock().unwrap() {
+            *saved = Some(std::mem::replace(&mut settings.address, address.clone()));
+        }
+        settings.save();
+        settings
+    }
+    pub fn joycon_rotation_add(&mut self, serial_number: String, degrees: i32) {
+        let entry = self.joycon.entry(serial_number).or_default();
+        entry.rotation = (entry.rotation + degrees).rem_euclid(360);
+    }
+    pub fn joycon_rotation_get(&self, serial_number: &str) -> i32 {
+        self.joycon.get(serial_number).map_or(0, |j| j.rotation)
+    }
+    pub fn joycon_scale_set(&mut self, serial_number: String, scale: f64) {
+        let entry = self.joycon.entry(serial_number).or_default();
+        entry.gyro_scale_factor = scale;
+    }
+    pub fn joycon_scale_get(&self, serial_number: &str) -> f64 {
+        self.joycon
+            .get(serial_number)
+            .map_or(1.0, |j| j.gyro_scale_factor)
+    }
+    pub fn joycon_gyro_origin_set(&mut self, serial_number: String, origin: Option<[i16; 3]>) {
+        let entry = self.joycon.entry(serial_number).or_default();
+        entry.gyro_origin = origin;
+    }
+    pub fn joycon_gyro_origin_get(&self, serial_number: &str) -> Option<[i16; 3]> {
+        self.joycon.get(serial_number).and_then(|j| j.gyro_origin)
+    }
+    pub fn joycon_accel_calibration_set(
+        &mut self,
+        serial_number: String,
+        calibration: Option<AccelCalibration>,
+    ) {
+        let entry = self.joycon.entry(serial_number).or_default();
+        entry.accel_calibration = calibration;
+    }
+    pub fn joycon_accel_calibration_get(&self, serial_number: &str) -> Option<AccelCalibration> {
+        self.joycon
+            .get(serial_number)
+            .and_then(|j| j.accel_calibration)
+    }
+    pub fn joycon_mounting_set(&mut self, serial_number: String, mounting: UnitQuaternion<f64>) {
+        let entry = self.joycon.entry(serial_number).or_default();
+        let q = mounting.quaternion();
+        entry.mounting = [q.w, q.i, q.j, q.k];
+    }
+    pub fn joycon_mounting_get(&self, serial_number: &str) -> UnitQuaternion<f64> {
+        self.joycon
+            .get(serial_number)
+            .map_or_else(UnitQuaternion::identity, |j| {
+                let [w, i, j, k] = j.mounting;
+                UnitQuaternion::from_quaternion(Quaternion::new(w, i, j, k))
+            })
+    }
+    pub fn joycon_level_set(&mut self, serial_number: String, level: UnitQuaternion<f64>) {
+        let entry = self.joycon.entry(serial_number).or_default();
+        let q = level.quaternion();
+        entry.level = [q.w, q.i, q.j, q.k];
+    }
+    pub fn joycon_level_get(&self, serial_number: &str) -> UnitQuaternion<f64> {
+        self.joycon
+            .get(serial_number)
+            .map_or_else(UnitQuaternion::identity, |j| {
+                let [w, i, j, k] = j.level;
+                UnitQuaternion::from_quaternion(Quaternion::new(w, i, j, k))
+            })
+    }
+    pub fn joycon_yaw_offset_set(&mut self, serial_number: String, degrees: f64) {
+        let entry = self.joycon.entry(serial_number).or_default();
+        entry.yaw_offset = degrees;
+    }
+    pub fn joycon_yaw_offset_get(&self, serial_number: &str) -> f64 {
+        self.joycon.get(serial_number).map_or(0.0, |j| j.yaw_offset)
+    }
+    pub fn joycon_yaw_trim_set(&mut self, serial_number: String, degrees: f64) {
+        let entry = self.joycon.entry(serial_number).or_default();
+        entry.yaw_trim = degrees;
+    }
+    pub fn joycon_yaw_trim_get(&self, serial_number: &str) -> f64 {
+        self.joycon.get(serial_number).map_or(0.0, |j| j.yaw_trim)
+    }
+    pub fn joycon_nickname_set(&mut self, serial_number: String, nickname: String) {
+        let entry = self.joycon.entry(serial_number).or_default();
+        entry.nickname = nickname;
+    }
+    pub fn joycon_nickname_get(&self, serial_number: &str) -> &str {
+        self.joycon.get(serial_number).map_or("", |j| &j.nickname)
+    }
+    pub fn joycon_group_set(&mut self, serial_number: String, group: String) {
+        let entry = self.joycon.entry(serial_number).or_default();
+        entry.group = group;
+    }
+    pub fn joycon_group_get(&self, serial_number: &str) -> &str {
+        self.joycon.get(serial_number).map_or("", |j| &j.group)
+    }
+    /// Names of all groups that have a tracker, sorted.
+    pub fn groups(&self) -> Vec<String> {
+        self.joycon
+            .values()
+            .filter(|j| !j.group.is_empty())
+            .map(|j| j.group.clone())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+    /// Serial numbers of the trackers in `group`.
+    pub fn group_members(&self, group: &str) -> Vec<String> {
+        self.joycon
+            .iter()
+            .filter(|(_, j)| !group.is_empty() && j.group == group)
+            .map(|(serial_number, _)| serial_number.clone())
+            .collect()
+    }
+    pub fn joycon_accent_set(&mut self, serial_number: String, accent: Option<u8>) {
+        let entry = self.joycon.entry(serial_number).or_default();
+        entry.accent = accent;
+    }
+    pub fn joycon_accent_get(&self, serial_number: &str) -> Option<u8> {
+        self.joycon.get(serial_number).and_then(|j| j.accent)
+    }
+    pub fn joycon_script_set(&mut self, serial_number: String, script: String) {
+        let entry = self.joycon.entry(serial_number).or_default();
+        entry.script = script;
+    }
+    pub fn joycon_script_get(&self, serial_number: &str) -> &str {
+        self.joycon.get(serial_number).map_or("", |j| &j.script)
+    }
+    pub fn joycon_frame_mode_set(&mut self, serial_number: String, frame_mode: FrameMode) {
+        let entry = self.joycon.entry(serial_number).or_default();
+        entry.frame_mode = frame_mode;
+    }
+    pub fn joycon_frame_mode_get(&self, serial_number: &str) -> FrameMode {
+        self.joycon
+            .get(serial_number)
+            .map_or(FrameMode::default(), |j| j.frame_mode)
+    }
+    /// Stored only when it differs from the fusion in the settings, the gain starts over at the
+    /// default of the new one.
+    pub fn joycon_fusion_set(&mut self, serial_number: String, fusion: Fusion) {
+        let own = (fusion != self.fusion).then_some(fusion);
+        let entry = self.joycon.entry(serial_number).or_default();
+        entry.fusion = own;
+        entry.fusion_gain = None;
+    }
+    pub fn joycon_fusion_get(&self, serial_number: &str) -> Fusion {
+        self.joycon
+            .get(serial_number)
+            .and_then(|j| j.fusion)
+            .unwrap_or(self.fusion)
+    }
+    pub fn joycon_fusion_gain_set(&mut self, serial_number: String, gain: f64) {
+        let entry = self.joycon.entry(serial_number).or_default();
+        entry.fusion_gain = Some(gain);
+    }
+    /// Gain for [`WranglerSettings::joycon_fusion_get`], `None` if it has none.
+    pub fn joycon_fusion_gain_get(&self, serial_number: &str) -> Option<f64> {
+        let own = self.joycon.get(serial_number).and_then(|j| j.fusion_gain);
+        self.joycon_fusion_get(serial_number)
+            .default_gain()
+            .map(|default| own.unwrap_or(default))
+    }
+    pub fn joycon_config_flag_set(&mut self, serial_number: String, flag: u16, state: bool) {
+        let entry = self.joycon.entry(serial_number).or_default();
+        entry.config_flags.insert(flag, state);
+    }
+    pub fn joycon_config_flags_get(&self, serial_number: &str) -> BTreeMap<u16, bool> {
+        self.joycon
+            .get(serial_number)
+            .map(|j| j.config_flags.clone())
+            .unwrap_or_default()
+    }
+    pub fn joycon_output_set(&mut self, serial_number: String, output: Output, included: bool) {
+        let entry = self.joycon.entry(serial_number).or_default();
+        entry.excluded_outputs.retain(|&o| o != output);
+        if !included {
+            entry.excluded_outputs.push(output);
+        }
+    }
+    pub fn joycon_output_get(&self, serial_number: &str, output: Output) -> bool {
+        self.joycon
+            .get(serial_number)
+            .map_or(true, |j| !j.excluded_outputs.contains(&output))
+    }
+    pub fn joycon_blocked_set(&mut self, serial_number: String, blocked: bool) {
+        let entry = self.joycon.entry(serial_number).or_default();
+        entry.blocked = blocked;
+    }
+    pub fn joycon_blocked_get(&self, serial_number: &str) -> bool {
+        self.joycon.get(serial_number).map_or(false, |j| j.blocked)
+    }
+    /// Saves the settings of every controller as profile `name`, replacing one of the same name.
+    pub fn profile_save(&mut self, name: String) {
+        self.profiles.insert(name.clone(), self.joycon.clone());
+        self.active_profile = name;
+    }
+    /// Replaces the settings of the controllers in profile `name`, after saving changes to the
+    /// active one. Server ids, the blocklist and groups belong to the controller and are kept.
+    pub fn profile_load(&mut self, name: &str) -> bool {
+        if !self.profile_apply(name, None) {
+            return false;
+        }
+        self.active_profile = name.to_owned();
+        true
+    }
+    /// Like [`WranglerSettings::profile_load`], but only for the controllers in `group`. The
+    /// others keep their settings and the active profile stays the same.
+    pub fn profile_load_group(&mut self, name: &str, group: &str) -> bool {
+        self.profile_apply(name, Some(group))
+    }
+    fn profile_apply(&mut self, name: &str, group: Option<&str>) -> bool {
+        let Some(saved) = self.profiles.get(name).cloned() else {
+            return false;
+        };
+        if let Some(active) = self.profiles.get_mut(&self.active_profile) {
+            *active = self.joycon.clone();
+        }
+        for (serial_number, mut joycon) in saved {
+            let current = self.joycon.get(&serial_number);
+            if let Some(group) = group {
+                if current.map_or(true, |current| current.group != group) {
+                    continue;
+                }
+            }
+            if let Some(current) = current {
+                joycon.keep_id = current.keep_id;
+                joycon.blocked = current.blocked;
+                joycon.group.clone_from(&current.group);
+                joycon.config_flags.clone_from(&current.config_flags);
+            }
+            self.joycon.insert(serial_number, joycon);
+        }
+        true
+    }
+    /// Loads the profile after the active one, returns its name.
+    pub fn profile_next(&mut self) -> Option<String> {
+        let next = self
+            .profiles
+            .range::<str, _>((
+                std::ops::Bound::Excluded(self.active_profile.as_str()),
+                std::ops::Bound::Unbounded,
+            ))
+            .next()
+            .or_else(|| self.profiles.iter().next())
+            .map(|(name, _)| name.clone())?;
+        self.profile_load(&next).then_some(next)
+    }
+    pub fn profile_delete(&mut self, name: &str) {
+        self.profiles.remove(name);
+        if self.active_profile == name {
+            self.active_profile.clear();
+        }
+    }
+    fn joycon_keep_id_set_new(&mut self, serial_number: String) {
+        let max = self.joycon.values().map(|j| j.keep_id).max();
+        let entry = self.joycon.entry(serial_number).or_default();
+        entry.keep_id = max.unwrap_or_default().saturating_add(1);
+        if entry.keep_id == u8::MAX {
+            error!(
+                "Too many controllers saved, their ids ran out, turn off \"Save mounting location on server\" in the settings"
+            );
+        }
+    }
+    pub fn gui_refresh_interval(&self) -> Duration {
+        Duration::from_secs(1) / self.gui_refresh_hz.clamp(1, 240)
+    }
+    pub fn telemetry_target(&self) -> Option<SocketAddr> {
+        self.telemetry_address.parse().ok()
+    }
+    pub fn osc_output_target(&self) -> Option<SocketAddr> {
+        self.osc_output_address.parse().ok()
+    }
+    pub fn output_get(&self, output: Output) -> bool {
+        match output {
+            Output::SlimeVr => self.output_slimevr,
+            Output::Osc => self.output_osc,
+        }
+    }
+    pub fn output_set(&mut self, output: Output, on: bool) {
+        match output {
+            Output::SlimeVr => self.output_slimevr = on,
+            Output::Osc => self.output_osc = on,
+        }
+    }
+    /// Whether the data of the tracker goes to `output`, it has to be on and include the tracker.
+    pub fn sends_to(&self, serial_number: &str, output: Output) -> bool {
+        self.output_get(output) && self.joycon_output_get(serial_number, output)
+    }
+    pub fn grpc_target(&self) -> Option<SocketAddr> {
+        self.grpc_address.parse().ok()
+    }
+    pub fn telemetry_interval(&self) -> Duration {
+        Duration::from_secs(1) / self.telemetry_hz.clamp(1, 1000)
+    }
+    pub fn prediction(&self) -> Option<Duration> {
+        (self.prediction_ms > 0).then(|| Duration::from_millis(u64::from(self.prediction_ms)))
+    }
+    pub fn sleep_timeout(&self) -> Option<Duration> {
+        (self.sleep_minutes > 0).then(|| Duration::from_secs(self.sleep_minutes as u64 * 60))
+    }
+    pub fn power_off_timeout(&self) -> Option<Duration> {
+        (self.power_off_minutes > 0)
+            .then(|| Duration::from_secs(self.power_off_minutes as u64 * 60))
+    }
+    /// MAC sent to the server, `emulated_mac` with `mac_prefix` if one is set.
+    pub fn mac_address(&self) -> [u8; 6] {
+        let mut mac = self.emulated_mac;
+        if let Some(prefix) = self.mac_prefix {
+            mac[..3].copy_from_slice(&prefix);
+            // Locally administered and unicast.
+            mac[0] = (mac[0] | 0x02) & !0x01;
+        }
+        mac
+    }
+    /// The server address if it is an ip with port, otherwise the default. Hostnames are looked
+    /// up by [`WranglerSettings::resolve_address`].
+    pub fn get_socket_address(&self) -> SocketAddr {
+        self.address
+            .parse::<SocketAddr>()
+            .unwrap_or_else(|_| DEFAULT_ADDR.parse().unwrap())
+    }
+    /// Whether the server address is an ip or hostname with a port, without looking it up.
+    pub fn address_is_valid(&self) -> bool {
+        self.address.parse::<SocketAddr>().is_ok()
+            || self.address.rsplit_once(':').map_or(false, |(host, port)| {
+                !host.is_empty()
+                    && !host.contains(char::is_whitespace)
+                    && port.parse::<u16>().is_ok()
+            })
+    }
+    /// Looks up the server address, see [`resolve`].
+    pub fn resolve_address(&self) -> Option<SocketAddr> {
+        resolve(&self.address)
+    }
+    /// The settings as JSON for a bug report: without the relay token, and with the home
+    /// directory in paths shortened to `~`.
+    pub fn report_json(&self) -> String {
+        let mut settings = self.clone();
+        if !settings.relay_token.is_empty() {
+            settings.relay_token = "<removed>".to_string();
+        }
+        let mut json = serde_json::to_string_pretty(&settings).unwrap_or_default();
+        if let Some(dirs) = directories::BaseDirs::new() {
+            // As it is written in JSON, with doubled backslashes on Windows.
+            let home =
+                serde_json::to_string(&dirs.home_dir().to_string_lossy()).unwrap_or_default();
+            let home = home.trim_matches('"');
+            if !home.is_empty() {
+                json = json.replace(home, "~");
+            }
+        }
+        json
+    }
+}
+/// Looks up `address` as ip:port or hostname:port. Prefers IPv4, which is what the socket to the
+/// server uses. Blocks until the lookup is done.
+pub fn resolve(address: &str) -> Option<SocketAddr> {
+    let addresses: Vec<_> = address.to_socket_addrs().ok()?.collect();
+    addresses
+        .iter()
+        .find(|address| address.is_ipv4())
+        .or_else(|| addresses.first())
+        .copied()
+}
+
+impl Default for WranglerSettings {
+    fn default() -> Self {
+        WranglerSettings::load_and_save()
+    }
+}
+
+#[derive(Default, Clone)]
+pub struct Handler {
+    arc: Arc<ArcSwap<WranglerSettings>>,
+}
+impl Handler {
+    pub fn load(&self) -> Guard<Arc<WranglerSettings>> {
+        self.arc.load()
+    }
+    pub fn change<T>(&self, func: T)
+    where
+        T: FnOnce(&mut WranglerSettings),
+    {
+        let mut current = (**self.arc.load()).clone();
+        func(&mut current);
+        current.save();
+        self.arc.store(Arc::new(current));
+    }
+    pub fn joycon_keep_id(&self, serial_number: String) -> u8 {
+        let keep_id = self
+            .load()
+            .joycon
+            .get(&serial_number)
+            .map_or(0, |j| j.keep_id);
+        if keep_id != 0 {
+            return keep_id;
+        }
+        self.change(|ws| ws.joycon_keep_id_set_new(serial_number.clone()));
+        self.load()
+            .joycon
+            .get(&serial_number)
+            .map_or(0, |j| j.keep_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracker_port() {
+        let config = "\
+server:
+  trackerPort: 6970
+  useMagnetometerOnAllTrackers: false
+";
+        assert_eq!(server_tracker_port(config), Some(6970));
+    }
+
+    #[test]
+    fn tracker_port_with_comments() {
+        let config = "\
+# trackerPort: 1
+server: # the SlimeVR server
+  # trackerPort: 2
+  trackerPort: 6970 # changed from 6969
+";
+        assert_eq!(server_tracker_port(config), Some(6970));
+    }
+
+    #[test]
+    fn tracker_port_of_other_sections() {
+        let config = "\
+bridge:
+  trackerPort: 1
+server:
+  webSocketPort: 21110
+filters:
+  trackerPort: 2
+";
+        assert_eq!(server_tracker_port(config), None);
+    }
+
+    #[test]
+    fn tracker_port_missing() {
+        assert_eq!(server_tracker_port(""), None);
+        assert_eq!(server_tracker_port("server:\n  trackerPort:\n"), None);
+        assert_eq!(server_tracker_port("trackerPort: 6970\n"), None);
+    }
+
+    // A fresh folder of its own for each test, the settings file isn't created yet.
+    fn scratch(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("wrangler-{}-{test}", std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+        dir.join("config.json")
+    }
+
+    fn with_address(address: &str) -> WranglerSettings {
+        serde_json::from_value(serde_json::json!({ "address": address })).unwrap()
+    }
+
+    #[test]
+    fn saving_twice_keeps_backup() {
+        let file = scratch("saving_twice");
+        write_settings(&file, &with_address("first")).unwrap();
+        write_settings(&file, &with_address("second")).unwrap();
+        assert_eq!(read_settings(&file).unwrap().address, "second");
+        assert_eq!(read_settings(&backup_name(&file)).unwrap().address, "first");
+        assert!(!file.with_extension("json.tmp").exists());
+        fs::remove_dir_all(file.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn corrupt_file_falls_back_to_backup() {
+        let file = scratch("corrupt");
+        write_settings(&file, &with_address("first")).unwrap();
+        write_settings(&file, &with_address("second")).unwrap();
+        fs::write(&file, "{ \"address\": ").unwrap();
+        assert_eq!(read_with_backup(&file).unwrap().address, "first");
+        // The broken file doesn't replace the backup.
+        write_settings(&file, &with_address("third")).unwrap();
+        assert_eq!(read_settings(&file).unwrap().address, "third");
+        assert_eq!(read_settings(&backup_name(&file)).unwrap().address, "first");
+        fs::remove_dir_all(file.parent().unwrap()).ok();
+    }
+
+    const MAC: [u8; 6] = [0x02, 0x57, 0x52, 0x12, 0x34, 0x56];
+
+    #[test]
+    fn serial_mac_is_fnv1a() {
+        // The low bytes of the 64 bit FNV-1a hash, the first two are its published test vectors.
+        assert_eq!(serial_mac(MAC, ""), [0x02, 0x57, 0x52, 0x25, 0x23, 0x22]);
+        assert_eq!(serial_mac(MAC, "a"), [0x02, 0x57, 0x52, 0x8c, 0xec, 0x01]);
+        assert_eq!(
+            serial_mac(MAC, "98:B6:E9:12:34:56"),
+            [0x02, 0x57, 0x52, 0xc9, 0x76, 0xe2]
+        );
+    }
+
+    #[test]
+    fn macs_locally_administered() {
+        for _ in 0..16 {
+            let mac = return_mac();
+            assert_eq!(mac[..3], MAC_PREFIX);
+            assert_eq!(mac[0] & 0x03, 0x02);
+        }
+        let mut settings = with_address("first");
+        settings.mac_prefix = Some([0x01, 0x57, 0x53]);
+        assert_eq!(settings.mac_address()[..3], [0x02, 0x57, 0x53]);
+    }
+
+    #[test]
+    fn derived_mac_differs() {
+        let test = derived_mac(MAC, TEST_MAC);
+        assert_ne!(test, MAC);
+        assert_eq!(test[..3], MAC[..3]);
+        assert_eq!(test, derived_mac(MAC, TEST_MAC));
+        assert_ne!(test, derived_mac(MAC, "second instance"));
+        let other_install = [0x02, 0x57, 0x52, 0x65, 0x43, 0x21];
+        assert_ne!(derived_mac(other_install, TEST_MAC), test);
+    }
+}
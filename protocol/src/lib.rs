@@ -2,8 +2,9 @@ mod test_deku;
 
 pub use deku;
 
-use std::string::FromUtf8Error;
+use std::{fmt, string::FromUtf8Error};
 
+use deku::bitvec::{BitVec, Msb0};
 use deku::prelude::*;
 
 #[derive(Debug, PartialEq, DekuRead, DekuWrite)]
@@ -52,6 +53,36 @@ mod nalgebra030_impls {
     impl_Nalgebra!();
 }
 
+/// What can go wrong building or serializing a packet.
+#[derive(Debug, PartialEq)]
+pub enum ProtocolError {
+    /// A [`SlimeString`] is prefixed with its length as one byte, this many bytes don't fit.
+    StringTooLong(usize),
+    /// Only packets with a `packet_id` go in a [`Bundle`].
+    NotBundleable,
+    Deku(DekuError),
+}
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::StringTooLong(len) => write!(
+                f,
+                "string of {len} bytes is longer than the {} that fit in a packet",
+                SlimeString::MAX_LEN
+            ),
+            Self::NotBundleable => write!(f, "only packets with a packet id can be bundled"),
+            Self::Deku(e) => write!(f, "{e}"),
+        }
+    }
+}
+impl std::error::Error for ProtocolError {}
+impl From<DekuError> for ProtocolError {
+    fn from(e: DekuError) -> Self {
+        Self::Deku(e)
+    }
+}
+
+/// UTF-8 prefixed with its length in bytes, so at most [`SlimeString::MAX_LEN`] of them.
 #[derive(PartialEq, Eq, Debug, DekuRead, DekuWrite)]
 #[deku(endian = "endian", ctx = "endian: deku::ctx::Endian")]
 pub struct SlimeString {
@@ -60,26 +91,59 @@ pub struct SlimeString {
     #[deku(count = "count")]
     data: Vec<u8>,
 }
-impl From<String> for SlimeString {
-    fn from(s: String) -> Self {
-        let bytes = s.into_bytes();
+impl SlimeString {
+    pub const MAX_LEN: usize = u8::MAX as usize;
+
+    pub fn new(s: &str) -> Result<Self, ProtocolError> {
+        let count = u8::try_from(s.len()).map_err(|_| ProtocolError::StringTooLong(s.len()))?;
+        Ok(Self {
+            count,
+            data: s.as_bytes().to_vec(),
+        })
+    }
+    /// Like [`SlimeString::new`], but cuts off what doesn't fit, at a character boundary.
+    pub fn truncated(s: &str) -> Self {
+        let mut end = s.len().min(Self::MAX_LEN);
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
         Self {
-            count: bytes.len() as _,
-            data: bytes,
+            count: end as u8,
+            data: s.as_bytes()[..end].to_vec(),
         }
     }
-}
-impl SlimeString {
-    #[allow(dead_code)]
-    fn to_string(&self) -> Result<String, FromUtf8Error> {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+    /// Received strings are not checked, the server or other firmware may send anything.
+    pub fn to_string(&self) -> Result<String, FromUtf8Error> {
         String::from_utf8(self.data.clone())
     }
+    pub fn to_string_lossy(&self) -> String {
+        String::from_utf8_lossy(&self.data).into_owned()
+    }
+}
+impl TryFrom<&str> for SlimeString {
+    type Error = ProtocolError;
+    fn try_from(s: &str) -> Result<Self, ProtocolError> {
+        Self::new(s)
+    }
+}
+impl TryFrom<String> for SlimeString {
+    type Error = ProtocolError;
+    fn try_from(s: String) -> Result<Self, ProtocolError> {
+        Self::new(&s)
+    }
 }
 
 #[derive(Debug, PartialEq, DekuRead, DekuWrite)]
 #[deku(type = "u32")]
 #[deku(endian = "big")]
 pub enum PacketType {
+    /// Sent by trackers once a second, so the server keeps the connection while nothing else is
+    /// sent.
+    #[deku(id = "0")]
+    Heartbeat { packet_id: u64 },
     #[deku(id = "1")]
     Rotation {
         packet_id: u64,
@@ -104,6 +168,14 @@ pub enum PacketType {
     },
     #[deku(id = "10")]
     Ping { id: u32 },
+    /// Battery of the whole device, `level` from 0 to 1. It has no sensor id, the server shows it
+    /// on every tracker of the device.
+    #[deku(id = "12")]
+    BatteryLevel {
+        packet_id: u64,
+        voltage: f32,
+        level: f32,
+    },
     #[deku(id = "15")]
     SensorInfo {
         packet_id: u64,
@@ -121,6 +193,124 @@ pub enum PacketType {
     },
     #[deku(id = "21")]
     UserAction { packet_id: u64, typ: u8 },
+    /// Confirms a [`PacketType::SetConfigFlag`] was applied.
+    #[deku(id = "24")]
+    AckConfigChange {
+        packet_id: u64,
+        sensor_id: u8,
+        flag_id: u16,
+    },
+    /// Sent by the server to turn a feature of a sensor on or off, `sensor_id` 255 is all of them.
+    #[deku(id = "25")]
+    SetConfigFlag {
+        packet_id: u64,
+        sensor_id: u8,
+        flag_id: u16,
+        state: bool,
+    },
+    /// Raw IMU samples for the inspection tools of the server, `typ` 1 with `data_type` 2 for
+    /// floats. Sensors without a magnetometer send zeros with accuracy 0.
+    #[deku(id = "105")]
+    Inspection {
+        packet_id: u64,
+        typ: u8,
+        sensor_id: u8,
+        data_type: u8,
+        gyro: (f32, f32, f32),
+        gyro_accuracy: u8,
+        accel: (f32, f32, f32),
+        accel_accuracy: u8,
+        mag: (f32, f32, f32),
+        mag_accuracy: u8,
+    },
     #[deku(id = "55076217")] // u8 array with [3, 'H', 'e', 'y'] as u32
     HandshakeResponse,
 }
+
+const HANDSHAKE_REPLY: &[u8] = b"\x03Hey OVR =D";
+
+/// Protocol version the server tells in its [`PacketType::HandshakeResponse`], the number after
+/// "Hey OVR =D". `None` if the reply has none.
+pub fn server_protocol_version(reply: &[u8]) -> Option<u32> {
+    let rest = reply.strip_prefix(HANDSHAKE_REPLY)?;
+    let digits: String = rest
+        .iter()
+        .skip_while(|&&b| b == b' ')
+        .take_while(|b| b.is_ascii_digit())
+        .map(|&b| b as char)
+        .collect();
+    digits.parse().ok()
+}
+
+// Packet type of a bundle, see [`Bundle`].
+const BUNDLE_ID: u32 = 100;
+
+/// Packets to send together in one datagram, which saves the headers of all but one on a slow
+/// link. Each packet is stored as its length and then the packet without its `packet_id`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Bundle(Vec<u8>);
+
+impl Bundle {
+    /// Adds a packet and returns how many bytes it takes up in the bundle. Packets without a
+    /// `packet_id` can't be bundled.
+    pub fn push(&mut self, packet: &PacketType) -> Result<usize, ProtocolError> {
+        if !packet.has_packet_id() {
+            return Err(ProtocolError::NotBundleable);
+        }
+        let bytes = packet.to_bytes()?;
+        let len = bytes.len() - 8;
+        self.0.extend_from_slice(&(len as u16).to_be_bytes());
+        self.0.extend_from_slice(&bytes[..4]);
+        self.0.extend_from_slice(&bytes[12..]);
+        Ok(len + 2)
+    }
+    /// Bytes taken up by the packets so far, without the header of the bundle.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+    /// The datagram with every packet added since the last call, which empties the bundle.
+    pub fn take(&mut self, packet_id: u64) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(12 + self.0.len());
+        bytes.extend_from_slice(&BUNDLE_ID.to_be_bytes());
+        bytes.extend_from_slice(&packet_id.to_be_bytes());
+        bytes.append(&mut self.0);
+        bytes
+    }
+}
+
+/// Storage for serializing packets, kept around so sending doesn't allocate every time.
+#[derive(Debug, Default)]
+pub struct PacketBuffer(BitVec<u8, Msb0>);
+
+impl PacketType {
+    /// Same bytes as [`DekuContainerWrite::to_bytes`], written into `buffer` instead of a new Vec.
+    pub fn write_to<'a>(&self, buffer: &'a mut PacketBuffer) -> Result<&'a [u8], ProtocolError> {
+        buffer.0.clear();
+        self.write(&mut buffer.0, ())?;
+        Ok(buffer.0.as_raw_slice())
+    }
+    /// Like [`PacketType::write_to`], with `packet_id` in place of the one of the packet. The
+    /// server drops packets with a lower id than one it already got, except for 0.
+    pub fn write_numbered<'a>(
+        &self,
+        buffer: &'a mut PacketBuffer,
+        packet_id: u64,
+    ) -> Result<&'a [u8], ProtocolError> {
+        self.write_to(buffer)?;
+        let bytes = buffer.0.as_raw_mut_slice();
+        if self.has_packet_id() {
+            bytes[4..12].copy_from_slice(&packet_id.to_be_bytes());
+        }
+        Ok(bytes)
+    }
+    /// Whether the packet has a `packet_id`, right after its type.
+    pub fn has_packet_id(&self) -> bool {
+        !matches!(
+            self,
+            PacketType::Ping { .. } | PacketType::HandshakeResponse
+        )
+    }
+}
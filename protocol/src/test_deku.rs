@@ -3,7 +3,9 @@ mod tests {
     use deku::{DekuContainerRead, DekuContainerWrite};
     use nalgebra032::{Quaternion, UnitQuaternion};
 
-    use crate::PacketType;
+    use crate::{
+        server_protocol_version, Bundle, PacketBuffer, PacketType, ProtocolError, SlimeString,
+    };
 
     #[test]
     fn handshake() {
@@ -15,7 +17,7 @@ mod tests {
             mcu_type: 4,
             imu_info: (5, 6, 7),
             build: 8,
-            firmware: "test".to_string().into(),
+            firmware: SlimeString::new("test").unwrap(),
             mac_address: mac,
         };
         let data: Vec<u8> = vec![
@@ -105,8 +107,177 @@ mod tests {
         );
     }
     #[test]
+    fn test_write_numbered() {
+        let mut buffer = PacketBuffer::default();
+        let heartbeat = PacketType::Heartbeat { packet_id: 0 };
+        assert_eq!(
+            heartbeat.write_numbered(&mut buffer, 258).unwrap(),
+            [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 2]
+        );
+        let ping = PacketType::Ping { id: 7 };
+        assert_eq!(
+            ping.write_numbered(&mut buffer, 258).unwrap(),
+            [0, 0, 0, 10, 0, 0, 0, 7]
+        );
+    }
+    #[test]
+    fn test_heartbeat() {
+        let heartbeat = PacketType::Heartbeat { packet_id: 2 };
+        assert_eq!(
+            heartbeat.to_bytes().unwrap(),
+            [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2]
+        );
+    }
+    #[test]
+    fn test_battery_level() {
+        let battery = PacketType::BatteryLevel {
+            packet_id: 1,
+            voltage: 3.75,
+            level: 0.5,
+        };
+        assert_eq!(
+            battery.to_bytes().unwrap(),
+            [0, 0, 0, 12, 0, 0, 0, 0, 0, 0, 0, 1, 64, 112, 0, 0, 63, 0, 0, 0]
+        );
+    }
+    #[test]
+    fn test_set_config_flag() {
+        let data = [0, 0, 0, 25, 0, 0, 0, 0, 0, 0, 0, 7, 255, 0, 1, 1];
+        let result = PacketType::from_bytes((&data, 0)).unwrap().1;
+
+        let flag = PacketType::SetConfigFlag {
+            packet_id: 7,
+            sensor_id: 255,
+            flag_id: 1,
+            state: true,
+        };
+        assert_eq!(result, flag);
+    }
+    #[test]
+    fn test_ack_config_change() {
+        let ack = PacketType::AckConfigChange {
+            packet_id: 1,
+            sensor_id: 2,
+            flag_id: 1,
+        };
+        assert_eq!(
+            ack.to_bytes().unwrap(),
+            [0, 0, 0, 24, 0, 0, 0, 0, 0, 0, 0, 1, 2, 0, 1]
+        );
+    }
+    #[test]
+    fn test_inspection() {
+        let inspection = PacketType::Inspection {
+            packet_id: 2,
+            typ: 1,
+            sensor_id: 3,
+            data_type: 2,
+            gyro: (1.0, 0.0, 0.0),
+            gyro_accuracy: 0,
+            accel: (0.0, 0.0, -1.0),
+            accel_accuracy: 0,
+            mag: (0.0, 0.0, 0.0),
+            mag_accuracy: 0,
+        };
+        let data: Vec<u8> = vec![
+            0, 0, 0, 105, 0, 0, 0, 0, 0, 0, 0, 2, 1, 3, 2, 63, 128, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 191, 128, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        ];
+
+        assert_eq!(inspection.to_bytes().unwrap(), data);
+    }
+    #[test]
+    fn test_write_to() {
+        let mut buffer = PacketBuffer::default();
+        let acc = PacketType::Acceleration {
+            packet_id: 16,
+            vector: (0.1, 0.5, 0.9),
+            sensor_id: Some(32),
+        };
+        assert_eq!(acc.write_to(&mut buffer).unwrap(), acc.to_bytes().unwrap());
+
+        // A shorter packet doesn't keep the tail of the previous one.
+        let ua = PacketType::UserAction {
+            packet_id: 1,
+            typ: 3,
+        };
+        assert_eq!(
+            ua.write_to(&mut buffer).unwrap(),
+            [0, 0, 0, 21, 0, 0, 0, 0, 0, 0, 0, 1, 3]
+        );
+    }
+    #[test]
+    fn test_bundle() {
+        let mut bundle = Bundle::default();
+        let ua = PacketType::UserAction {
+            packet_id: 1,
+            typ: 3,
+        };
+        let si = PacketType::SensorInfo {
+            packet_id: 2,
+            sensor_id: 1,
+            sensor_status: 1,
+            sensor_type: 0,
+        };
+        assert_eq!(bundle.push(&ua).unwrap(), 7);
+        assert_eq!(bundle.push(&si).unwrap(), 9);
+        assert_eq!(
+            bundle.push(&PacketType::Ping { id: 5 }),
+            Err(ProtocolError::NotBundleable)
+        );
+        assert_eq!(bundle.len(), 16);
+
+        let data: Vec<u8> = vec![
+            0, 0, 0, 100, 0, 0, 0, 0, 0, 0, 0, 9, 0, 5, 0, 0, 0, 21, 3, 0, 7, 0, 0, 0, 15, 1, 1, 0,
+        ];
+        assert_eq!(bundle.take(9), data);
+        assert!(bundle.is_empty());
+    }
+    #[test]
     fn test_handshake_response() {
         let hr = PacketType::HandshakeResponse;
         assert_eq!(hr.to_bytes().unwrap(), "\x03Hey".as_bytes());
     }
+    #[test]
+    fn test_server_protocol_version() {
+        assert_eq!(server_protocol_version(b"\x03Hey OVR =D 5"), Some(5));
+        // The server pads the reply with zeros.
+        assert_eq!(
+            server_protocol_version(b"\x03Hey OVR =D 12\0\0\0"),
+            Some(12)
+        );
+        assert_eq!(server_protocol_version(b"\x03Hey OVR =D"), None);
+        assert_eq!(server_protocol_version(b"\x03Hey there 5"), None);
+    }
+    #[test]
+    fn test_slime_string() {
+        let handshake = PacketType::Handshake {
+            packet_id: 1,
+            board: 0,
+            imu: 0,
+            mcu_type: 0,
+            imu_info: (0, 0, 0),
+            build: 9,
+            firmware: SlimeString::new("Jöycon 左").unwrap(),
+            mac_address: [0; 6],
+        };
+        let bytes = handshake.to_bytes().unwrap();
+        // The length is in bytes, not characters.
+        assert_eq!(bytes[40] as usize, "Jöycon 左".len());
+        let (_, read) = PacketType::from_bytes((&bytes, 0)).unwrap();
+        assert_eq!(read, handshake);
+
+        let long = "a".repeat(256);
+        assert_eq!(
+            SlimeString::new(&long),
+            Err(ProtocolError::StringTooLong(256))
+        );
+        assert_eq!(SlimeString::truncated(&long).as_bytes().len(), 255);
+        // Cut before the character that doesn't fit instead of through it.
+        let wide = "a".repeat(254) + "ö";
+        assert_eq!(
+            SlimeString::truncated(&wide).as_bytes(),
+            "a".repeat(254).as_bytes()
+        );
+    }
 }
@@ -0,0 +1,198 @@
+//! "Export diagnostics": what helps with a bug report, zipped into one file to attach to it.
+
+use std::{
+    fmt::Write as _,
+    fs::{self, File},
+    io::{self, Write},
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use directories::UserDirs;
+use itertools::Itertools;
+use slimevr_wrangler_core::joycon::{self, Queue, Stage};
+use slimevr_wrangler_core::settings;
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+use crate::{design_name, euler_readout, format_time, link_text, logging, MainState};
+
+// Only the latest sessions, the log keeps growing.
+const SESSION_LOG_LINES: usize = 50;
+
+/// Writes the diagnostics of `state` into a new zip in the downloads folder, or next to the
+/// settings without one. Returns where it went.
+pub fn export(state: &MainState) -> io::Result<PathBuf> {
+    let dir = UserDirs::new()
+        .and_then(|dirs| dirs.download_dir().map(|dir| dir.to_owned()))
+        .or_else(settings::config_dir)
+        .ok_or(io::ErrorKind::NotFound)?;
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let path = dir.join(format!("wrangler-diagnostics-{time}.zip"));
+    let mut zip = ZipWriter::new(File::create(&path)?);
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    for (name, contents) in files(state) {
+        zip.start_file(name, options)?;
+        zip.write_all(contents.as_bytes())?;
+    }
+    zip.finish()?;
+    Ok(path)
+}
+
+fn files(state: &MainState) -> Vec<(&'static str, String)> {
+    let settings = state.settings.load();
+    let mut info = format!(
+        "SlimeVR Wrangler {}\nOS: {} {}\nRunning for: {}\nServer: {} ({:?})\n",
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        joycon::format_duration(state.session.uptime()),
+        settings.address,
+        state.server_connected,
+    );
+    if !settings.relay_address.is_empty() {
+        info.push_str("Through a relay\n");
+    }
+    writeln!(
+        info,
+        "Angles: {} order, {}, {}",
+        settings.euler_order,
+        settings.euler_handedness,
+        if settings.euler_radians {
+            "radians"
+        } else {
+            "degrees"
+        }
+    )
+    .ok();
+
+    let mut devices = String::new();
+    for status in &state.joycon_boxes.statuses {
+        writeln!(
+            devices,
+            "{}: {}, {}, battery {:?}, {} reports/s, {}, {} bytes/s, firmware {}, calibration {:?}, rotation {}",
+            status.serial_number,
+            design_name(&status.design),
+            status.status,
+            status.battery,
+            status.rate,
+            link_text(status).unwrap_or_else(|| "link quality unknown".to_string()),
+            status.bandwidth,
+            status
+                .firmware
+                .map_or("unknown".to_string(), |firmware| firmware.to_string()),
+            status.calibration,
+            euler_readout(&status.orientation, &settings),
+        )
+        .ok();
+    }
+    for device in state.session.devices() {
+        writeln!(devices, "Session of {}: {device}", device.serial_number).ok();
+    }
+
+    let errors = state
+        .errors
+        .iter()
+        .map(|report| {
+            let mut line = format!(
+                "{} {}: {}",
+                format_time(report.time),
+                report.source,
+                report.message
+            );
+            if let Some(suggestion) = &report.suggestion {
+                line = format!("{line}. {suggestion}");
+            }
+            line + "\n"
+        })
+        .collect();
+
+    let self_test = state
+        .self_test
+        .iter()
+        .chain(&state.server_probe)
+        .chain(&state.network_diagnosis)
+        .map(|check| format!("{} {}: {}\n", check.outcome(), check.name, check.detail))
+        .collect();
+
+    let mut packets = String::new();
+    let log = &state.handshake_log;
+    let sent = log
+        .handshake
+        .iter()
+        .map(|p| ("Sent Handshake".to_string(), p))
+        .chain(
+            log.sensor_info
+                .iter()
+                .map(|(id, p)| (format!("Sent SensorInfo {id}"), p)),
+        )
+        .chain(log.reply.iter().map(|p| ("Server reply".to_string(), p)));
+    for (name, packet) in sent {
+        let fields = packet
+            .fields
+            .iter()
+            .map(|(field, value)| format!("{field}: {value}"))
+            .join(", ");
+        writeln!(
+            packets,
+            "{name}, {:.1}s ago: {fields}\n{}",
+            packet.time.elapsed().as_secs_f32(),
+            packet.hex()
+        )
+        .ok();
+    }
+    let dropped = Queue::ALL
+        .iter()
+        .map(|&queue| format!("{queue:?} {}", joycon::dropped(queue)))
+        .join(", ");
+    writeln!(packets, "Dropped messages: {dropped}").ok();
+    if state.profile.is_empty() {
+        writeln!(
+            packets,
+            "CPU time: not measured, only while the settings are open"
+        )
+        .ok();
+    }
+    for (thread, total, stages) in &state.profile {
+        let stages = Stage::ALL
+            .iter()
+            .zip(stages)
+            .map(|(stage, share)| format!("{} {:.1}%", stage.name(), share * 100.0))
+            .join(", ");
+        writeln!(
+            packets,
+            "{thread}: {:.1}% of a core ({stages})",
+            total * 100.0
+        )
+        .ok();
+    }
+
+    let sessions = joycon::session_log_file()
+        .and_then(|file| fs::read_to_string(file).ok())
+        .map(|log| {
+            let lines: Vec<_> = log.lines().collect();
+            let start = lines.len().saturating_sub(SESSION_LOG_LINES);
+            lines[start..]
+                .iter()
+                .map(|line| format!("{line}\n"))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let log = logging::recent()
+        .into_iter()
+        .map(|line| line + "\n")
+        .collect();
+
+    vec![
+        ("info.txt", info),
+        ("config.json", settings.report_json()),
+        ("devices.txt", devices),
+        ("errors.txt", errors),
+        ("self-test.txt", self_test),
+        ("packets.txt", packets),
+        ("sessions.jsonl", sessions),
+        ("log.txt", log),
+    ]
+}
@@ -0,0 +1,80 @@
+use std::path::PathBuf;
+
+use clap::{value_parser, Arg, ArgAction, Command};
+
+/// What wrangler was started with.
+pub struct Args {
+    pub config: Option<PathBuf>,
+    pub server: Option<String>,
+    pub headless: bool,
+    pub json: bool,
+    #[cfg(feature = "relay")]
+    pub relay: Option<String>,
+}
+
+/// Reads the command line, exits with a message for one that doesn't make sense, or after
+/// printing `--help`.
+pub fn parse() -> Args {
+    let command = Command::new("slimevr-wrangler")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Use Joy-Cons as SlimeVR trackers")
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .value_name("FILE")
+                .value_parser(value_parser!(PathBuf))
+                .help("Read and save the settings in FILE instead of the usual config.json"),
+        )
+        .arg(
+            Arg::new("server")
+                .long("server")
+                .value_name("ADDRESS")
+                .help("Address of the SlimeVR server for this run, the settings keep theirs"),
+        )
+        .arg(
+            Arg::new("headless")
+                .long("headless")
+                .action(ArgAction::SetTrue)
+                .help("Run without a window, printing what happens"),
+        )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .action(ArgAction::SetTrue)
+                .requires("headless")
+                .help("Print one JSON object per line without a window"),
+        )
+        // Read by the core, see `joycon::default_sources`.
+        .arg(
+            Arg::new("sources")
+                .value_name("test|stress[=COUNT]")
+                .num_args(0..)
+                .value_parser(fake_source)
+                .help("Add fake controllers, for testing"),
+        );
+    #[cfg(feature = "relay")]
+    let command = command.arg(
+        Arg::new("relay")
+            .long("relay")
+            .value_name("LISTEN")
+            .help("Run as the relay for wranglers on another network, listening on LISTEN"),
+    );
+    let matches = command.get_matches();
+    Args {
+        config: matches.get_one::<PathBuf>("config").cloned(),
+        server: matches.get_one::<String>("server").cloned(),
+        headless: matches.get_flag("headless"),
+        json: matches.get_flag("json"),
+        #[cfg(feature = "relay")]
+        relay: matches.get_one::<String>("relay").cloned(),
+    }
+}
+
+fn fake_source(arg: &str) -> Result<String, String> {
+    let count = arg.strip_prefix("stress=");
+    if arg == "test" || arg == "stress" || count.map_or(false, |c| c.parse::<usize>().is_ok()) {
+        Ok(arg.into())
+    } else {
+        Err("expected test, stress or stress=<count>".into())
+    }
+}
@@ -0,0 +1,317 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use itertools::Itertools;
+use serde_json::{json, Value};
+use slimevr_wrangler_core::joycon::{
+    self, Battery, DeviceStatus, ErrorReport, Queue, ServerStatus,
+};
+use slimevr_wrangler_core::settings;
+
+use crate::launcher::{ServerProcess, SERVER_SETTLE};
+use crate::link_text;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+const STATS_INTERVAL: Duration = Duration::from_secs(5);
+// The connection is diagnosed once the server has been silent this long.
+const DIAGNOSE_AFTER: Duration = Duration::from_secs(15);
+// Without a window to close, the session is also printed this often.
+const SESSION_INTERVAL: Duration = Duration::from_secs(600);
+
+/// Runs wrangler without a window, printing what happens on stdout. With `json`, each line is
+/// one JSON object with an `event` field, for supervisors and scripts.
+pub fn run(json: bool) {
+    let settings = settings::Handler::default();
+    let mut out = Output { json };
+    let checks = joycon::self_test();
+    let stop = stop_requested();
+    let server_path = settings.load().server_path.clone();
+    let mut patience = Duration::ZERO;
+    let mut server_process = None;
+    if !server_path.is_empty() {
+        let address = settings.load().get_socket_address();
+        match ServerProcess::launch(&server_path, address) {
+            Ok(process) => {
+                if process.is_some() {
+                    patience = SERVER_SETTLE;
+                }
+                server_process = process;
+            }
+            Err(e) => out.error(&ErrorReport::new(
+                "Server",
+                format!("Could not start the SlimeVR server: {e}"),
+            )),
+        }
+    }
+    let address = settings.load().address.clone();
+    let mac_address = settings::derived_mac(settings.load().mac_address(), settings::TEST_MAC);
+    let mut probe = Some(thread::spawn(move || {
+        joycon::probe_server(&address, mac_address, patience)
+    }));
+    for check in &checks {
+        out.check(check);
+    }
+    let wrangler = joycon::Wrapper::new(settings.clone());
+    let mut known: HashMap<String, (DeviceStatus, Battery)> = HashMap::new();
+    let mut last_stats = Instant::now();
+    let mut last_dropped = [0; Queue::ALL.len()];
+    // Since when the server hasn't answered, and whether that was diagnosed yet.
+    let mut searching_since = None;
+    let mut diagnosed = false;
+    let mut diagnosis = None;
+    let mut session = joycon::SessionStats::default();
+    let mut last_session = Instant::now();
+
+    while !stop.load(Ordering::Relaxed) {
+        if probe.as_ref().map_or(false, |probe| probe.is_finished()) {
+            if let Some(Ok(check)) = probe.take().map(thread::JoinHandle::join) {
+                out.check(&check);
+            }
+        }
+        if diagnosis
+            .as_ref()
+            .map_or(false, thread::JoinHandle::is_finished)
+        {
+            if let Some(Ok(checks)) = diagnosis.take().map(thread::JoinHandle::join) {
+                for check in &checks {
+                    out.check(check);
+                }
+            }
+        }
+        // Not through a relay, the server isn't reached directly then.
+        let direct = {
+            let settings = settings.load();
+            settings.output_slimevr && settings.relay_address.is_empty()
+        };
+        if direct
+            && !diagnosed
+            && searching_since.map_or(false, |since: Instant| since.elapsed() >= DIAGNOSE_AFTER)
+        {
+            diagnosed = true;
+            let settings = settings.load();
+            let address = settings.address.clone();
+            // Another MAC, so the server doesn't move the trackers to the test socket.
+            let mac_address = settings::derived_mac(settings.mac_address(), settings::TEST_MAC);
+            diagnosis = Some(thread::spawn(move || {
+                joycon::diagnose_network(&address, mac_address)
+            }));
+        }
+        if let Some(status) = wrangler.poll_server() {
+            if status == ServerStatus::Connected {
+                searching_since = None;
+                diagnosed = false;
+            } else {
+                searching_since.get_or_insert_with(Instant::now);
+            }
+            out.emit(
+                "server",
+                json!({ "status": format!("{status:?}") }),
+                match status {
+                    ServerStatus::Connected => "Connected to the SlimeVR server".into(),
+                    ServerStatus::Unreachable => {
+                        "Nothing is listening at the server address, retrying".into()
+                    }
+                    ServerStatus::SendFailed => "Can't send to the server address, retrying".into(),
+                    _ => format!("Server connection: {status:?}"),
+                },
+            );
+        }
+        for report in wrangler.poll_errors() {
+            out.error(&report);
+        }
+        if let Some(statuses) = wrangler.poll_status() {
+            session.update(&statuses);
+            for status in &statuses {
+                let previous = known.insert(
+                    status.serial_number.clone(),
+                    (status.status, status.battery),
+                );
+                let was_connected = previous.map_or(false, |(previous, _)| {
+                    previous != DeviceStatus::Disconnected
+                });
+                let connected = status.status != DeviceStatus::Disconnected;
+                if connected && !was_connected {
+                    out.device("connected", status, "connected".into());
+                } else if !connected && was_connected {
+                    out.device("disconnected", status, "disconnected".into());
+                }
+                if previous.map_or(false, |(_, battery)| battery != status.battery) {
+                    out.device("battery", status, format!("battery {:?}", status.battery));
+                }
+            }
+            if last_stats.elapsed() >= STATS_INTERVAL {
+                last_stats = Instant::now();
+                for status in &statuses {
+                    out.device(
+                        "stats",
+                        status,
+                        format!(
+                            "{}, {} reports/s, {} bytes/s{}",
+                            status.status,
+                            status.rate,
+                            status.bandwidth,
+                            link_text(status).map_or(String::new(), |link| format!(", {link}"))
+                        ),
+                    );
+                }
+                let dropped = Queue::ALL.map(joycon::dropped);
+                if dropped != last_dropped {
+                    last_dropped = dropped;
+                    let counts = Queue::ALL.iter().zip(dropped);
+                    out.emit(
+                        "dropped",
+                        counts
+                            .clone()
+                            .map(|(queue, count)| (format!("{queue:?}"), Value::from(count)))
+                            .collect(),
+                        format!(
+                            "Dropped messages: {}",
+                            counts
+                                .filter(|(_, count)| *count > 0)
+                                .map(|(queue, count)| format!("{queue:?} {count}"))
+                                .join(", ")
+                        ),
+                    );
+                }
+            }
+        }
+        if last_session.elapsed() >= SESSION_INTERVAL {
+            last_session = Instant::now();
+            let devices = session.devices();
+            out.emit(
+                "session",
+                json!({ "uptime_secs": session.uptime().as_secs(), "devices": devices }),
+                format!(
+                    "Session of {}: {}",
+                    joycon::format_duration(session.uptime()),
+                    devices
+                        .iter()
+                        .map(|device| format!("{}: {device}", device.serial_number))
+                        .join("; ")
+                ),
+            );
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    session.write_log().ok();
+    if settings.load().server_stop_on_exit {
+        if let Some(process) = server_process {
+            process.stop();
+        }
+    }
+    settings::flush();
+}
+
+// Set once wrangler is asked to stop, with Ctrl+C or by a service manager.
+fn stop_requested() -> Arc<AtomicBool> {
+    let stop = Arc::new(AtomicBool::new(false));
+    let flag = stop.clone();
+    thread::spawn(move || {
+        let Ok(runtime) = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+        else {
+            return;
+        };
+        runtime.block_on(async {
+            #[cfg(unix)]
+            if let Ok(mut terminate) =
+                tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+                return;
+            }
+            tokio::signal::ctrl_c().await.ok();
+        });
+        flag.store(true, Ordering::Relaxed);
+    });
+    stop
+}
+
+struct Output {
+    json: bool,
+}
+impl Output {
+    fn emit(&mut self, event: &str, fields: Value, text: String) {
+        self.emit_at(SystemTime::now(), event, fields, text);
+    }
+    fn emit_at(&mut self, time: SystemTime, event: &str, mut fields: Value, text: String) {
+        if self.json {
+            fields["event"] = event.into();
+            fields["time"] = unix_millis(time).into();
+            println!("{fields}");
+        } else {
+            println!("{text}");
+        }
+    }
+    fn device(&mut self, event: &str, status: &joycon::Status, text: String) {
+        self.emit(
+            event,
+            json!({
+                "serial": status.serial_number,
+                "status": status.status.to_string(),
+                "battery": format!("{:?}", status.battery),
+                "rate": status.rate,
+                "bandwidth": status.bandwidth,
+                "drift": status.drift,
+                "lost": status.lost,
+                "rssi": status.rssi,
+                "temperature": status.temperature,
+            }),
+            format!("{}: {text}", status.serial_number),
+        );
+    }
+    fn check(&mut self, check: &joycon::CheckResult) {
+        let mut text = format!(
+            "Self-test {}: {}: {}",
+            check.outcome(),
+            check.name,
+            check.detail
+        );
+        if let Some(fix) = &check.fix {
+            text = format!("{text}. {fix}");
+        }
+        self.emit(
+            "selftest",
+            json!({
+                "name": check.name,
+                "passed": check.passed,
+                "warning": check.warning,
+                "detail": check.detail,
+                "fix": check.fix,
+            }),
+            text,
+        );
+    }
+    fn error(&mut self, report: &ErrorReport) {
+        let mut text = format!("{}: {}", report.source, report.message);
+        if let Some(suggestion) = &report.suggestion {
+            text = format!("{text}. {suggestion}");
+        }
+        self.emit_at(
+            report.time,
+            "error",
+            json!({
+                "source": report.source,
+                "message": report.message,
+                "suggestion": report.suggestion,
+            }),
+            text,
+        );
+    }
+}
+
+fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
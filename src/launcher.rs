@@ -0,0 +1,62 @@
+use std::{
+    io,
+    net::{SocketAddr, UdpSocket},
+    path::Path,
+    process::{Child, Command},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How long a server that was just started gets to open its port.
+pub const SERVER_STARTUP: Duration = Duration::from_secs(30);
+/// Once its port is open, how long the server gets to answer the first ping.
+pub const SERVER_SETTLE: Duration = Duration::from_secs(5);
+// How often the port is checked while the server starts.
+const STARTUP_POLL: Duration = Duration::from_millis(250);
+
+/// The SlimeVR server, started by wrangler.
+#[derive(Debug, Clone)]
+pub struct ServerProcess {
+    child: Arc<Mutex<Child>>,
+}
+impl ServerProcess {
+    /// Starts the server executable at `path` from its own folder, unless a local server already
+    /// listens on the port of `address`. Blocks until the server opened that port, for up to
+    /// [`SERVER_STARTUP`], and fails if it exits before.
+    pub fn launch(path: &str, address: SocketAddr) -> io::Result<Option<Self>> {
+        let port = address.port();
+        if address.ip().is_loopback() && port_taken(port) {
+            return Ok(None);
+        }
+        let path = Path::new(path);
+        let mut command = Command::new(path);
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            command.current_dir(dir);
+        }
+        let mut child = command.spawn()?;
+        let started = Instant::now();
+        while !port_taken(port) && started.elapsed() < SERVER_STARTUP {
+            if let Some(status) = child.try_wait()? {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("it exited while starting, {status}"),
+                ));
+            }
+            thread::sleep(STARTUP_POLL);
+        }
+        Ok(Some(Self {
+            child: Arc::new(Mutex::new(child)),
+        }))
+    }
+    pub fn stop(self) {
+        let mut child = self.child.lock().unwrap();
+        child.kill().ok();
+        child.wait().ok();
+    }
+}
+
+// Whether something, like a server, listens on the UDP `port` of this PC.
+fn port_taken(port: u16) -> bool {
+    UdpSocket::bind(SocketAddr::from(([0, 0, 0, 0], port))).is_err()
+}
@@ -0,0 +1,109 @@
+//! The log of what the controllers and the connection to the server did. It goes into a file per
+//! day in `logs` next to the settings file in use, and its latest lines are kept for the log
+//! panel.
+
+use std::{
+    fmt::{self, Write as _},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+use slimevr_wrangler_core::settings;
+use tracing::{
+    field::{Field, Visit},
+    Event, Level, Subscriber,
+};
+use tracing_appender::{
+    non_blocking::WorkerGuard,
+    rolling::{RollingFileAppender, Rotation},
+};
+use tracing_subscriber::{
+    filter::Targets,
+    fmt as format,
+    layer::{Context, SubscriberExt},
+    util::SubscriberInitExt,
+    Layer,
+};
+
+use crate::format_time;
+
+// As many as fit in a bug report without scrolling forever.
+const RECENT_LINES: usize = 500;
+// A day per file, older ones are deleted.
+const KEPT_FILES: usize = 7;
+
+static RECENT: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Starts logging, returns what has to be kept until exit for the file to get everything. Without
+/// a place for the files, only the latest lines are kept. Call it once the settings file is known.
+pub fn init() -> Option<WorkerGuard> {
+    let appender = settings::config_dir().and_then(|dir| {
+        RollingFileAppender::builder()
+            .rotation(Rotation::DAILY)
+            .filename_prefix("wrangler")
+            .filename_suffix("log")
+            .max_log_files(KEPT_FILES)
+            .build(dir.join("logs"))
+            .ok()
+    });
+    let (file, guard) = match appender {
+        Some(appender) => {
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            let layer = format::layer().with_writer(writer).with_ansi(false);
+            (Some(layer), Some(guard))
+        }
+        None => (None, None),
+    };
+    // Only wrangler itself, not what its libraries have to say.
+    let targets = Targets::new()
+        .with_target("slimevr_wrangler", Level::INFO)
+        .with_target("slimevr_wrangler_core", Level::INFO);
+    tracing_subscriber::registry()
+        .with(targets)
+        .with(file)
+        .with(Recent)
+        .try_init()
+        .ok();
+    guard
+}
+
+/// The latest lines of the log, oldest first.
+pub fn recent() -> Vec<String> {
+    RECENT.lock().unwrap().clone()
+}
+
+struct Recent;
+impl<S: Subscriber> Layer<S> for Recent {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut line = format!(
+            "{} {}:",
+            format_time(SystemTime::now()),
+            event.metadata().level()
+        );
+        event.record(&mut Fields(&mut line));
+        let mut recent = RECENT.lock().unwrap();
+        if recent.len() >= RECENT_LINES {
+            recent.remove(0);
+        }
+        recent.push(line);
+    }
+}
+
+// The message as it is, the other fields as `name=value` after it.
+struct Fields<'a>(&'a mut String);
+impl Visit for Fields<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            write!(self.0, " {value}").ok();
+        } else {
+            write!(self.0, " {}={value}", field.name()).ok();
+        }
+    }
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            write!(self.0, " {value:?}").ok();
+        } else {
+            write!(self.0, " {}={value:?}", field.name()).ok();
+        }
+    }
+}
@@ -1,39 +1,85 @@
 #![deny(clippy::all)]
 
 use iced::{
-    executor,
+    executor, keyboard, subscription,
     theme::{self, Theme},
     time,
     widget::{
-        button, canvas, checkbox, container, horizontal_space, scrollable, slider, text,
+        button, canvas, checkbox, container, horizontal_space, pick_list, scrollable, slider, text,
         text_input, Column, Container, Row, Scrollable, Svg,
     },
-    window, Alignment, Application, Color, Command, Element, Font, Length, Settings, Subscription,
+    window, Alignment, Application, Color, Command, Element, Event, Font, Length, Settings,
+    Subscription,
 };
 
 use circle::circle;
 use iced_aw::Grid;
-use joycon::{Battery, DeviceStatus, ServerStatus};
+use nalgebra::UnitQuaternion;
 use needle::Needle;
-use settings::WranglerSettings;
+use slimevr_wrangler_core::joycon::{
+    self, Action, Battery, CalibrationState, CalibrationStep, DeviceStatus, ErrorReport,
+    JoyconDesign, JoyconDesignType, ServerStatus, Stage, ThreadProfile,
+};
+use slimevr_wrangler_core::settings::{
+    self, ButtonCombo, EulerOrder, FrameMode, Fusion, Handedness, HomeLight, LightState, Output,
+    PlayerLights, WranglerSettings,
+};
 use std::{
+    collections::BTreeSet,
     io::{
         self,
         prelude::{Read, Write},
     },
     net::SocketAddr,
-    time::{Duration, Instant},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
-mod joycon;
 mod steam_blacklist;
 use steam_blacklist as blacklist;
+mod bug_report;
 mod circle;
+mod cli;
+mod headless;
+mod launcher;
+mod logging;
 mod needle;
-mod settings;
 mod style;
+mod svg;
 mod update;
 
 const WINDOW_SIZE: (u32, u32) = (980, 700);
+const COMPACT_WINDOW_SIZE: (u32, u32) = (420, 240);
+const MAX_TOASTS: usize = 5;
+// Errors kept for the diagnostics export.
+const MAX_ERRORS: usize = 200;
+const TOAST_DURATION: Duration = Duration::from_secs(20);
+// The connection is diagnosed once the server has been silent this long.
+const DIAGNOSE_AFTER: Duration = Duration::from_secs(15);
+// How long "Find servers" waits for answers.
+const DISCOVERY_TIME: Duration = Duration::from_secs(2);
+// Time constant of the smoothing between the shown and the latest rotation of a tracker.
+const INTERPOLATION_TIME: Duration = Duration::from_millis(40);
+const PROFILE_INTERVAL: Duration = Duration::from_secs(1);
+// Degrees the yaw trim slider on a tracker goes either way.
+const MAX_YAW_TRIM: f64 = 30.0;
+// Prediction further ahead than a Bluetooth round trip only overshoots.
+const MAX_PREDICTION_MS: u32 = 50;
+// Shares of lost reports and signal strengths in dBm where a link is shown as weak or poor.
+const WEAK_LINK_LOSS: f64 = 0.02;
+const POOR_LINK_LOSS: f64 = 0.1;
+const WEAK_LINK_RSSI: i16 = -75;
+const POOR_LINK_RSSI: i16 = -85;
+
+// Okabe-Ito palette, tells apart well with all kinds of color blindness.
+const ACCENT_COLORS: [(u8, u8, u8); 8] = [
+    (0xe6, 0x9f, 0x00),
+    (0x56, 0xb4, 0xe9),
+    (0x00, 0x9e, 0x73),
+    (0xf0, 0xe4, 0x42),
+    (0x00, 0x72, 0xb2),
+    (0xd5, 0x5e, 0x00),
+    (0xcc, 0x79, 0xa7),
+    (0x99, 0x99, 0x99),
+];
 
 pub const ICONS: Font = Font::External {
     name: "Icons",
@@ -41,19 +87,42 @@ pub const ICONS: Font = Font::External {
 };
 pub const ICON: &[u8; 16384] = include_bytes!("../assets/icon_64.rgba8");
 
+// The argument after `name`, like the address in `--server 192.168.1.2:6969`.
 pub fn main() -> iced::Result {
+    let args = cli::parse();
+    if let Some(file) = args.config {
+        settings::use_config_file(file);
+    }
+    let _log = logging::init();
+    if let Some(address) = args.server {
+        settings::override_server(address);
+    }
+    #[cfg(feature = "relay")]
+    if let Some(listen) = args.relay {
+        if let Err(e) = joycon::relay::serve(&listen, settings::Handler::default()) {
+            println!("The relay stopped: {e}");
+        }
+        settings::flush();
+        return Ok(());
+    }
+    if args.headless {
+        headless::run(args.json);
+        return Ok(());
+    }
     /*
     let rgba8 = image_rs::io::Reader::open("assets/icon.png").unwrap().decode().unwrap().to_rgba8();
     std::fs::write("assets/icon_64.rgba8", rgba8.into_raw());
     */
     let settings = Settings {
         window: window::Settings {
-            min_size: Some(WINDOW_SIZE),
+            min_size: Some(COMPACT_WINDOW_SIZE),
             size: WINDOW_SIZE,
             icon: window::icon::from_rgba(ICON.to_vec(), 64, 64).ok(),
             ..window::Settings::default()
         },
         antialiasing: true,
+        // Closing goes through Message::CloseRequested, to stop the server first.
+        exit_on_close_request: false,
         ..Settings::default()
     };
     match MainState::run(settings) {
@@ -71,17 +140,97 @@ pub fn main() -> iced::Result {
 #[derive(Debug, Clone)]
 enum Message {
     SettingsPressed,
+    PairingPressed,
+    CompactToggled,
+    AlignAllPressed,
     Tick(Instant),
     Dot(Instant),
+    ToastDismissed(usize),
     AddressChange(String),
     UpdateFound(Option<String>),
     UpdatePressed,
     BlacklistChecked(blacklist::BlacklistResult),
     BlacklistFixPressed,
+    ServerLaunched(Result<Option<launcher::ServerProcess>, String>),
+    ServerProbed(joycon::CheckResult),
+    DiagnosePressed,
+    PauseToggled,
+    ExportDiagnosticsPressed,
+    CopyLogPressed,
+    NetworkDiagnosed(Vec<joycon::CheckResult>),
+    DiscoverPressed,
+    ServersDiscovered(Result<Vec<SocketAddr>, String>),
+    SelfTestDismissed,
     JoyconRotate(String, bool),
     JoyconScale(String, f64),
+    JoyconYawTrim(String, f64),
+    JoyconCaptureMounting(String),
+    JoyconClearMounting(String),
+    JoyconTwoPoseStarted(String),
+    JoyconTwoPoseCaptured,
+    JoyconTwoPoseCancelled,
+    JoyconLevel(String),
+    JoyconIdentify(String),
+    JoyconFrozen(String, bool),
+    JoyconCalibrate(String),
+    JoyconWriteCalibration(String),
+    JoyconRestoreCalibration(String),
+    JoyconAccelCalibration(String, bool),
+    JoyconAccelCalibrationReset(String),
+    JoyconNickname(String, String),
+    JoyconGroup(String, String),
+    GroupAligned(String),
+    GroupPauseToggled(String),
+    GroupIdentify(String),
+    GroupProfileLoaded(String, String),
+    JoyconAccent(String, Option<u8>),
+    JoyconBlocked(String, bool),
+    JoyconFrameMode(String, FrameMode),
+    JoyconFusion(String, Fusion),
+    JoyconFusionGain(String, f64),
+    JoyconOutput(String, Output, bool),
+    SearchChanged(String),
+    FilterChanged(DeviceFilter),
     SettingsResetToggled(bool),
     SettingsIdsToggled(bool),
+    SettingsSeparateDevicesToggled(bool),
+    SettingsSleepChanged(u32),
+    SettingsPredictionChanged(u32),
+    SettingsPowerOffChanged(u32),
+    SettingsVrPauseToggled(bool),
+    SettingsVrProcessChanged(String),
+    SettingsLowBatteryRumbleToggled(bool),
+    SettingsReaderPriorityToggled(bool),
+    SettingsReaderCoreChanged(ReaderCore),
+    SettingsSharedAccessToggled(bool),
+    SettingsInspectionToggled(bool),
+    SettingsLowBandwidthToggled(bool),
+    SettingsOutputSmoothingToggled(bool),
+    SettingsComboProfileChanged(ButtonCombo),
+    SettingsComboPauseChanged(ButtonCombo),
+    SettingsComboResetChanged(ButtonCombo),
+    ProfileNameChanged(String),
+    ProfileSaved,
+    ProfileLoaded(String),
+    ProfileDeleted(String),
+    SettingsPlayerLightsChanged(LightState, PlayerLights),
+    SettingsHomeLightChanged(LightState, HomeLight),
+    SettingsGyroPerformanceToggled(bool),
+    SettingsAccelFilterToggled(bool),
+    SettingsFusionChanged(Fusion),
+    SettingsAutoLevelToggled(bool),
+    SettingsGyroBiasToggled(bool),
+    SettingsEulerOrderChanged(EulerOrder),
+    SettingsEulerRadiansToggled(bool),
+    SettingsEulerHandednessChanged(Handedness),
+    SettingsRefreshRateChanged(u32),
+    SettingsTelemetryAddressChanged(String),
+    SettingsOutputToggled(Output, bool),
+    SettingsOscOutputAddressChanged(String),
+    SettingsTelemetryRateChanged(u32),
+    SettingsServerPathChanged(String),
+    SettingsServerStopToggled(bool),
+    CloseRequested,
 }
 
 #[derive(Default)]
@@ -90,8 +239,45 @@ struct MainState {
     joycon_boxes: JoyconBoxes,
     search_dots: usize,
     settings_show: bool,
+    pairing: Option<Pairing>,
+    search: String,
+    device_filter: DeviceFilter,
     server_connected: ServerStatus,
+    /// Tracker data is paused, by the GUI or anything else.
+    paused: bool,
+    paused_groups: BTreeSet<String>,
+    /// Paused because no VR session runs, see [`WranglerSettings::vr_process`].
+    vr_idle: bool,
+    handshake_log: joycon::HandshakeLog,
+    /// The latest lines of the log, refreshed with the search dots.
+    log: Vec<String>,
+    toasts: Vec<Toast>,
+    /// Every error of the session, up to [`MAX_ERRORS`].
+    errors: Vec<ErrorReport>,
+    /// Where the diagnostics were exported to, or why that failed.
+    diagnostics_export: Option<String>,
+    server_process: Option<launcher::ServerProcess>,
+    two_pose: Option<(String, MountingStep)>,
     server_address: String,
+    /// Address of a SlimeVR server set up on this PC, looked up once at startup.
+    local_server: Option<String>,
+    profile_sample: Option<(Instant, Vec<ThreadProfile>)>,
+    // Thread name, share of a core in total and per stage.
+    profile: Vec<(String, f64, [f64; Stage::ALL.len()])>,
+    self_test: Vec<joycon::CheckResult>,
+    server_probe: Option<joycon::CheckResult>,
+    /// The last network diagnosis, until the server answers.
+    network_diagnosis: Vec<joycon::CheckResult>,
+    diagnosing: bool,
+    discovering: bool,
+    /// Servers that answered the last search on the network, or why it failed.
+    discovered: Option<Result<Vec<SocketAddr>, String>>,
+    /// Since when the server hasn't answered, and whether that was diagnosed on its own yet.
+    searching_since: Option<Instant>,
+    diagnosed: bool,
+    self_test_dismissed: bool,
+    session: joycon::SessionStats,
+    profile_name: String,
 
     settings: settings::Handler,
     update_found: Option<String>,
@@ -104,16 +290,32 @@ impl Application for MainState {
     type Theme = Theme;
 
     fn new(_: Self::Flags) -> (Self, Command<Self::Message>) {
-        let mut new = Self::default();
+        let mut new = Self {
+            self_test: joycon::self_test(),
+            local_server: settings::local_server_address(),
+            ..Self::default()
+        };
         new.joycon = Some(joycon::Wrapper::new(new.settings.clone()));
         new.server_address = format!("{}", new.settings.load().get_socket_address());
-        (
-            new,
-            Command::batch(vec![
-                Command::perform(update::check_updates(), Message::UpdateFound),
-                Command::perform(blacklist::check_blacklist(), Message::BlacklistChecked),
-            ]),
-        )
+        let server_path = new.settings.load().server_path.clone();
+        // The server is probed once it started, or right away without one to start.
+        let server = if server_path.is_empty() {
+            new.probe_server(Duration::ZERO)
+        } else {
+            Command::perform(
+                launch_server(server_path, new.settings.load().get_socket_address()),
+                Message::ServerLaunched,
+            )
+        };
+        let mut commands = vec![
+            Command::perform(update::check_updates(), Message::UpdateFound),
+            Command::perform(blacklist::check_blacklist(), Message::BlacklistChecked),
+            server,
+        ];
+        if new.settings.load().compact {
+            commands.push(window::resize(COMPACT_WINDOW_SIZE.0, COMPACT_WINDOW_SIZE.1));
+        }
+        (new, Command::batch(commands))
     }
 
     fn title(&self) -> String {
@@ -127,19 +329,81 @@ impl Application for MainState {
         match message {
             Message::SettingsPressed => {
                 self.settings_show = !self.settings_show;
+                self.pairing = None;
+            }
+            Message::CompactToggled => {
+                let compact = !self.settings.load().compact;
+                self.settings.change(|ws| ws.compact = compact);
+                let (width, height) = if compact {
+                    COMPACT_WINDOW_SIZE
+                } else {
+                    WINDOW_SIZE
+                };
+                return window::resize(width, height);
             }
-            Message::Tick(_time) => {
+            Message::PairingPressed => {
+                self.pairing = match self.pairing {
+                    Some(_) => None,
+                    None => Some(Pairing::new(&self.joycon_boxes.statuses)),
+                };
+                self.settings_show = false;
+            }
+            Message::Tick(time) => {
                 if let Some(ref ji) = self.joycon {
                     if let Some(res) = ji.poll_status() {
-                        self.joycon_boxes.statuses = res;
+                        self.session.update(&res);
+                        self.joycon_boxes.latest = res;
                     }
                     if let Some(connected) = ji.poll_server() {
                         self.server_connected = connected;
+                        if connected == ServerStatus::Connected {
+                            self.network_diagnosis.clear();
+                            self.searching_since = None;
+                            self.diagnosed = false;
+                        } else {
+                            self.searching_since.get_or_insert_with(Instant::now);
+                        }
+                    }
+                    self.paused = ji.paused();
+                    self.paused_groups = ji.paused_groups();
+                    self.vr_idle = ji.vr_idle();
+                    if let Some(log) = ji.poll_handshake() {
+                        self.handshake_log = log;
+                    }
+                    for report in ji.poll_errors() {
+                        self.push_toast(report);
                     }
                 }
+                self.joycon_boxes.interpolate(time);
+                if self.settings_show {
+                    self.sample_profile(time);
+                } else if self.profile_sample.take().is_some() {
+                    joycon::set_profiling(false);
+                    self.profile.clear();
+                }
+                self.toasts
+                    .retain(|toast| toast.shown.elapsed() < TOAST_DURATION);
+            }
+            Message::ToastDismissed(index) => {
+                if index < self.toasts.len() {
+                    self.toasts.remove(index);
+                }
             }
             Message::Dot(_time) => {
                 self.search_dots = (self.search_dots + 1) % 4;
+                self.log = logging::recent();
+                let settings = self.settings.load();
+                // Not through a relay, the server isn't reached directly then.
+                let direct = settings.output_slimevr && settings.relay_address.is_empty();
+                if direct
+                    && !self.diagnosed
+                    && self
+                        .searching_since
+                        .map_or(false, |since| since.elapsed() >= DIAGNOSE_AFTER)
+                {
+                    self.diagnosed = true;
+                    return self.diagnose();
+                }
             }
             Message::AddressChange(value) => {
                 self.settings.change(|ws| ws.address = value);
@@ -159,6 +423,65 @@ impl Application for MainState {
                     blacklist::BlacklistResult::info("Updating steam config file.....");
                 return Command::perform(blacklist::update_blacklist(), Message::BlacklistChecked);
             }
+            Message::ServerLaunched(result) => {
+                let patience = match result {
+                    Ok(Some(process)) => {
+                        self.server_process = Some(process);
+                        launcher::SERVER_SETTLE
+                    }
+                    Ok(None) => Duration::ZERO,
+                    Err(e) => {
+                        self.push_toast(
+                            ErrorReport::new(
+                                "Server",
+                                format!("Could not start the SlimeVR server: {e}"),
+                            )
+                            .suggest("Check the server executable in the settings."),
+                        );
+                        Duration::ZERO
+                    }
+                };
+                return self.probe_server(patience);
+            }
+            Message::ServerProbed(check) => {
+                self.server_probe = Some(check);
+            }
+            Message::DiagnosePressed => {
+                return self.diagnose();
+            }
+            Message::ExportDiagnosticsPressed => {
+                self.diagnostics_export = Some(match bug_report::export(self) {
+                    Ok(path) => format!("Saved to {}", path.display()),
+                    Err(e) => format!("Could not export the diagnostics: {e}"),
+                });
+            }
+            Message::CopyLogPressed => {
+                return iced::clipboard::write(self.log.join("\n"));
+            }
+            Message::DiscoverPressed if !self.discovering => {
+                self.discovering = true;
+                // Another MAC, so the server doesn't move the trackers to the search socket. Always
+                // the same one, so each search doesn't leave a device behind on the server.
+                let mac_address =
+                    settings::derived_mac(self.settings.load().mac_address(), settings::TEST_MAC);
+                return Command::perform(discover_servers(mac_address), Message::ServersDiscovered);
+            }
+            Message::DiscoverPressed => {}
+            Message::ServersDiscovered(servers) => {
+                self.discovering = false;
+                self.discovered = Some(servers);
+            }
+            Message::NetworkDiagnosed(checks) => {
+                self.diagnosing = false;
+                // A new problem shows up again if the last ones were dismissed.
+                if checks.iter().any(|check| !check.passed) {
+                    self.self_test_dismissed = false;
+                }
+                self.network_diagnosis = checks;
+            }
+            Message::SelfTestDismissed => {
+                self.self_test_dismissed = true;
+            }
             Message::JoyconRotate(serial_number, direction) => {
                 self.settings.change(|ws| {
                     ws.joycon_rotation_add(serial_number, if direction { 90 } else { -90 });
@@ -168,12 +491,290 @@ impl Application for MainState {
                 self.settings
                     .change(|ws| ws.joycon_scale_set(serial_number, scale));
             }
+            Message::JoyconYawTrim(serial_number, trim) => {
+                self.settings
+                    .change(|ws| ws.joycon_yaw_trim_set(serial_number, trim));
+            }
+            Message::JoyconCaptureMounting(serial_number) => {
+                if let Some(ref ji) = self.joycon {
+                    ji.action(Action::CaptureMounting(serial_number));
+                }
+            }
+            Message::PauseToggled => {
+                if let Some(ref ji) = self.joycon {
+                    self.paused = !self.paused;
+                    ji.set_paused(self.paused);
+                }
+            }
+            Message::AlignAllPressed => {
+                if let Some(ref ji) = self.joycon {
+                    ji.action(Action::AlignAll);
+                }
+            }
+            Message::JoyconClearMounting(serial_number) => {
+                self.settings.change(|ws| {
+                    ws.joycon_mounting_set(serial_number, UnitQuaternion::identity());
+                });
+            }
+            Message::JoyconTwoPoseStarted(serial_number) => {
+                self.two_pose = Some((serial_number, MountingStep::Standing));
+            }
+            Message::JoyconTwoPoseCaptured => {
+                if let (Some((serial_number, step)), Some(ref ji)) =
+                    (self.two_pose.take(), &self.joycon)
+                {
+                    match step {
+                        MountingStep::Standing => {
+                            ji.action(Action::CaptureStanding(serial_number.clone()));
+                            self.two_pose = Some((serial_number, MountingStep::Bent));
+                        }
+                        MountingStep::Bent => ji.action(Action::CaptureBent(serial_number)),
+                    }
+                }
+            }
+            Message::JoyconTwoPoseCancelled => {
+                self.two_pose = None;
+            }
             Message::SettingsResetToggled(new) => {
                 self.settings.change(|ws| ws.send_reset = new);
             }
             Message::SettingsIdsToggled(new) => {
                 self.settings.change(|ws| ws.keep_ids = new);
             }
+            Message::SettingsSeparateDevicesToggled(new) => {
+                self.settings.change(|ws| ws.separate_devices = new);
+            }
+            Message::SettingsSleepChanged(minutes) => {
+                self.settings.change(|ws| ws.sleep_minutes = minutes);
+            }
+            Message::SettingsPredictionChanged(ms) => {
+                self.settings.change(|ws| ws.prediction_ms = ms);
+            }
+            Message::SettingsPowerOffChanged(minutes) => {
+                self.settings.change(|ws| ws.power_off_minutes = minutes);
+            }
+            Message::SettingsVrPauseToggled(new) => {
+                let process = if new { settings::STEAMVR_PROCESS } else { "" };
+                self.settings
+                    .change(|ws| ws.vr_process = process.to_owned());
+            }
+            Message::SettingsVrProcessChanged(process) => {
+                self.settings.change(|ws| ws.vr_process = process);
+            }
+            Message::SettingsLowBatteryRumbleToggled(rumble) => {
+                self.settings.change(|ws| ws.low_battery_rumble = rumble);
+            }
+            Message::SettingsReaderPriorityToggled(priority) => {
+                self.settings.change(|ws| ws.reader_priority = priority);
+            }
+            Message::SettingsReaderCoreChanged(ReaderCore(core)) => {
+                self.settings.change(|ws| ws.reader_core = core);
+            }
+            Message::SettingsInspectionToggled(inspection) => {
+                self.settings.change(|ws| ws.inspection = inspection);
+            }
+            Message::SettingsLowBandwidthToggled(low_bandwidth) => {
+                self.settings.change(|ws| ws.low_bandwidth = low_bandwidth);
+            }
+            Message::SettingsOutputSmoothingToggled(output_smoothing) => {
+                self.settings
+                    .change(|ws| ws.output_smoothing = output_smoothing);
+            }
+            Message::SettingsSharedAccessToggled(shared) => {
+                self.settings.change(|ws| ws.shared_access = shared);
+            }
+            Message::SettingsComboProfileChanged(combo) => {
+                self.settings.change(|ws| ws.combo_next_profile = combo);
+            }
+            Message::SettingsComboPauseChanged(combo) => {
+                self.settings.change(|ws| ws.combo_pause = combo);
+            }
+            Message::SettingsComboResetChanged(combo) => {
+                self.settings.change(|ws| ws.combo_reset = combo);
+            }
+            Message::ProfileNameChanged(name) => {
+                self.profile_name = name;
+            }
+            Message::ProfileSaved => {
+                let name = self.profile_name.trim().to_owned();
+                if !name.is_empty() {
+                    self.settings.change(|ws| ws.profile_save(name));
+                    self.profile_name.clear();
+                }
+            }
+            Message::ProfileLoaded(name) => {
+                self.settings.change(|ws| {
+                    ws.profile_load(&name);
+                });
+            }
+            Message::ProfileDeleted(name) => {
+                self.settings.change(|ws| ws.profile_delete(&name));
+            }
+            Message::SettingsPlayerLightsChanged(state, player) => {
+                self.settings
+                    .change(|ws| ws.lights.get_mut(state).player = player);
+            }
+            Message::SettingsHomeLightChanged(state, home) => {
+                self.settings
+                    .change(|ws| ws.lights.get_mut(state).home = home);
+            }
+            Message::SettingsGyroPerformanceToggled(new) => {
+                self.settings
+                    .change(|ws| ws.imu_gyro_high_performance = new);
+            }
+            Message::SettingsAccelFilterToggled(new) => {
+                self.settings.change(|ws| ws.imu_accel_wide_filter = new);
+            }
+            Message::JoyconNickname(serial_number, nickname) => {
+                self.settings
+                    .change(|ws| ws.joycon_nickname_set(serial_number, nickname));
+            }
+            Message::JoyconGroup(serial_number, group) => {
+                self.settings
+                    .change(|ws| ws.joycon_group_set(serial_number, group));
+            }
+            Message::GroupAligned(group) => {
+                if let Some(ref ji) = self.joycon {
+                    ji.action(Action::AlignGroup(group));
+                }
+            }
+            Message::GroupPauseToggled(group) => {
+                if let Some(ref ji) = self.joycon {
+                    let paused = !self.paused_groups.contains(&group);
+                    ji.set_group_paused(&group, paused);
+                    self.paused_groups = ji.paused_groups();
+                }
+            }
+            Message::GroupIdentify(group) => {
+                if let Some(ref ji) = self.joycon {
+                    for serial_number in self.settings.load().group_members(&group) {
+                        ji.action(Action::Identify(serial_number));
+                    }
+                }
+            }
+            Message::GroupProfileLoaded(group, name) => {
+                self.settings.change(|ws| {
+                    ws.profile_load_group(&name, &group);
+                });
+            }
+            Message::JoyconAccent(serial_number, accent) => {
+                self.settings
+                    .change(|ws| ws.joycon_accent_set(serial_number, accent));
+            }
+            Message::JoyconFrameMode(serial_number, frame_mode) => {
+                self.settings
+                    .change(|ws| ws.joycon_frame_mode_set(serial_number, frame_mode));
+            }
+            Message::JoyconFusion(serial_number, fusion) => {
+                self.settings
+                    .change(|ws| ws.joycon_fusion_set(serial_number, fusion));
+            }
+            Message::JoyconFusionGain(serial_number, gain) => {
+                self.settings
+                    .change(|ws| ws.joycon_fusion_gain_set(serial_number, gain));
+            }
+            Message::JoyconOutput(serial_number, output, included) => {
+                self.settings
+                    .change(|ws| ws.joycon_output_set(serial_number, output, included));
+            }
+            Message::JoyconBlocked(serial_number, blocked) => {
+                self.settings
+                    .change(|ws| ws.joycon_blocked_set(serial_number, blocked));
+            }
+            Message::SearchChanged(search) => {
+                self.search = search;
+            }
+            Message::FilterChanged(filter) => {
+                self.device_filter = filter;
+            }
+            Message::JoyconLevel(serial_number) => {
+                if let Some(ref ji) = self.joycon {
+                    ji.action(Action::Level(serial_number));
+                }
+            }
+            Message::JoyconIdentify(serial_number) => {
+                if let Some(ref ji) = self.joycon {
+                    ji.action(Action::Identify(serial_number));
+                }
+            }
+            Message::JoyconFrozen(serial_number, frozen) => {
+                if let Some(ref ji) = self.joycon {
+                    ji.action(Action::Freeze(serial_number, frozen));
+                }
+            }
+            Message::JoyconCalibrate(serial_number) => {
+                if let Some(ref ji) = self.joycon {
+                    ji.action(Action::CalibrateImu(serial_number));
+                }
+            }
+            Message::JoyconWriteCalibration(serial_number) => {
+                if let Some(ref ji) = self.joycon {
+                    ji.action(Action::WriteCalibration(serial_number));
+                }
+            }
+            Message::JoyconRestoreCalibration(serial_number) => {
+                if let Some(ref ji) = self.joycon {
+                    ji.action(Action::RestoreCalibration(serial_number));
+                }
+            }
+            Message::JoyconAccelCalibration(serial_number, start) => {
+                if let Some(ref ji) = self.joycon {
+                    ji.action(Action::CalibrateAccel(serial_number, start));
+                }
+            }
+            Message::JoyconAccelCalibrationReset(serial_number) => {
+                self.settings
+                    .change(|ws| ws.joycon_accel_calibration_set(serial_number, None));
+            }
+            Message::SettingsAutoLevelToggled(new) => {
+                self.settings.change(|ws| ws.auto_level = new);
+            }
+            Message::SettingsGyroBiasToggled(new) => {
+                self.settings.change(|ws| ws.gyro_bias_refinement = new);
+            }
+            Message::SettingsEulerOrderChanged(order) => {
+                self.settings.change(|ws| ws.euler_order = order);
+            }
+            Message::SettingsEulerRadiansToggled(new) => {
+                self.settings.change(|ws| ws.euler_radians = new);
+            }
+            Message::SettingsEulerHandednessChanged(handedness) => {
+                self.settings.change(|ws| ws.euler_handedness = handedness);
+            }
+            Message::SettingsFusionChanged(fusion) => {
+                self.settings.change(|ws| ws.fusion = fusion);
+            }
+            Message::SettingsRefreshRateChanged(hz) => {
+                self.settings.change(|ws| ws.gui_refresh_hz = hz);
+            }
+            Message::SettingsTelemetryAddressChanged(address) => {
+                self.settings.change(|ws| ws.telemetry_address = address);
+            }
+            Message::SettingsOutputToggled(output, on) => {
+                self.settings.change(|ws| ws.output_set(output, on));
+            }
+            Message::SettingsOscOutputAddressChanged(address) => {
+                self.settings.change(|ws| ws.osc_output_address = address);
+            }
+            Message::SettingsTelemetryRateChanged(hz) => {
+                self.settings.change(|ws| ws.telemetry_hz = hz);
+            }
+            Message::SettingsServerPathChanged(path) => {
+                self.settings.change(|ws| ws.server_path = path);
+            }
+            Message::SettingsServerStopToggled(new) => {
+                self.settings.change(|ws| ws.server_stop_on_exit = new);
+            }
+            Message::CloseRequested => {
+                self.session.write_log().ok();
+                settings::flush();
+                if self.settings.load().server_stop_on_exit {
+                    if let Some(process) = self.server_process.take() {
+                        process.stop();
+                    }
+                }
+                return window::close();
+            }
         }
         Command::none()
     }
@@ -181,20 +782,44 @@ impl Application for MainState {
     fn subscription(&self) -> Subscription<Message> {
         Subscription::batch(vec![
             time::every(Duration::from_millis(500)).map(Message::Dot),
-            time::every(Duration::from_millis(50)).map(Message::Tick),
+            time::every(self.settings.load().gui_refresh_interval()).map(Message::Tick),
+            subscription::events_with(|event, _| match event {
+                Event::Window(window::Event::CloseRequested) => Some(Message::CloseRequested),
+                Event::Keyboard(keyboard::Event::KeyPressed {
+                    key_code: keyboard::KeyCode::P,
+                    modifiers,
+                }) if modifiers.control() => Some(Message::PauseToggled),
+                _ => None,
+            }),
         ])
     }
 
     fn view(&self) -> Element<Message> {
-        let mut app = Column::new().push(top_bar(self.update_found.clone()));
+        if self.settings.load().compact {
+            return self.compact_view().into();
+        }
+        let mut app = Column::new().push(top_bar(self.update_found.clone(), self.paused));
 
-        if self.blacklist_info.visible() {
+        // Steam has to see the controllers to share them.
+        if self.blacklist_info.visible() && !self.settings.load().shared_access {
             app = app.push(blacklist_bar(&self.blacklist_info));
         }
+        let failed: Vec<_> = self
+            .self_test
+            .iter()
+            .chain(&self.server_probe)
+            .chain(&self.network_diagnosis)
+            .filter(|check| !check.passed)
+            .collect();
+        if !self.self_test_dismissed && !failed.is_empty() {
+            app = app.push(self_test_bar(&failed));
+        }
 
         app.push(
             if self.settings_show {
-                container(self.settings_screen()).padding(20)
+                container(scrollable(self.settings_screen().padding(20)))
+            } else if let Some(ref pairing) = self.pairing {
+                container(scrollable(self.pairing_screen(pairing).padding(20)))
             } else {
                 container(self.joycon_screen())
             }
@@ -202,53 +827,1122 @@ impl Application for MainState {
             .height(Length::Fill)
             .style(style::container_darker as for<'r> fn(&'r _) -> _),
         )
-        .push(bottom_bar(
-            self.server_connected,
-            &".".repeat(self.search_dots),
-            &self.server_address,
+        .push(self.toast_area())
+        .push(bottom_bar(
+            self.server_connected,
+            &".".repeat(self.search_dots),
+            &self.server_address,
+            self.paused,
+            self.vr_idle,
+        ))
+        .into()
+    }
+}
+
+impl MainState {
+    fn diagnose(&mut self) -> Command<Message> {
+        if self.diagnosing {
+            return Command::none();
+        }
+        self.diagnosing = true;
+        let settings = self.settings.load();
+        // Another MAC, so the server doesn't move the trackers to the test socket.
+        let mac_address = settings::derived_mac(settings.mac_address(), settings::TEST_MAC);
+        Command::perform(
+            diagnose_network(settings.address.clone(), mac_address),
+            Message::NetworkDiagnosed,
+        )
+    }
+
+    fn probe_server(&self, patience: Duration) -> Command<Message> {
+        let settings = self.settings.load();
+        let mac_address = settings::derived_mac(settings.mac_address(), settings::TEST_MAC);
+        Command::perform(
+            probe_server(settings.address.clone(), mac_address, patience),
+            Message::ServerProbed,
+        )
+    }
+
+    fn push_toast(&mut self, report: ErrorReport) {
+        if self.errors.len() >= MAX_ERRORS {
+            self.errors.remove(0);
+        }
+        self.errors.push(report.clone());
+        if let Some(toast) = self.toasts.iter_mut().find(|toast| {
+            toast.report.source == report.source && toast.report.message == report.message
+        }) {
+            toast.count += 1;
+            toast.report = report;
+            toast.shown = Instant::now();
+            return;
+        }
+        if self.toasts.len() >= MAX_TOASTS {
+            self.toasts.remove(0);
+        }
+        self.toasts.push(Toast {
+            report,
+            shown: Instant::now(),
+            count: 1,
+        });
+    }
+
+    fn toast_area(&self) -> Column<'_, Message> {
+        let settings = self.settings.load();
+        let mut area = Column::new();
+        for (index, toast) in self.toasts.iter().enumerate() {
+            let source = match settings.joycon_nickname_get(&toast.report.source) {
+                "" => toast.report.source.clone(),
+                nickname => nickname.to_owned(),
+            };
+            let mut message = format!("{}: {}", source, toast.report.message);
+            if toast.count > 1 {
+                message.push_str(&format!(" (x{})", toast.count));
+            }
+            let mut details = Column::new().push(
+                Row::new()
+                    .spacing(10)
+                    .push(text(format_time(toast.report.time)))
+                    .push(
+                        container(text(message))
+                            .style(style::text_orange as for<'r> fn(&'r _) -> _),
+                    ),
+            );
+            if let Some(ref suggestion) = toast.report.suggestion {
+                details = details.push(text(suggestion).size(16));
+            }
+            let row = Row::new()
+                .align_items(Alignment::Center)
+                .push(details.width(Length::Fill))
+                .push(button(text("×")).on_press(Message::ToastDismissed(index)));
+            area = area.push(
+                container(row)
+                    .width(Length::Fill)
+                    .padding([5, 20])
+                    .style(style::container_info as for<'r> fn(&'r _) -> _),
+            );
+        }
+        area
+    }
+
+    fn compact_view(&self) -> Container<'_, Message> {
+        let settings = self.settings.load();
+        let header = Row::new()
+            .spacing(10)
+            .align_items(Alignment::Center)
+            .push(
+                container(text(format!("Server: {:?}", self.server_connected)))
+                    .style(server_style(self.server_connected)),
+            )
+            .push(horizontal_space(Length::Fill))
+            .push(pause_button(self.paused))
+            .push(
+                button(text("Full view"))
+                    .style(theme::Button::Custom(Box::new(style::PrimaryButton)))
+                    .on_press(Message::CompactToggled),
+            );
+        let mut rows = Column::new().spacing(5);
+        for status in &self.joycon_boxes.statuses {
+            if settings.joycon_blocked_get(&status.serial_number) {
+                continue;
+            }
+            let nickname = settings.joycon_nickname_get(&status.serial_number);
+            let name = if nickname.is_empty() {
+                design_name(&status.design)
+            } else {
+                nickname
+            };
+            rows = rows.push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push(circle(6.0, status_color(status.status)))
+                    .push(text(status_icon(status.status)).width(Length::Fixed(20.0)))
+                    .push(circle(
+                        4.0,
+                        accent_color(settings.joycon_accent_get(&status.serial_number))
+                            .unwrap_or(Color::TRANSPARENT),
+                    ))
+                    .push(text(name).width(Length::Fill))
+                    .push(text(format!("{:?}", status.battery)).width(Length::Fixed(80.0)))
+                    .push(
+                        container(text(format!("{}/s", status.rate)))
+                            .width(Length::Fixed(50.0))
+                            .style(link_style(status)),
+                    ),
+            );
+        }
+        container(
+            Column::new()
+                .spacing(10)
+                .padding(10)
+                .push(header)
+                .push(scrollable(rows)),
+        )
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .style(style::container_darker as for<'r> fn(&'r _) -> _)
+    }
+    fn joycon_screen(&self) -> Scrollable<'_, Message> {
+        let settings = self.settings.load();
+        let search = self.search.to_lowercase();
+        let matches = |status: &joycon::Status| {
+            !settings.joycon_blocked_get(&status.serial_number)
+                && self.device_filter.matches(status)
+                && (status.serial_number.to_lowercase().contains(&search)
+                    || settings
+                        .joycon_nickname_get(&status.serial_number)
+                        .to_lowercase()
+                        .contains(&search)
+                    || settings
+                        .joycon_group_get(&status.serial_number)
+                        .to_lowercase()
+                        .contains(&search))
+        };
+        let mut grid = Grid::with_column_width(320.0);
+        for bax in self
+            .joycon_boxes
+            .view(&settings, self.two_pose.as_ref(), matches)
+        {
+            grid.insert(container(bax).padding(10));
+        }
+        let search_row = Row::new()
+            .spacing(10)
+            .padding(10)
+            .align_items(Alignment::Center)
+            .push(
+                text_input("Search by nickname, group or serial", &self.search)
+                    .on_input(Message::SearchChanged)
+                    .width(Length::Fixed(300.0))
+                    .padding(10),
+            )
+            .push("Show:")
+            .push(pick_list(
+                &DeviceFilter::ALL[..],
+                Some(self.device_filter),
+                Message::FilterChanged,
+            ));
+        let mut list = Column::new()
+            .padding(10)
+            .width(Length::Fill)
+            .push(search_row);
+        if let Some(groups) = self.groups_panel() {
+            list = list.push(groups);
+        }
+        let compatibility = self.handshake_log.compatibility();
+        if let Some(warning) = compatibility.warning() {
+            let style = match compatibility {
+                joycon::Compatibility::TooOld(_) => style::text_orange,
+                _ => style::text_yellow,
+            };
+            list = list.push(
+                container(text(format!(
+                    "{}. {}",
+                    warning.message,
+                    warning.suggestion.unwrap_or_default()
+                )))
+                .padding(10)
+                .style(style as for<'r> fn(&'r _) -> _),
+            );
+        }
+        if let Some(advice) = bluetooth_advice(&self.joycon_boxes.statuses) {
+            list = list.push(
+                container(text(advice))
+                    .padding(10)
+                    .style(style::text_yellow as for<'r> fn(&'r _) -> _),
+            );
+        }
+        let list = list.push(grid);
+
+        let list = list.push(
+            container(text(format!(
+                "Searching for Joycon controllers{}\n\
+                    Please pair controllers in the bluetooth \
+                    settings of Windows if they don't show up here.",
+                ".".repeat(self.search_dots)
+            )))
+            .padding(10),
+        );
+        scrollable(list).height(Length::Fill)
+    }
+    fn groups_panel(&self) -> Option<Column<'_, Message>> {
+        let settings = self.settings.load();
+        let groups = settings.groups();
+        if groups.is_empty() {
+            return None;
+        }
+        let profiles: Vec<String> = settings.profiles.keys().cloned().collect();
+        let mut panel = Column::new().spacing(10).padding(10);
+        for group in groups {
+            let paused = self.paused_groups.contains(&group);
+            let button = |label: &str, message| {
+                button(text(label))
+                    .on_press(message)
+                    .style(theme::Button::Custom(Box::new(style::PrimaryButton)))
+            };
+            let mut row = Row::new()
+                .spacing(10)
+                .align_items(Alignment::Center)
+                .push(
+                    text(format!(
+                        "{group} ({})",
+                        settings.group_members(&group).len()
+                    ))
+                    .width(Length::Fixed(150.0)),
+                )
+                .push(button("Align", Message::GroupAligned(group.clone())))
+                .push(button(
+                    if paused { "Resume" } else { "Pause" },
+                    Message::GroupPauseToggled(group.clone()),
+                ))
+                .push(button("Identify", Message::GroupIdentify(group.clone())));
+            if !profiles.is_empty() {
+                let group = group.clone();
+                row = row.push(
+                    pick_list(profiles.clone(), None, move |name| {
+                        Message::GroupProfileLoaded(group.clone(), name)
+                    })
+                    .placeholder("Load profile"),
+                );
+            }
+            if paused {
+                row = row.push(
+                    container(text("Paused, shown as offline"))
+                        .style(style::text_yellow as for<'r> fn(&'r _) -> _),
+                );
+            }
+            panel = panel.push(row);
+        }
+        Some(panel)
+    }
+    fn pairing_screen(&self, pairing: &Pairing) -> Column<'_, Message> {
+        let statuses = &self.joycon_boxes.statuses;
+        let mut screen = Column::new()
+            .spacing(20)
+            .push(text("Pair controllers").size(24))
+            .push(text(
+                "Controllers show up here as soon as your computer connects to them. \
+                Leave this screen open while pairing.",
+            ));
+
+        let mut list = Column::new().spacing(10);
+        for status in statuses {
+            let svg = Svg::new(self.joycon_boxes.svg_handler.get(&status.design, 0))
+                .width(Length::Fixed(40.0))
+                .height(Length::Fixed(40.0));
+            let mut row = Row::new()
+                .spacing(20)
+                .align_items(Alignment::Center)
+                .push(svg)
+                .push(text(design_name(&status.design)).width(Length::Fixed(150.0)))
+                .push(text(&status.design.color).width(Length::Fixed(100.0)))
+                .push(text(&status.serial_number).width(Length::Fixed(200.0)))
+                .push(text(format!("{}", status.status)));
+            if !pairing.known.contains(&status.serial_number) {
+                row = row.push(
+                    container(text("Just found!"))
+                        .style(style::text_green as for<'r> fn(&'r _) -> _),
+                );
+            }
+            list = list.push(row);
+        }
+        screen = screen.push(
+            container(list)
+                .padding(10)
+                .width(Length::Fill)
+                .style(style::item_normal as for<'r> fn(&'r _) -> _),
+        );
+
+        let found_new = statuses
+            .iter()
+            .any(|s| !pairing.known.contains(&s.serial_number));
+        let elapsed = pairing.since.elapsed();
+        if found_new {
+            return screen;
+        }
+        screen = screen.push(text(format!(
+            "Searching for new controllers{}",
+            ".".repeat(self.search_dots)
+        )));
+        let shown = PAIRING_HINTS
+            .iter()
+            .take_while(|(after, _)| elapsed >= Duration::from_secs(*after));
+        for (i, (_, hint)) in shown.enumerate() {
+            screen = screen.push(text(format!("{}. {hint}", i + 1)));
+        }
+        screen
+    }
+    // Profiling only runs while the settings with the Performance panel are open. The first
+    // snapshot waits an interval, until every thread measured again and its total is current.
+    fn sample_profile(&mut self, now: Instant) {
+        let Some((since, earlier)) = &self.profile_sample else {
+            joycon::set_profiling(true);
+            self.profile_sample = Some((now, vec![]));
+            return;
+        };
+        if now.duration_since(*since) < PROFILE_INTERVAL {
+            return;
+        }
+        let current = joycon::profile();
+        if !earlier.is_empty() {
+            let elapsed = now.duration_since(*since);
+            self.profile = current
+                .iter()
+                .map(|thread| {
+                    let before = earlier
+                        .iter()
+                        .find(|e| e.thread == thread.thread)
+                        .cloned()
+                        .unwrap_or_default();
+                    let (total, stages) = thread.usage_since(&before, elapsed);
+                    (thread.thread.clone(), total, stages)
+                })
+                .collect();
+        }
+        self.profile_sample = Some((now, current));
+    }
+    fn profiles_panel(&self) -> Column<'_, Message> {
+        let settings = self.settings.load();
+        let mut panel = Column::new()
+            .spacing(10)
+            .push(text("Profiles").size(24))
+            .push(text(
+                "Save the settings of every tracker under a name, to switch between setups.",
+            ))
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push(
+                        text_input("Profile name", &self.profile_name)
+                            .on_input(Message::ProfileNameChanged)
+                            .on_submit(Message::ProfileSaved)
+                            .width(Length::Fixed(300.0))
+                            .padding(10),
+                    )
+                    .push(
+                        button(text("Save"))
+                            .on_press(Message::ProfileSaved)
+                            .style(theme::Button::Custom(Box::new(style::PrimaryButton))),
+                    ),
+            );
+        for name in settings.profiles.keys() {
+            let label = if *name == settings.active_profile {
+                format!("{name} (active)")
+            } else {
+                name.clone()
+            };
+            panel = panel.push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push(text(label).width(Length::Fixed(300.0)))
+                    .push(
+                        button(text("Load"))
+                            .on_press(Message::ProfileLoaded(name.clone()))
+                            .style(theme::Button::Custom(Box::new(style::PrimaryButton))),
+                    )
+                    .push(
+                        button(text("Delete"))
+                            .on_press(Message::ProfileDeleted(name.clone()))
+                            .style(theme::Button::Custom(Box::new(style::PrimaryButton))),
+                    ),
+            );
+        }
+        let combo = |label, combo, on_change: fn(ButtonCombo) -> Message| {
+            Row::new()
+                .spacing(10)
+                .align_items(Alignment::Center)
+                .push(text(label).width(Length::Fixed(300.0)))
+                .push(pick_list(&ButtonCombo::ALL[..], Some(combo), on_change))
+        };
+        panel
+            .push(combo(
+                "Next profile, from a controller:",
+                settings.combo_next_profile,
+                Message::SettingsComboProfileChanged,
+            ))
+            .push(combo(
+                "Pause or resume all trackers:",
+                settings.combo_pause,
+                Message::SettingsComboPauseChanged,
+            ))
+            .push(combo(
+                "Yaw reset on the server:",
+                settings.combo_reset,
+                Message::SettingsComboResetChanged,
+            ))
+    }
+    fn self_test_panel(&self) -> Column<'_, Message> {
+        let mut panel = Column::new().spacing(10).push(text("Self-test").size(24));
+        let checks = self
+            .self_test
+            .iter()
+            .chain(&self.server_probe)
+            .chain(&self.network_diagnosis);
+        for check in checks {
+            let (mark, color): (_, fn(&_) -> _) = if check.passed {
+                ("✓", style::text_green)
+            } else if check.warning {
+                ("!", style::text_yellow)
+            } else {
+                ("✗", style::text_orange)
+            };
+            panel = panel.push(
+                Row::new()
+                    .spacing(10)
+                    .push(container(text(mark)).style(color))
+                    .push(text(format!("{}: {}", check.name, check.detail))),
+            );
+            if let Some(ref fix) = check.fix {
+                panel = panel.push(text(fix).size(16));
+            }
+        }
+        if self.server_probe.is_none() {
+            panel = panel.push(text("Checking the SlimeVR server..."));
+        }
+        let mut diagnose = button(text(if self.diagnosing {
+            "Diagnosing the connection..."
+        } else {
+            "Diagnose the connection to the server"
+        }))
+        .style(theme::Button::Custom(Box::new(style::PrimaryButton)));
+        if !self.diagnosing {
+            diagnose = diagnose.on_press(Message::DiagnosePressed);
+        }
+        panel = panel.push(diagnose).push(
+            Row::new()
+                .spacing(10)
+                .align_items(Alignment::Center)
+                .push(
+                    button(text("Export diagnostics"))
+                        .style(theme::Button::Custom(Box::new(style::PrimaryButton)))
+                        .on_press(Message::ExportDiagnosticsPressed),
+                )
+                .push(text(
+                    "Zips the settings without secrets, the trackers, errors, packets and the log, to attach to a bug report.",
+                )),
+        );
+        if let Some(ref export) = self.diagnostics_export {
+            panel = panel.push(text(export));
+        }
+        panel
+    }
+    fn profile_panel(&self) -> Column<'_, Message> {
+        let mut panel = Column::new().spacing(10).push(text("Performance").size(24));
+        if self.profile.is_empty() {
+            return panel.push(text("Nothing measured yet."));
+        }
+        for (thread, total, stages) in &self.profile {
+            let stages = Stage::ALL
+                .iter()
+                .zip(stages)
+                .filter(|(_, share)| **share > 0.0)
+                .map(|(stage, share)| format!("{} {:.1}%", stage.name(), share * 100.0))
+                .collect::<Vec<_>>()
+                .join(", ");
+            panel = panel.push(text(format!(
+                "{thread}: {:.1}% of a core ({stages})",
+                total * 100.0
+            )));
+        }
+        panel
+    }
+    fn bandwidth_panel(&self) -> Column<'_, Message> {
+        let settings = self.settings.load();
+        let statuses = &self.joycon_boxes.statuses;
+        let mut panel = Column::new()
+            .spacing(10)
+            .push(text("Bandwidth").size(24))
+            .push(checkbox(
+                "Low-bandwidth mode: send fewer packets, and none for trackers that don't move, bundled together. For weak Wi-Fi.",
+                settings.low_bandwidth,
+                Message::SettingsLowBandwidthToggled,
+            ))
+            .push(checkbox(
+                "Smooth output: send the rotations of each tracker evenly spaced instead of in the bursts Bluetooth delivers them in, up to 20ms later. Not in low-bandwidth mode.",
+                settings.output_smoothing,
+                Message::SettingsOutputSmoothingToggled,
+            ));
+        let total: usize = statuses.iter().map(|status| status.bandwidth).sum();
+        panel = panel.push(text(format!("Total: {}", format_bandwidth(total))));
+        for status in statuses {
+            let name = match settings.joycon_nickname_get(&status.serial_number) {
+                "" => &status.serial_number,
+                nickname => nickname,
+            };
+            panel = panel.push(text(format!(
+                "{name}: {}",
+                format_bandwidth(status.bandwidth)
+            )));
+        }
+        panel
+    }
+    fn session_panel(&self) -> Column<'_, Message> {
+        let settings = self.settings.load();
+        let mut panel = Column::new()
+            .spacing(10)
+            .push(text("Session").size(24))
+            .push(text(format!(
+                "Running for {}. Saved to sessions.jsonl next to the settings on exit.",
+                joycon::format_duration(self.session.uptime())
+            )));
+        for device in self.session.devices() {
+            let name = match settings.joycon_nickname_get(&device.serial_number) {
+                "" => device.serial_number.clone(),
+                nickname => nickname.to_owned(),
+            };
+            panel = panel.push(text(format!("{name}: {device}")));
+        }
+        panel
+    }
+    fn log_panel(&self) -> Column<'_, Message> {
+        let lines = self
+            .log
+            .iter()
+            .fold(Column::new().spacing(2), |lines, line| {
+                lines.push(text(line).size(14))
+            });
+        Column::new()
+            .spacing(10)
+            .push(text("Log").size(24))
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push(
+                        button(text("Copy log"))
+                            .style(theme::Button::Custom(Box::new(style::PrimaryButton)))
+                            .on_press(Message::CopyLogPressed),
+                    )
+                    .push(text(
+                        "Copies the latest lines, to paste into a bug report. Older days are in the logs folder next to the settings.",
+                    )),
+            )
+            .push(scrollable(lines).height(Length::Fixed(300.0)))
+    }
+    fn handshake_panel(&self) -> Column<'_, Message> {
+        let log = &self.handshake_log;
+        let mut panel = Column::new()
+            .spacing(10)
+            .push(text("Handshake inspection").size(24))
+            .push(checkbox(
+                "Send raw IMU samples to the server, for its inspection tools.",
+                self.settings.load().inspection,
+                Message::SettingsInspectionToggled,
+            ));
+        let sensors = log
+            .sensor_info
+            .iter()
+            .map(|(id, p)| (format!("SensorInfo {id}"), p));
+        let packets = log
+            .handshake
+            .iter()
+            .map(|p| ("Handshake".to_string(), p))
+            .chain(sensors)
+            .map(|(name, p)| (format!("Sent {name}"), p))
+            .chain(log.reply.iter().map(|p| ("Server reply".to_string(), p)));
+        for (name, packet) in packets {
+            let fields = packet
+                .fields
+                .iter()
+                .map(|(field, value)| format!("{field}: {value}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            panel = panel.push(
+                container(
+                    Column::new()
+                        .spacing(5)
+                        .push(text(format!(
+                            "{name}, {:.1}s ago",
+                            packet.time.elapsed().as_secs_f32()
+                        )))
+                        .push(text(fields))
+                        .push(text(packet.hex()).size(14)),
+                )
+                .padding(10)
+                .width(Length::Fill)
+                .style(style::item_normal as for<'r> fn(&'r _) -> _),
+            );
+        }
+        if log.reply.is_none() {
+            panel = panel.push(text("No reply from the server yet."));
+        }
+        panel
+    }
+    fn settings_screen(&self) -> Column<'_, Message> {
+        let sleep_minutes = self.settings.load().sleep_minutes;
+        let power_off_minutes = self.settings.load().power_off_minutes;
+        let refresh_hz = self.settings.load().gui_refresh_hz;
+        let prediction_ms = self.settings.load().prediction_ms;
+        Column::new()
+            .spacing(20)
+            .push(address(
+                &self.settings.load(),
+                self.local_server.as_deref(),
+                self.discovering,
+                self.discovered.as_ref(),
+            ))
+            .push(checkbox(
+                "Send yaw reset command to SlimeVR Server after B or UP button press.",
+                self.settings.load().send_reset,
+                Message::SettingsResetToggled,
+            ))
+            .push(checkbox(
+                "Save mounting location on server. Requires SlimeVR Server v0.6.1 or newer. Restart Wrangler after changing this.",
+                self.settings.load().keep_ids,
+                Message::SettingsIdsToggled,
+            ))
+            .push(checkbox(
+                "Show every tracker as a device of its own on the server, with its own battery. Restart Wrangler after changing this.",
+                self.settings.load().separate_devices,
+                Message::SettingsSeparateDevicesToggled,
+            ))
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push(
+                        slider(0..=30, sleep_minutes, Message::SettingsSleepChanged)
+                            .width(Length::Fixed(300.0)),
+                    )
+                    .push(text(if sleep_minutes == 0 {
+                        "Never put trackers to sleep when they stop moving.".to_string()
+                    } else {
+                        format!("Put trackers to sleep after {sleep_minutes} minutes without movement.")
+                    })),
+            )
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push(
+                        slider(0..=120, power_off_minutes, Message::SettingsPowerOffChanged)
+                            .width(Length::Fixed(300.0)),
+                    )
+                    .push(text(if power_off_minutes == 0 {
+                        "Never turn off controllers that stop moving.".to_string()
+                    } else {
+                        format!("Turn off controllers after {power_off_minutes} minutes without movement, unless they are charging.")
+                    })),
+            )
+            .push(vr_pause(&self.settings.load()))
+            .push(checkbox(
+                "Share controllers with Steam and games, only read their motion. Reconnect controllers after changing this.",
+                self.settings.load().shared_access,
+                Message::SettingsSharedAccessToggled,
+            ))
+            .push(checkbox(
+                "Read the controllers at a raised priority, against lag while a game uses all of the CPU. Restart wrangler after changing this.",
+                self.settings.load().reader_priority,
+                Message::SettingsReaderPriorityToggled,
+            ))
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push("Read the controllers on:")
+                    .push(pick_list(
+                        ReaderCore::all(),
+                        Some(ReaderCore(self.settings.load().reader_core)),
+                        Message::SettingsReaderCoreChanged,
+                    ))
+                    .push("Restart wrangler after changing this."),
+            )
+            .push(checkbox(
+                "Buzz a tracker when its battery gets low.",
+                self.settings.load().low_battery_rumble,
+                Message::SettingsLowBatteryRumbleToggled,
+            ))
+            .push(checkbox(
+                "Run the gyroscope in high performance mode (833Hz). Less latency, slightly more noise.",
+                self.settings.load().imu_gyro_high_performance,
+                Message::SettingsGyroPerformanceToggled,
+            ))
+            .push(checkbox(
+                "Use the wider accelerometer filter (200Hz instead of 100Hz). Less latency, more noise.",
+                self.settings.load().imu_accel_wide_filter,
+                Message::SettingsAccelFilterToggled,
+            ))
+            .push(checkbox(
+                "Automatically level trackers that are placed flat and still on a surface.",
+                self.settings.load().auto_level,
+                Message::SettingsAutoLevelToggled,
+            ))
+            .push(checkbox(
+                "Refine the gyro offsets whenever a tracker lies still, against slow drift in long sessions.",
+                self.settings.load().gyro_bias_refinement,
+                Message::SettingsGyroBiasToggled,
+            ))
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push("Sensor fusion:")
+                    .push(pick_list(
+                        &Fusion::ALL[..],
+                        Some(self.settings.load().fusion),
+                        Message::SettingsFusionChanged,
+                    ))
+                    .push("Trackers restart their fusion when this is changed. Each tracker can also have one of its own."),
+            )
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push(
+                        slider(0..=MAX_PREDICTION_MS, prediction_ms, Message::SettingsPredictionChanged)
+                            .width(Length::Fixed(300.0)),
+                    )
+                    .push(text(if prediction_ms == 0 {
+                        "Send the rotations as measured.".to_string()
+                    } else {
+                        format!("Predict rotations {prediction_ms}ms ahead, to hide some of the Bluetooth latency. Trackers overshoot a little when a movement stops.")
+                    })),
+            )
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push("Rotation order of the numeric readout:")
+                    .push(pick_list(
+                        &EulerOrder::ALL[..],
+                        Some(self.settings.load().euler_order),
+                        Message::SettingsEulerOrderChanged,
+                    ))
+                    .push(pick_list(
+                        &Handedness::ALL[..],
+                        Some(self.settings.load().euler_handedness),
+                        Message::SettingsEulerHandednessChanged,
+                    ))
+                    .push(checkbox(
+                        "Show in radians",
+                        self.settings.load().euler_radians,
+                        Message::SettingsEulerRadiansToggled,
+                    )),
+            )
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push(
+                        slider(10..=60, refresh_hz, Message::SettingsRefreshRateChanged)
+                            .width(Length::Fixed(300.0)),
+                    )
+                    .push(text(format!(
+                        "Redraw the trackers {refresh_hz} times a second. Lower it if wrangler is slow on your computer."
+                    ))),
+            )
+            .push(server_launcher(&self.settings.load()))
+            .push(outputs_panel(&self.settings.load()))
+            .push(telemetry(&self.settings.load()))
+            .push(self.profiles_panel())
+            .push(lights_panel(&self.settings.load()))
+            .push(blocked_panel(&self.settings.load()))
+            .push(self.self_test_panel())
+            .push(self.handshake_panel())
+            .push(self.profile_panel())
+            .push(self.bandwidth_panel())
+            .push(self.session_panel())
+            .push(self.log_panel())
+    }
+}
+
+/// Pose the two-pose mounting calibration is waiting for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MountingStep {
+    Standing,
+    Bent,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum DeviceFilter {
+    #[default]
+    All,
+    Disconnected,
+    LowBattery,
+    Errors,
+}
+impl DeviceFilter {
+    const ALL: [DeviceFilter; 4] = [
+        DeviceFilter::All,
+        DeviceFilter::Disconnected,
+        DeviceFilter::LowBattery,
+        DeviceFilter::Errors,
+    ];
+    fn matches(&self, status: &joycon::Status) -> bool {
+        match self {
+            DeviceFilter::All => true,
+            DeviceFilter::Disconnected => status.status == DeviceStatus::Disconnected,
+            DeviceFilter::LowBattery => status.battery <= Battery::Low,
+            DeviceFilter::Errors => {
+                matches!(status.status, DeviceStatus::NoIMU | DeviceStatus::LaggyIMU)
+            }
+        }
+    }
+}
+impl std::fmt::Display for DeviceFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            DeviceFilter::All => "All trackers",
+            DeviceFilter::Disconnected => "Disconnected only",
+            DeviceFilter::LowBattery => "Low battery",
+            DeviceFilter::Errors => "Errors",
+        })
+    }
+}
+
+/// CPU core the controller readers are pinned to, `None` for any.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ReaderCore(Option<usize>);
+impl ReaderCore {
+    /// Any, then each core of this computer.
+    fn all() -> Vec<ReaderCore> {
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+        std::iter::once(ReaderCore(None))
+            .chain((0..cores).map(|core| ReaderCore(Some(core))))
+            .collect()
+    }
+}
+impl std::fmt::Display for ReaderCore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Some(core) => write!(f, "Core {core}"),
+            None => f.write_str("Any core"),
+        }
+    }
+}
+
+/// State of the pairing screen while it is open.
+struct Pairing {
+    since: Instant,
+    /// Controllers that were already connected when the screen was opened.
+    known: Vec<String>,
+}
+impl Pairing {
+    fn new(statuses: &[joycon::Status]) -> Self {
+        Self {
+            since: Instant::now(),
+            known: statuses.iter().map(|s| s.serial_number.clone()).collect(),
+        }
+    }
+}
+
+// Troubleshooting steps, shown one by one after the given seconds without a new controller.
+const PAIRING_HINTS: [(u64, &str); 5] = [
+    (10, "Hold the small round sync button on the side of the controller until the green lights start running back and forth."),
+    (10, "Open the bluetooth settings of your computer and add a new device. Pick \"Joy-Con (L)\", \"Joy-Con (R)\" or \"Pro Controller\"."),
+    (25, "If the controller is listed as connected but doesn't show up here, remove it from the bluetooth devices and pair it again."),
+    (40, "Close Steam and other programs that use controllers, and check for a Steam warning at the top of this window."),
+    (55, "Move the controller closer to the bluetooth adapter, or try a different adapter."),
+];
+
+fn format_bandwidth(bytes_per_second: usize) -> String {
+    format!("{:.1} kB/s", bytes_per_second as f64 / 1000.0)
+}
+
+fn design_name(design: &JoyconDesign) -> &'static str {
+    match design.design_type {
+        JoyconDesignType::Left => "Joy-Con (L)",
+        JoyconDesignType::Right => "Joy-Con (R)",
+        JoyconDesignType::Pro => "Pro Controller",
+    }
+}
+
+fn vr_pause<'a>(settings: &WranglerSettings) -> Row<'a, Message> {
+    let enabled = !settings.vr_process.is_empty();
+    let mut row = Row::new()
+        .spacing(10)
+        .align_items(Alignment::Center)
+        .push(checkbox(
+            "Pause streaming and turn the lights off while no VR session runs. Process:",
+            enabled,
+            Message::SettingsVrPauseToggled,
+        ));
+    if enabled {
+        row = row
+            .push(
+                text_input(settings::STEAMVR_PROCESS, &settings.vr_process)
+                    .on_input(Message::SettingsVrProcessChanged)
+                    .width(Length::Fixed(150.0))
+                    .padding(10),
+            )
+            .push(text(format!("{} for SteamVR.", settings::STEAMVR_PROCESS)));
+    }
+    row
+}
+
+fn server_launcher<'a>(settings: &WranglerSettings) -> Column<'a, Message> {
+    let path = text_input("None", &settings.server_path)
+        .on_input(Message::SettingsServerPathChanged)
+        .width(Length::Fixed(300.0))
+        .padding(10);
+    Column::new()
+        .spacing(10)
+        .push(
+            Row::new()
+                .spacing(10)
+                .align_items(Alignment::Center)
+                .push("SlimeVR Server executable:")
+                .push(path)
+                .push("Started together with Wrangler, unless it is already running."),
+        )
+        .push(checkbox(
+            "Stop the SlimeVR Server when Wrangler closes, if Wrangler started it.",
+            settings.server_stop_on_exit,
+            Message::SettingsServerStopToggled,
         ))
-        .into()
-    }
 }
 
-impl MainState {
-    fn joycon_screen(&self) -> Scrollable<'_, Message> {
-        let mut grid = Grid::with_column_width(320.0);
-        for bax in self.joycon_boxes.view(&self.settings.load()) {
-            grid.insert(container(bax).padding(10));
-        }
-        let list = Column::new().padding(10).width(Length::Fill).push(grid);
+fn lights_panel<'a>(settings: &WranglerSettings) -> Column<'a, Message> {
+    let mut panel = Column::new()
+        .spacing(10)
+        .push(text("Controller lights").size(24))
+        .push(text(
+            "Player lights and HOME light for each state. The left Joy-Con has no HOME light.",
+        ));
+    for state in LightState::ALL {
+        let pattern = settings.lights.get(state);
+        panel = panel.push(
+            Row::new()
+                .spacing(10)
+                .align_items(Alignment::Center)
+                .push(text(format!("{state}:")).width(Length::Fixed(100.0)))
+                .push(pick_list(
+                    &PlayerLights::ALL[..],
+                    Some(pattern.player),
+                    move |player| Message::SettingsPlayerLightsChanged(state, player),
+                ))
+                .push(pick_list(
+                    &HomeLight::ALL[..],
+                    Some(pattern.home),
+                    move |home| Message::SettingsHomeLightChanged(state, home),
+                )),
+        );
+    }
+    panel
+}
 
-        let list = list.push(
-            container(text(format!(
-                "Searching for Joycon controllers{}\n\
-                    Please pair controllers in the bluetooth \
-                    settings of Windows if they don't show up here.",
-                ".".repeat(self.search_dots)
-            )))
-            .padding(10),
+fn blocked_panel<'a>(settings: &WranglerSettings) -> Column<'a, Message> {
+    let mut panel = Column::new()
+        .spacing(10)
+        .push(text("Blocked controllers").size(24));
+    let mut blocked: Vec<_> = settings
+        .joycon
+        .iter()
+        .filter(|(_, joycon)| joycon.blocked)
+        .collect();
+    if blocked.is_empty() {
+        return panel.push(text(
+            "None. Block a controller on its tracker to keep wrangler away from it, for playing.",
+        ));
+    }
+    blocked.sort_by_key(|(serial_number, _)| *serial_number);
+    for (serial_number, joycon) in blocked {
+        let name = if joycon.nickname.is_empty() {
+            serial_number.clone()
+        } else {
+            format!("{} ({serial_number})", joycon.nickname)
+        };
+        panel = panel.push(
+            Row::new()
+                .spacing(10)
+                .align_items(Alignment::Center)
+                .push(text(name))
+                .push(
+                    button(text("Unblock"))
+                        .on_press(Message::JoyconBlocked(serial_number.clone(), false))
+                        .style(theme::Button::Custom(Box::new(style::PrimaryButton))),
+                ),
         );
-        scrollable(list).height(Length::Fill)
     }
-    fn settings_screen(&self) -> Column<'_, Message> {
-        Column::new()
-            .spacing(20)
-            .push(address(&self.settings.load().address))
-            .push(checkbox(
-                "Send yaw reset command to SlimeVR Server after B or UP button press.",
-                self.settings.load().send_reset,
-                Message::SettingsResetToggled,
+    panel
+}
+
+fn outputs_panel<'a>(settings: &WranglerSettings) -> Column<'a, Message> {
+    let mut outputs = Row::new().spacing(20).align_items(Alignment::Center);
+    for output in Output::ALL {
+        outputs = outputs.push(checkbox(
+            output.to_string(),
+            settings.output_get(output),
+            move |on| Message::SettingsOutputToggled(output, on),
+        ));
+    }
+    let address = text_input("127.0.0.1:9000", &settings.osc_output_address)
+        .on_input(Message::SettingsOscOutputAddressChanged)
+        .width(Length::Fixed(300.0))
+        .padding(10);
+    let mut column = Column::new()
+        .spacing(10)
+        .push(
+            Row::new()
+                .spacing(10)
+                .align_items(Alignment::Center)
+                .push("Send trackers to:")
+                .push(outputs),
+        )
+        .push(
+            Row::new()
+                .spacing(10)
+                .align_items(Alignment::Center)
+                .push("OSC trackers address:")
+                .push(address)
+                .push("Rotations only, VRChat listens on port 9000."),
+        );
+    if settings.output_osc && settings.osc_output_target().is_none() {
+        column = column.push(
+            container(text(
+                "Address is not a valid ip with port number, OSC trackers are off.",
             ))
-            .push(checkbox(
-                "Save mounting location on server. Requires SlimeVR Server v0.6.1 or newer. Restart Wrangler after changing this.",
-                self.settings.load().keep_ids,
-                Message::SettingsIdsToggled,
+            .style(style::text_yellow as for<'r> fn(&'r _) -> _),
+        );
+    }
+    column
+}
+
+fn telemetry<'a>(settings: &WranglerSettings) -> Column<'a, Message> {
+    let address = text_input("Off", &settings.telemetry_address)
+        .on_input(Message::SettingsTelemetryAddressChanged)
+        .width(Length::Fixed(300.0))
+        .padding(10);
+    let hz = settings.telemetry_hz;
+    let mut column = Column::new()
+        .spacing(10)
+        .push(
+            Row::new()
+                .spacing(10)
+                .align_items(Alignment::Center)
+                .push("JSON telemetry address:")
+                .push(address)
+                .push("Raw and fused data of every tracker, for visualizers."),
+        )
+        .push(
+            Row::new()
+                .spacing(10)
+                .align_items(Alignment::Center)
+                .push(
+                    slider(10..=200, hz, Message::SettingsTelemetryRateChanged)
+                        .width(Length::Fixed(300.0)),
+                )
+                .push(text(format!("Send telemetry {hz} times a second."))),
+        );
+    if !settings.telemetry_address.is_empty() && settings.telemetry_target().is_none() {
+        column = column.push(
+            container(text(
+                "Address is not a valid ip with port number, telemetry is off.",
             ))
+            .style(style::text_yellow as for<'r> fn(&'r _) -> _),
+        );
     }
+    column
 }
 
-fn address<'a>(input_value: &str) -> Column<'a, Message> {
-    let address = text_input("127.0.0.1:6969", input_value)
+fn address<'a>(
+    settings: &WranglerSettings,
+    local_server: Option<&str>,
+    discovering: bool,
+    discovered: Option<&Result<Vec<SocketAddr>, String>>,
+) -> Column<'a, Message> {
+    let address = text_input("127.0.0.1:6969", &settings.address)
         .on_input(Message::AddressChange)
         .width(Length::Fixed(300.0))
         .padding(10);
@@ -258,20 +1952,67 @@ fn address<'a>(input_value: &str) -> Column<'a, Message> {
         .align_items(Alignment::Center)
         .push("SlimeVR Server address:")
         .push(address)
-        .push("Restart Wrangler after changing this.");
+        .push("An ip or a hostname like slime-pc.local, with the port. Changes apply within 30 seconds.");
     let mut allc = Column::new().push(address_row).spacing(10);
 
-    if input_value.parse::<SocketAddr>().is_err() {
+    if !settings.address_is_valid() {
         allc = allc.push(
             container(text(
-                "Address is not a valid ip with port number! Using default instead (127.0.0.1:6969).",
+                "Address is not a valid ip or hostname with port number! The last working address is kept, or 127.0.0.1:6969.",
             ))
             .style(style::text_yellow as for<'r> fn(&'r _) -> _),
         );
     }
-    allc
+    if let Some(local) = local_server.filter(|local| *local != settings.address) {
+        allc = allc.push(
+            Row::new()
+                .spacing(10)
+                .align_items(Alignment::Center)
+                .push(text(format!(
+                    "A SlimeVR server is set up on this PC, at {local}."
+                )))
+                .push(button(text("Use it")).on_press(Message::AddressChange(local.to_owned()))),
+        );
+    }
+    let mut search = Row::new()
+        .spacing(10)
+        .align_items(Alignment::Center)
+        .push(if discovering {
+            button(text("Searching..."))
+        } else {
+            button(text("Find servers")).on_press(Message::DiscoverPressed)
+        });
+    search = match discovered {
+        None => search.push(text(
+            "Looks for SlimeVR servers on this network that use the default port.",
+        )),
+        Some(Ok(servers)) if servers.is_empty() => search.push(text(
+            "No server answered. A firewall on the server PC may block it, enter its address instead.",
+        )),
+        Some(Ok(servers)) => {
+            let current = settings.resolve_address();
+            let selected = servers.iter().copied().find(|&server| Some(server) == current);
+            search
+                .push(pick_list(servers.clone(), selected, |server| {
+                    Message::AddressChange(server.to_string())
+                }))
+                .push(text(format!("{} found.", servers.len())))
+        }
+        Some(Err(e)) => search.push(text(format!("Could not search the network: {e}"))),
+    };
+    allc.push(search)
 }
-fn top_bar<'a>(update: Option<String>) -> Container<'a, Message> {
+fn pause_button<'a>(paused: bool) -> iced::widget::Button<'a, Message> {
+    button(text(if paused {
+        "Resume streaming"
+    } else {
+        "Pause streaming"
+    }))
+    .style(theme::Button::Custom(Box::new(style::PrimaryButton)))
+    .on_press(Message::PauseToggled)
+}
+
+fn top_bar<'a>(update: Option<String>, paused: bool) -> Container<'a, Message> {
     let mut top_column = Row::new()
         .align_items(Alignment::Center)
         .push(text("SlimeVR Wrangler").size(24));
@@ -286,11 +2027,28 @@ fn top_bar<'a>(update: Option<String>) -> Container<'a, Message> {
             .push(update_btn);
     }
 
+    let align = button(text("Align all"))
+        .style(theme::Button::Custom(Box::new(style::PrimaryButton)))
+        .on_press(Message::AlignAllPressed);
+    let compact = button(text("Compact view"))
+        .style(theme::Button::Custom(Box::new(style::PrimaryButton)))
+        .on_press(Message::CompactToggled);
+    let pair = button(text("Pair controllers"))
+        .style(theme::Button::Custom(Box::new(style::PrimaryButton)))
+        .on_press(Message::PairingPressed);
     let settings = button(text("Settings"))
         .style(theme::Button::Custom(Box::new(style::PrimaryButton)))
         .on_press(Message::SettingsPressed);
     top_column = top_column
         .push(horizontal_space(Length::Fill))
+        .push(pause_button(paused))
+        .push(horizontal_space(Length::Fixed(10.0)))
+        .push(compact)
+        .push(horizontal_space(Length::Fixed(10.0)))
+        .push(pair)
+        .push(horizontal_space(Length::Fixed(10.0)))
+        .push(align)
+        .push(horizontal_space(Length::Fixed(10.0)))
         .push(settings);
 
     container(top_column)
@@ -317,25 +2075,114 @@ fn blacklist_bar<'a>(result: &blacklist::BlacklistResult) -> Container<'a, Messa
         .style(style::container_info as for<'r> fn(&'r _) -> _)
 }
 
+fn self_test_bar<'a>(failed: &[&joycon::CheckResult]) -> Container<'a, Message> {
+    let mut list = Column::new().spacing(5);
+    for check in failed {
+        let mut message = format!("{}: {}.", check.name, check.detail);
+        if let Some(ref fix) = check.fix {
+            message = format!("{message} {fix}");
+        }
+        list = list.push(text(message));
+    }
+    let row = Row::new()
+        .align_items(Alignment::Center)
+        .push(list.width(Length::Fill))
+        .push(button(text("×")).on_press(Message::SelfTestDismissed));
+    container(row)
+        .width(Length::Fill)
+        .padding(20)
+        .style(style::container_info as for<'r> fn(&'r _) -> _)
+}
+
+async fn launch_server(
+    path: String,
+    address: SocketAddr,
+) -> Result<Option<launcher::ServerProcess>, String> {
+    tokio::task::spawn_blocking(move || launcher::ServerProcess::launch(&path, address))
+        .await
+        .unwrap()
+        .map_err(|e| e.to_string())
+}
+
+async fn probe_server(
+    address: String,
+    mac_address: [u8; 6],
+    patience: Duration,
+) -> joycon::CheckResult {
+    tokio::task::spawn_blocking(move || joycon::probe_server(&address, mac_address, patience))
+        .await
+        .unwrap()
+}
+
+async fn discover_servers(mac_address: [u8; 6]) -> Result<Vec<SocketAddr>, String> {
+    tokio::task::spawn_blocking(move || {
+        joycon::discover_servers(mac_address, DISCOVERY_TIME).map_err(|e| e.to_string())
+    })
+    .await
+    .unwrap()
+}
+
+async fn diagnose_network(address: String, mac_address: [u8; 6]) -> Vec<joycon::CheckResult> {
+    tokio::task::spawn_blocking(move || joycon::diagnose_network(&address, mac_address))
+        .await
+        .unwrap()
+}
+
+/// An error from the background threads, shown above the bottom bar until dismissed or expired.
+struct Toast {
+    report: ErrorReport,
+    shown: Instant,
+    /// How many times the same error arrived while it was shown.
+    count: usize,
+}
+
+fn format_time(time: SystemTime) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()) % 86400;
+    format!(
+        "{:02}:{:02}:{:02} UTC",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+fn server_style(connected: ServerStatus) -> fn(&Theme) -> container::Appearance {
+    match connected {
+        ServerStatus::Connected => style::text_green,
+        ServerStatus::Unreachable | ServerStatus::SendFailed => style::text_orange,
+        _ => style::text_yellow,
+    }
+}
+
 fn bottom_bar<'a>(
     connected: ServerStatus,
     search_dots: &String,
     address: &String,
+    paused: bool,
+    vr_idle: bool,
 ) -> Container<'a, Message> {
-    let status = Row::new()
+    let mut status = Row::new()
         .push(text("Connection to SlimeVR Server: "))
-        .push(container(text(format!("{connected:?}"))).style(
-            if connected == ServerStatus::Connected {
-                style::text_green
-            } else {
-                style::text_yellow
-            },
-        ))
-        .push(text(if connected == ServerStatus::Connected {
-            format!(" to {address}.")
-        } else {
-            format!(". Trying to connect to {address}{search_dots}")
+        .push(container(text(format!("{connected:?}"))).style(server_style(connected)))
+        .push(text(match connected {
+            ServerStatus::Connected => format!(" to {address}."),
+            ServerStatus::Unreachable => format!(
+                ". Nothing is listening at {address}, is the server running? Retrying{search_dots}"
+            ),
+            ServerStatus::SendFailed => format!(
+                ". Can't send to {address}, is the network down? Tracking goes on, retrying{search_dots}"
+            ),
+            _ => format!(". Trying to connect to {address}{search_dots}"),
         }));
+    if paused || vr_idle {
+        let paused = if paused {
+            " Streaming is paused, the trackers show as offline."
+        } else {
+            " Streaming is paused until VR starts, the trackers show as offline."
+        };
+        status = status
+            .push(container(text(paused)).style(style::text_yellow as for<'r> fn(&'r _) -> _));
+    }
     container(status)
         .width(Length::Fill)
         .padding(20)
@@ -344,8 +2191,11 @@ fn bottom_bar<'a>(
 
 #[derive(Debug)]
 struct JoyconBoxes {
+    /// What is drawn, moved towards `latest` every frame.
     pub statuses: Vec<joycon::Status>,
-    svg_handler: joycon::Svg,
+    latest: Vec<joycon::Status>,
+    last_frame: Option<Instant>,
+    svg_handler: svg::Svg,
     needles: Vec<Needle>,
 }
 
@@ -353,41 +2203,354 @@ impl Default for JoyconBoxes {
     fn default() -> Self {
         Self {
             statuses: vec![],
-            svg_handler: joycon::Svg::new(),
+            latest: vec![],
+            last_frame: None,
+            svg_handler: svg::Svg::new(),
             needles: (0..360).map(Needle::new).collect(),
         }
     }
 }
 
 impl JoyconBoxes {
-    fn view<'a>(&'a self, settings: &WranglerSettings) -> Vec<Container<'a, Message>> {
+    fn interpolate(&mut self, now: Instant) {
+        let elapsed = self.last_frame.map_or(INTERPOLATION_TIME, |last| {
+            now.saturating_duration_since(last)
+        });
+        self.last_frame = Some(now);
+        let t = 1.0 - (-elapsed.as_secs_f64() / INTERPOLATION_TIME.as_secs_f64()).exp();
+        let slerp = |from: &UnitQuaternion<f64>, to: &UnitQuaternion<f64>| {
+            from.try_slerp(to, t, 1.0e-6).unwrap_or(*to)
+        };
+
+        let statuses = self
+            .latest
+            .iter()
+            .map(|latest| {
+                let mut status = latest.clone();
+                if let Some(shown) = self
+                    .statuses
+                    .iter()
+                    .find(|shown| shown.serial_number == latest.serial_number)
+                {
+                    status.orientation = slerp(&shown.orientation, &latest.orientation);
+                    let (roll, pitch, yaw) = shown.rotation;
+                    let from = UnitQuaternion::from_euler_angles(
+                        roll.to_radians(),
+                        pitch.to_radians(),
+                        yaw.to_radians(),
+                    );
+                    let (roll, pitch, yaw) = latest.rotation;
+                    let to = UnitQuaternion::from_euler_angles(
+                        roll.to_radians(),
+                        pitch.to_radians(),
+                        yaw.to_radians(),
+                    );
+                    let (roll, pitch, yaw) = slerp(&from, &to).euler_angles();
+                    status.rotation = (roll.to_degrees(), pitch.to_degrees(), yaw.to_degrees());
+                }
+                status
+            })
+            .collect();
+        self.statuses = statuses;
+    }
+
+    fn view<'a>(
+        &'a self,
+        settings: &WranglerSettings,
+        two_pose: Option<&(String, MountingStep)>,
+        filter: impl Fn(&joycon::Status) -> bool,
+    ) -> Vec<Container<'a, Message>> {
         self.statuses
             .iter()
+            .filter(|status| filter(status))
             .map(|status| {
+                let style = match accent_color(settings.joycon_accent_get(&status.serial_number)) {
+                    Some(color) => theme::Container::Custom(Box::new(style::Accent(color))),
+                    None => (style::item_normal as for<'r> fn(&'r _) -> _).into(),
+                };
+                let step = two_pose
+                    .filter(|(sn, _)| *sn == status.serial_number)
+                    .map(|&(_, step)| step);
                 container(single_box_view(
                     status,
                     &self.svg_handler,
                     &self.needles,
-                    settings.joycon_scale_get(&status.serial_number),
-                    settings.joycon_rotation_get(&status.serial_number),
+                    settings,
+                    step,
                 ))
-                .height(Length::Fixed(335.0))
+                .height(Length::Fixed(740.0))
                 .width(Length::Fixed(300.0))
                 .padding(10)
-                .style(style::item_normal as for<'r> fn(&'r _) -> _)
+                .style(style)
             })
             .collect()
     }
 }
 
+/// `rotation` in the euler order, frame and units of the settings.
+fn euler_readout(rotation: &UnitQuaternion<f64>, settings: &WranglerSettings) -> String {
+    let order = settings.euler_order;
+    let rotation = joycon::in_frame(rotation, settings.euler_handedness);
+    let angles = joycon::euler_angles(&rotation, order);
+    order
+        .axes()
+        .iter()
+        .zip(angles)
+        .map(|(&axis, angle)| {
+            let axis = ['X', 'Y', 'Z'][axis];
+            if settings.euler_radians {
+                format!("{axis} {angle:.3} rad")
+            } else {
+                format!("{axis} {:.1}°", angle.to_degrees())
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("  ")
+}
+
+// Nudges the heading of a misaligned tracker while playing, "Align all" starts over without trims.
+fn yaw_trim_row<'a>(sn: &str, trim: f64) -> Row<'a, Message> {
+    let slide = sn.to_owned();
+    let zero = sn.to_owned();
+    Row::new()
+        .spacing(10)
+        .align_items(Alignment::Center)
+        .push(text(format!("Yaw trim: {trim:+.1}°")).width(Length::Fixed(120.0)))
+        .push(
+            slider(-MAX_YAW_TRIM..=MAX_YAW_TRIM, trim, move |trim| {
+                Message::JoyconYawTrim(slide.clone(), trim)
+            })
+            .step(0.5),
+        )
+        .push(
+            button(text("Zero"))
+                .on_press(Message::JoyconYawTrim(zero, 0.0))
+                .style(theme::Button::Custom(Box::new(style::PrimaryButton))),
+        )
+}
+
+// Higher follows the accelerometer faster, lower trusts the gyro more while moving.
+fn fusion_gain_row<'a>(sn: &str, fusion: Fusion, gain: f64) -> Row<'a, Message> {
+    let slide = sn.to_owned();
+    let reset = sn.to_owned();
+    let name = match fusion {
+        Fusion::Madgwick => "Beta",
+        _ => "Gain",
+    };
+    let range = fusion.gain_range();
+    let step = (range.end() - range.start()) / 100.0;
+    Row::new()
+        .spacing(10)
+        .align_items(Alignment::Center)
+        .push(text(format!("{name}: {gain:.2}")).width(Length::Fixed(120.0)))
+        .push(
+            slider(range, gain, move |gain| {
+                Message::JoyconFusionGain(slide.clone(), gain)
+            })
+            .step(step),
+        )
+        .push(
+            button(text("Default"))
+                .on_press(Message::JoyconFusionGain(
+                    reset,
+                    fusion.default_gain().unwrap_or(gain),
+                ))
+                .style(theme::Button::Custom(Box::new(style::PrimaryButton))),
+        )
+}
+
+/// Lost reports and signal strength, as far as the controller and the OS tell.
+fn link_text(status: &joycon::Status) -> Option<String> {
+    let parts: Vec<_> = status
+        .lost
+        .map(|lost| format!("{lost} lost/s"))
+        .into_iter()
+        .chain(status.rssi.map(|rssi| format!("{rssi} dBm")))
+        .collect();
+    (!parts.is_empty()).then(|| parts.join(", "))
+}
+
+fn link_style(status: &joycon::Status) -> fn(&Theme) -> container::Appearance {
+    let expected = status.rate + status.lost.unwrap_or(0);
+    let lost_share = status.lost.unwrap_or(0) as f64 / expected.max(1) as f64;
+    let rssi = status.rssi.unwrap_or(0);
+    if lost_share > POOR_LINK_LOSS || rssi < POOR_LINK_RSSI {
+        style::text_orange
+    } else if lost_share > WEAK_LINK_LOSS || rssi < WEAK_LINK_RSSI {
+        style::text_yellow
+    } else {
+        style::text_green
+    }
+}
+
+fn accent_color(accent: Option<u8>) -> Option<Color> {
+    let (r, g, b) = *ACCENT_COLORS.get(accent? as usize)?;
+    Some(Color::from_rgb8(r, g, b))
+}
+
+// Shown next to the status colors, so they can be told apart without seeing the color.
+fn status_icon(status: DeviceStatus) -> &'static str {
+    match status {
+        DeviceStatus::Healthy => "OK",
+        DeviceStatus::LaggyIMU => "!",
+        DeviceStatus::Asleep => "z",
+        DeviceStatus::NoIMU => "?",
+        DeviceStatus::Disconnected => "X",
+    }
+}
+
+fn status_color(status: DeviceStatus) -> Color {
+    match status {
+        DeviceStatus::Disconnected | DeviceStatus::NoIMU => Color::from_rgb8(0xff, 0x38, 0x4A),
+        DeviceStatus::LaggyIMU => Color::from_rgb8(0xff, 0xe3, 0x3c),
+        DeviceStatus::Asleep => Color::from_rgb8(0x82, 0x82, 0x82),
+        DeviceStatus::Healthy => Color::from_rgb8(0x3d, 0xff, 0x81),
+    }
+}
+
+fn calibration_row<'a>(sn: &str, calibration: CalibrationState) -> Row<'a, Message> {
+    let mut row = Row::new().spacing(10).align_items(Alignment::Center).push(
+        text(match calibration.step {
+            CalibrationStep::Idle => "Gyro calibration",
+            CalibrationStep::Measuring => "Measuring, keep it still...",
+            CalibrationStep::Measured => "Measured, remembered by wrangler.",
+            CalibrationStep::Written => "Stored in the controller.",
+        })
+        .width(Length::Fill),
+    );
+    match calibration.step {
+        CalibrationStep::Measuring => {}
+        CalibrationStep::Measured => {
+            row = row.push(
+                button(text("Write"))
+                    .on_press(Message::JoyconWriteCalibration(sn.to_owned()))
+                    .style(theme::Button::Custom(Box::new(style::PrimaryButton))),
+            );
+        }
+        CalibrationStep::Idle | CalibrationStep::Written => {
+            row = row.push(
+                button(text("Measure"))
+                    .on_press(Message::JoyconCalibrate(sn.to_owned()))
+                    .style(theme::Button::Custom(Box::new(style::PrimaryButton))),
+            );
+        }
+    }
+    if calibration.backup && calibration.step != CalibrationStep::Measuring {
+        row = row.push(
+            button(text("Restore"))
+                .on_press(Message::JoyconRestoreCalibration(sn.to_owned()))
+                .style(theme::Button::Custom(Box::new(style::PrimaryButton))),
+        );
+    }
+    row
+}
+
+fn accel_calibration_row<'a>(sn: &str, sides: Option<usize>, calibrated: bool) -> Row<'a, Message> {
+    let mut row = Row::new().spacing(10).align_items(Alignment::Center).push(
+        text(match sides {
+            Some(sides) => {
+                format!("Lay it on each of its six sides until it is taken, {sides} of 6 so far...")
+            }
+            None if calibrated => "Accelerometer calibrated by wrangler.".to_string(),
+            None => "Accelerometer calibration".to_string(),
+        })
+        .width(Length::Fill),
+    );
+    if sides.is_some() {
+        return row.push(
+            button(text("Cancel"))
+                .on_press(Message::JoyconAccelCalibration(sn.to_owned(), false))
+                .style(theme::Button::Custom(Box::new(style::PrimaryButton))),
+        );
+    }
+    row = row.push(
+        button(text("Calibrate"))
+            .on_press(Message::JoyconAccelCalibration(sn.to_owned(), true))
+            .style(theme::Button::Custom(Box::new(style::PrimaryButton))),
+    );
+    if calibrated {
+        row = row.push(
+            button(text("Reset"))
+                .on_press(Message::JoyconAccelCalibrationReset(sn.to_owned()))
+                .style(theme::Button::Custom(Box::new(style::PrimaryButton))),
+        );
+    }
+    row
+}
+
+// A Bluetooth adapter connects 7 controllers at most, most get laggy well before that.
+const BLUETOOTH_LIMIT: usize = 7;
+const BLUETOOTH_COMFORTABLE: usize = 4;
+
+fn bluetooth_advice(statuses: &[joycon::Status]) -> Option<String> {
+    let connected = statuses
+        .iter()
+        .filter(|status| status.status != DeviceStatus::Disconnected)
+        .count();
+    let laggy = statuses
+        .iter()
+        .any(|status| status.status == DeviceStatus::LaggyIMU);
+    if connected <= BLUETOOTH_LIMIT && !(laggy && connected > BLUETOOTH_COMFORTABLE) {
+        return None;
+    }
+    // Windows only uses one adapter at a time.
+    let fix = if cfg!(target_os = "linux") {
+        "Spread them over several adapters, on USB extension cables away from each other."
+    } else {
+        "Put the adapter on a USB extension cable close to where you play, away from other radios."
+    };
+    Some(format!(
+        "{connected} controllers are connected. A Bluetooth adapter handles up to {BLUETOOTH_LIMIT}, \
+        many lag with more than {BLUETOOTH_COMFORTABLE}. {fix}"
+    ))
+}
+
+fn firmware_row<'a>(firmware: Option<joycon::FirmwareVersion>) -> Row<'a, Message> {
+    let Some(version) = firmware else {
+        return Row::new().push(text("Firmware: unknown"));
+    };
+    let row = Row::new().push(text(format!("Firmware: {version}")));
+    match version.problem() {
+        Some(problem) => row.push(
+            container(text(format!(", {problem}. Update it on a Switch.")))
+                .style(style::text_orange as for<'r> fn(&'r _) -> _),
+        ),
+        None => row,
+    }
+}
+
+fn config_flags_row<'a>(settings: &WranglerSettings, serial_number: &str) -> Row<'a, Message> {
+    let flags = settings.joycon_config_flags_get(serial_number);
+    if flags.is_empty() {
+        return Row::new();
+    }
+    let names: Vec<_> = flags
+        .iter()
+        .map(|(&flag, &state)| {
+            let state = if state { "on" } else { "off" };
+            format!("{} {state}", settings::config_flag_name(flag))
+        })
+        .collect();
+    let row = Row::new().push(text(format!("Set by the server: {}", names.join(", "))));
+    if flags.get(&settings::MAGNETOMETER_FLAG) == Some(&true) {
+        row.push(text(", Joy-Cons have no magnetometer"))
+    } else {
+        row
+    }
+}
+
 fn single_box_view<'a>(
     status: &joycon::Status,
-    svg_handler: &joycon::Svg,
+    svg_handler: &svg::Svg,
     needles: &'a [Needle],
-    scale: f64,
-    mount_rot: i32,
+    settings: &WranglerSettings,
+    two_pose: Option<MountingStep>,
 ) -> Column<'a, Message> {
     let sn = status.serial_number.clone();
+    let scale = settings.joycon_scale_get(&sn);
+    let mount_rot = settings.joycon_rotation_get(&sn);
+    let mounting = settings.joycon_mounting_get(&sn);
+    let euler = euler_readout(&status.orientation, settings);
 
     let buttons = Row::new()
         .spacing(10)
@@ -435,14 +2598,11 @@ fn single_box_view<'a>(
             .collect(),
     );
 
-    let circle = circle(
-        8.0,
-        match status.status {
-            DeviceStatus::Disconnected | DeviceStatus::NoIMU => Color::from_rgb8(0xff, 0x38, 0x4A),
-            DeviceStatus::LaggyIMU => Color::from_rgb8(0xff, 0xe3, 0x3c),
-            DeviceStatus::Healthy => Color::from_rgb8(0x3d, 0xff, 0x81),
-        },
-    );
+    let circle = Column::new()
+        .spacing(5)
+        .align_items(Alignment::Center)
+        .push(circle(8.0, status_color(status.status)))
+        .push(text(status_icon(status.status)).size(14));
 
     let top = Row::new()
         .spacing(5)
@@ -460,12 +2620,223 @@ fn single_box_view<'a>(
 
     let status_text = container(text(format!("{}", status.status))).style(match status.status {
         DeviceStatus::Disconnected | DeviceStatus::NoIMU => style::text_orange,
-        DeviceStatus::LaggyIMU => style::text_yellow,
+        DeviceStatus::LaggyIMU | DeviceStatus::Asleep => style::text_yellow,
         DeviceStatus::Healthy => style::text_green,
     });
 
-    let bottom = Column::new()
+    let mounting_row = Row::new()
+        .spacing(10)
+        .align_items(Alignment::Center)
+        .push(text(format!("Offset: {}", euler_readout(&mounting, settings))).width(Length::Fill))
+        .push(
+            button(text("Set neutral"))
+                .on_press(Message::JoyconCaptureMounting(sn.clone()))
+                .style(theme::Button::Custom(Box::new(style::PrimaryButton))),
+        )
+        .push(
+            button(text("Clear"))
+                .on_press(Message::JoyconClearMounting(sn.clone()))
+                .style(theme::Button::Custom(Box::new(style::PrimaryButton))),
+        );
+
+    let two_pose_row = match two_pose {
+        None => Row::new()
+            .spacing(10)
+            .align_items(Alignment::Center)
+            .push(text("Two-pose calibration").width(Length::Fill))
+            .push(
+                button(text("Start"))
+                    .on_press(Message::JoyconTwoPoseStarted(sn.clone()))
+                    .style(theme::Button::Custom(Box::new(style::PrimaryButton))),
+            ),
+        Some(step) => Row::new()
+            .spacing(10)
+            .align_items(Alignment::Center)
+            .push(
+                text(match step {
+                    MountingStep::Standing => "Stand straight, then capture.",
+                    MountingStep::Bent => "Bend forward 90°, then capture.",
+                })
+                .width(Length::Fill),
+            )
+            .push(
+                button(text("Capture"))
+                    .on_press(Message::JoyconTwoPoseCaptured)
+                    .style(theme::Button::Custom(Box::new(style::PrimaryButton))),
+            )
+            .push(
+                button(text("Cancel"))
+                    .on_press(Message::JoyconTwoPoseCancelled)
+                    .style(theme::Button::Custom(Box::new(style::PrimaryButton))),
+            ),
+    };
+
+    let nickname = {
+        let sn = sn.clone();
+        text_input("Nickname", settings.joycon_nickname_get(&sn))
+            .on_input(move |name| Message::JoyconNickname(sn.clone(), name))
+            .padding(5)
+    };
+    let group = {
+        let sn = sn.clone();
+        text_input("Group, like legs", settings.joycon_group_get(&sn))
+            .on_input(move |group| Message::JoyconGroup(sn.clone(), group))
+            .padding(5)
+    };
+    let accent = settings.joycon_accent_get(&sn);
+    let mut accents = Row::new()
+        .spacing(5)
+        .align_items(Alignment::Center)
+        .push(text("Color:").width(Length::Fill));
+    for (i, &(r, g, b)) in ACCENT_COLORS.iter().enumerate() {
+        let selected = accent == Some(i as u8);
+        accents = accents.push(
+            button(text(" "))
+                .width(Length::Fixed(20.0))
+                .height(Length::Fixed(20.0))
+                .on_press(Message::JoyconAccent(
+                    sn.clone(),
+                    (!selected).then_some(i as u8),
+                ))
+                .style(theme::Button::Custom(Box::new(style::Swatch(
+                    Color::from_rgb8(r, g, b),
+                    selected,
+                )))),
+        );
+    }
+    // The player lights as the controller shows them, to tell which one it is.
+    let shows_accent = matches!(
+        settings.lights.streaming.player,
+        PlayerLights::Accent | PlayerLights::AccentBlink
+    );
+    let player_lights = match accent_color(accent) {
+        Some(color) if shows_accent => {
+            let mask = PlayerLights::accent_mask(accent);
+            let mut lights = Row::new()
+                .spacing(5)
+                .align_items(Alignment::Center)
+                .push(text("Player lights:").width(Length::Fill));
+            for bit in 0..4 {
+                let on = mask & (1 << bit) != 0;
+                lights = lights.push(circle::circle(
+                    5.0,
+                    if on {
+                        color
+                    } else {
+                        Color::from_rgb(0.3, 0.3, 0.3)
+                    },
+                ));
+            }
+            Some(lights)
+        }
+        _ => None,
+    };
+    let frame_mode = {
+        let sn = sn.clone();
+        Row::new()
+            .spacing(10)
+            .align_items(Alignment::Center)
+            .push(text("IMU frames:").width(Length::Fill))
+            .push(pick_list(
+                &FrameMode::ALL[..],
+                Some(settings.joycon_frame_mode_get(&sn)),
+                move |mode| Message::JoyconFrameMode(sn.clone(), mode),
+            ))
+    };
+    let fusion = {
+        let sn = sn.clone();
+        Row::new()
+            .spacing(10)
+            .align_items(Alignment::Center)
+            .push(text("Sensor fusion:").width(Length::Fill))
+            .push(pick_list(
+                &Fusion::ALL[..],
+                Some(settings.joycon_fusion_get(&sn)),
+                move |fusion| Message::JoyconFusion(sn.clone(), fusion),
+            ))
+    };
+    let mut outputs = Row::new()
+        .spacing(10)
+        .align_items(Alignment::Center)
+        .push(text("Send to:").width(Length::Fill));
+    // Only the outputs that are on, the others are picked in the settings.
+    for output in Output::ALL.into_iter().filter(|&o| settings.output_get(o)) {
+        let sn = sn.clone();
+        outputs = outputs.push(checkbox(
+            output.to_string(),
+            settings.joycon_output_get(&sn, output),
+            move |on| Message::JoyconOutput(sn.clone(), output, on),
+        ));
+    }
+    let mut bottom = Column::new()
+        .spacing(10)
+        .push(nickname)
+        .push(group)
+        .push(accents);
+    if let Some(player_lights) = player_lights {
+        bottom = bottom.push(player_lights);
+    }
+    bottom = bottom.push(frame_mode).push(fusion);
+    if let Some(gain) = settings.joycon_fusion_gain_get(&sn) {
+        bottom = bottom.push(fusion_gain_row(&sn, settings.joycon_fusion_get(&sn), gain));
+    }
+    bottom = bottom
+        .push(outputs)
+        .push(text(euler))
+        .push(mounting_row)
+        .push(yaw_trim_row(&sn, settings.joycon_yaw_trim_get(&sn)))
+        .push(two_pose_row);
+    if status.flat {
+        bottom = bottom.push(
+            Row::new()
+                .spacing(10)
+                .align_items(Alignment::Center)
+                .push(text("Lying flat.").width(Length::Fill))
+                .push(
+                    button(text("Level"))
+                        .on_press(Message::JoyconLevel(sn.clone()))
+                        .style(theme::Button::Custom(Box::new(style::PrimaryButton))),
+                ),
+        );
+    }
+    if status.frozen {
+        bottom = bottom.push(
+            container(text(
+                "Frozen: the server keeps getting the rotation it had. Unfreeze to continue at the same heading.",
+            ))
+            .style(style::text_yellow as for<'r> fn(&'r _) -> _),
+        );
+    }
+    if let Some(calibration) = status.calibration {
+        bottom = bottom.push(calibration_row(&sn, calibration));
+    }
+    bottom = bottom.push(accel_calibration_row(
+        &sn,
+        status.accel_calibration,
+        settings.joycon_accel_calibration_get(&sn).is_some(),
+    ));
+    let status_row = Row::new()
         .spacing(10)
+        .align_items(Alignment::Center)
+        .push(text("Status: "))
+        .push(status_text)
+        .push(horizontal_space(Length::Fill))
+        .push(
+            button(text("Identify"))
+                .on_press(Message::JoyconIdentify(sn.clone()))
+                .style(theme::Button::Custom(Box::new(style::PrimaryButton))),
+        )
+        .push(
+            button(text(if status.frozen { "Unfreeze" } else { "Freeze" }))
+                .on_press(Message::JoyconFrozen(sn.clone(), !status.frozen))
+                .style(theme::Button::Custom(Box::new(style::PrimaryButton))),
+        )
+        .push(
+            button(text("Block"))
+                .on_press(Message::JoyconBlocked(sn.clone(), true))
+                .style(theme::Button::Custom(Box::new(style::PrimaryButton))),
+        );
+    let bottom = bottom
         .push(
             slider(0.8..=1.2, scale, move |c| {
                 Message::JoyconScale(sn.clone(), c)
@@ -479,8 +2850,28 @@ fn single_box_view<'a>(
             )
             .size(14),
         )
+        .push(text(match status.drift {
+            Some(drift) => format!("Drift: {drift:.2}°/min"),
+            None => "Drift: not measured yet, leave the tracker still.".to_string(),
+        }))
+        .push(text(match status.temperature {
+            Some(temperature) => format!("IMU temperature: {temperature:.1}°C"),
+            None => "IMU temperature: not reported.".to_string(),
+        }))
+        .push(firmware_row(status.firmware))
+        .push(config_flags_row(settings, &status.serial_number))
         .push(Row::new().push(text("Battery level: ")).push(battery_text))
-        .push(Row::new().push(text("Status: ")).push(status_text));
+        .push(
+            Row::new().push(text("Link: ")).push(
+                container(text(format!(
+                    "{} reports/s{}",
+                    status.rate,
+                    link_text(status).map_or(String::new(), |link| format!(", {link}"))
+                )))
+                .style(link_style(status)),
+            ),
+        )
+        .push(status_row);
 
     Column::new().spacing(10).push(top).push(bottom)
 }
@@ -51,6 +51,43 @@ fn text_base(bg: Color) -> container::Appearance {
     }
 }
 
+/// Item border in a tracker's accent color.
+pub struct Accent(pub Color);
+
+impl container::StyleSheet for Accent {
+    type Style = Theme;
+    fn appearance(&self, _style: &Self::Style) -> container::Appearance {
+        container::Appearance {
+            border_width: 3.0,
+            border_color: self.0,
+            ..item_base(Color::from_rgb8(0x36, 0x39, 0x3F))
+        }
+    }
+}
+
+/// Small square color picker button.
+pub struct Swatch(pub Color, pub bool);
+
+impl button::StyleSheet for Swatch {
+    type Style = Theme;
+    fn active(&self, _style: &Self::Style) -> button::Appearance {
+        button::Appearance {
+            background: self.0.into(),
+            border_radius: 3.0,
+            border_width: if self.1 { 2.0 } else { 0.0 },
+            border_color: Color::WHITE,
+            ..button::Appearance::default()
+        }
+    }
+
+    fn hovered(&self, style: &Self::Style) -> button::Appearance {
+        button::Appearance {
+            border_width: if self.1 { 2.0 } else { 1.0 },
+            ..self.active(style)
+        }
+    }
+}
+
 pub struct PrimaryButton;
 
 impl button::StyleSheet for PrimaryButton {
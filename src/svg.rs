@@ -1,4 +1,5 @@
 use iced::widget::svg::Handle;
+use slimevr_wrangler_core::joycon::{JoyconDesign, JoyconDesignType};
 use std::{
     cell::RefCell,
     collections::{
@@ -7,22 +8,9 @@ use std::{
     },
 };
 
-static LEFT: &str = include_str!("../../assets/joycon-left.svg");
-static RIGHT: &str = include_str!("../../assets/joycon-right.svg");
-static PRO: &str = include_str!("../../assets/pro-controller.svg");
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum JoyconDesignType {
-    Left,
-    Right,
-    Pro,
-}
-
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct JoyconDesign {
-    pub color: String,
-    pub design_type: JoyconDesignType,
-}
+static LEFT: &str = include_str!("../assets/joycon-left.svg");
+static RIGHT: &str = include_str!("../assets/joycon-right.svg");
+static PRO: &str = include_str!("../assets/pro-controller.svg");
 
 fn generate(design: &JoyconDesign, rotation: i32) -> Handle {
     let svg_code = match design.design_type {